@@ -1,13 +1,13 @@
-use search_client::AzureSearchClient;
+use search_client::SearchClient;
 
 pub struct AzureContext {
-    pub products_client: AzureSearchClient,
-    pub bmgf_client: AzureSearchClient,
+    pub products_client: SearchClient,
+    pub bmgf_client: SearchClient,
 }
 
 pub fn create_context(products_index: String, bmgf_index: String) -> AzureContext {
-    let products_client = AzureSearchClient::new_with_index(products_index);
-    let bmgf_client = AzureSearchClient::new_with_index(bmgf_index);
+    let products_client = SearchClient::new_with_index(products_index);
+    let bmgf_client = SearchClient::new_with_index(bmgf_index);
     AzureContext {
         products_client,
         bmgf_client,
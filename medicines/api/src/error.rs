@@ -0,0 +1,150 @@
+use crate::{
+    pagination::PaginationError,
+    query_budget::BudgetExceeded,
+    query_objects::{
+        medicine_levels_in_pregnancy::report::TooManyReports, shared::created::CreatedRangeError,
+    },
+};
+use async_graphql::FieldError;
+use search_client::{SearchError, SearchErrorKind};
+use serde_json::json;
+use std::fmt::{Display, Formatter};
+
+// Sent as `extensions.code`, so that clients can tell errors apart without parsing their messages.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorCode {
+    BadUserInput,
+    NotFound,
+    UpstreamUnavailable,
+    UpstreamThrottled,
+    BudgetExceeded,
+    RateLimited,
+    TooManyResults,
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorCode::BadUserInput => write!(f, "BAD_USER_INPUT"),
+            ErrorCode::NotFound => write!(f, "NOT_FOUND"),
+            ErrorCode::UpstreamUnavailable => write!(f, "UPSTREAM_UNAVAILABLE"),
+            ErrorCode::UpstreamThrottled => write!(f, "UPSTREAM_THROTTLED"),
+            ErrorCode::BudgetExceeded => write!(f, "QUERY_BUDGET_EXCEEDED"),
+            ErrorCode::RateLimited => write!(f, "RATE_LIMITED"),
+            ErrorCode::TooManyResults => write!(f, "TOO_MANY_RESULTS"),
+        }
+    }
+}
+
+pub fn field_error(code: ErrorCode, message: impl Display) -> FieldError {
+    FieldError(
+        message.to_string(),
+        Some(json!({ "code": code.to_string() })),
+    )
+}
+
+pub fn bad_user_input(message: impl Display) -> FieldError {
+    field_error(ErrorCode::BadUserInput, message)
+}
+
+pub fn not_found(message: impl Display) -> FieldError {
+    field_error(ErrorCode::NotFound, message)
+}
+
+// Anything the client didn't cause is logged here, as the client is only told which kind of error it was.
+pub fn to_field_error(e: &anyhow::Error) -> FieldError {
+    if let Some(e) = e.downcast_ref::<PaginationError>() {
+        return bad_user_input(e);
+    }
+    if let Some(e) = e.downcast_ref::<CreatedRangeError>() {
+        return bad_user_input(e);
+    }
+    if let Some(e) = e.downcast_ref::<BudgetExceeded>() {
+        return field_error(ErrorCode::BudgetExceeded, e);
+    }
+    if let Some(e) = e.downcast_ref::<TooManyReports>() {
+        return field_error(ErrorCode::TooManyResults, e);
+    }
+
+    match e.downcast_ref::<SearchError>().map(SearchError::kind) {
+        Some(SearchErrorKind::BadRequest) => {
+            tracing::warn!("Azure search service rejected the request: {:?}", e);
+            bad_user_input("The search could not be understood")
+        }
+        Some(SearchErrorKind::Throttled) => {
+            tracing::warn!("Azure search service is throttling requests: {:?}", e);
+            let retry_after = e
+                .downcast_ref::<SearchError>()
+                .and_then(SearchError::retry_after);
+            FieldError(
+                "Too many requests, try again later".to_string(),
+                Some(json!({
+                    "code": ErrorCode::UpstreamThrottled.to_string(),
+                    "retryAfter": retry_after.map(|retry_after| retry_after.as_secs()),
+                })),
+            )
+        }
+        _ => {
+            tracing::error!("Error fetching results from Azure search service: {:?}", e);
+            field_error(ErrorCode::UpstreamUnavailable, "Error retrieving results")
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::query_budget::QueryBudget;
+    use anyhow::anyhow;
+    use reqwest::StatusCode;
+    use std::time::Duration;
+    use test_case::test_case;
+
+    fn http_error(status: StatusCode) -> anyhow::Error {
+        SearchError::Http {
+            status,
+            body: String::new(),
+            retry_after: Some(Duration::from_secs(5)),
+        }
+        .into()
+    }
+
+    fn code(error: &FieldError) -> Option<String> {
+        error
+            .1
+            .as_ref()
+            .and_then(|extensions| extensions["code"].as_str())
+            .map(String::from)
+    }
+
+    #[test_case(PaginationError::InvalidCursor.into(), ErrorCode::BadUserInput)]
+    #[test_case(CreatedRangeError::Empty.into(), ErrorCode::BadUserInput)]
+    #[test_case(QueryBudget::new(0).spend(1).unwrap_err().into(), ErrorCode::BudgetExceeded)]
+    #[test_case(http_error(StatusCode::BAD_REQUEST), ErrorCode::BadUserInput)]
+    #[test_case(
+        http_error(StatusCode::TOO_MANY_REQUESTS),
+        ErrorCode::UpstreamThrottled
+    )]
+    #[test_case(
+        http_error(StatusCode::SERVICE_UNAVAILABLE),
+        ErrorCode::UpstreamThrottled
+    )]
+    #[test_case(http_error(StatusCode::BAD_GATEWAY), ErrorCode::UpstreamUnavailable)]
+    #[test_case(SearchError::CircuitOpen.into(), ErrorCode::UpstreamUnavailable)]
+    #[test_case(anyhow!("something else"), ErrorCode::UpstreamUnavailable)]
+    fn test_to_field_error(e: anyhow::Error, expected: ErrorCode) {
+        assert_eq!(code(&to_field_error(&e)), Some(expected.to_string()));
+    }
+
+    #[test]
+    fn test_throttled_errors_say_when_to_retry() {
+        let error = to_field_error(&http_error(StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(error.1.unwrap()["retryAfter"], 5);
+    }
+
+    #[test]
+    fn test_upstream_errors_hide_their_details() {
+        let error = to_field_error(&anyhow!("secret connection string"));
+        assert_eq!(error.0, "Error retrieving results");
+    }
+}
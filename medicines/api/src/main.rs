@@ -1,4 +1,9 @@
-use crate::azure_context::create_context;
+use crate::{
+    azure_context::create_context,
+    query_budget::QueryBudget,
+    query_objects::products::document_loader::DocumentLoader,
+    rate_limit::{parse_trusted_proxies, rate_limit, too_many_requests, RateLimited, RateLimiter},
+};
 use anyhow::anyhow;
 use async_graphql::{
     http::{playground_source, GraphQLPlaygroundConfig},
@@ -6,7 +11,7 @@ use async_graphql::{
 };
 use async_graphql_warp::{BadRequest, GQLResponse};
 use core::fmt::Display;
-use std::{convert::Infallible, env, net::SocketAddr, str::FromStr};
+use std::{convert::Infallible, env, net::SocketAddr, str::FromStr, sync::Arc};
 use tracing::Level;
 use warp::{
     self,
@@ -15,11 +20,20 @@ use warp::{
 };
 
 mod azure_context;
+mod error;
 mod pagination;
+mod query_budget;
 mod query_objects;
+mod rate_limit;
 mod schema;
 
 const PORT: u16 = 8000;
+// Deep enough for the introspection query the playground sends, with its nested `ofType` fields.
+const MAX_QUERY_DEPTH: usize = 15;
+const MAX_QUERY_COMPLEXITY: usize = 250;
+const QUERY_BUDGET: u32 = 50;
+const RATE_LIMIT_BURST: u32 = 60;
+const RATE_LIMIT_PER_SECOND: f64 = 2.0;
 
 pub fn healthz() -> impl Filter<Extract = impl Reply, Error = Rejection> + Copy {
     warp::path!("healthz")
@@ -40,7 +54,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let products_index = get_env_or_default("AZURE_SEARCH_INDEX", "products-index".to_string());
     let bmgf_index = get_env_or_default("BMGF_AZURE_SEARCH_INDEX", "bmgf-index".to_string());
-    let schema = schema::ApiSchema::new(create_context(products_index, bmgf_index));
+    let schema = schema::ApiSchema::new(
+        create_context(products_index, bmgf_index),
+        get_env_or_default("MAX_QUERY_DEPTH", MAX_QUERY_DEPTH),
+        get_env_or_default("MAX_QUERY_COMPLEXITY", MAX_QUERY_COMPLEXITY),
+    );
+    let query_budget = get_env_or_default("QUERY_BUDGET", QUERY_BUDGET);
+    let rate_limiter = Arc::new(RateLimiter::new(
+        get_env_or_default("RATE_LIMIT_BURST", RATE_LIMIT_BURST),
+        get_env_or_default("RATE_LIMIT_PER_SECOND", RATE_LIMIT_PER_SECOND),
+    ));
+    let trusted_proxies = Arc::new(parse_trusted_proxies(&get_env_or_default(
+        "RATE_LIMIT_TRUSTED_PROXIES",
+        String::new(),
+    ))?);
 
     let cors = warp::cors()
         .allow_methods(vec![Method::GET, Method::POST])
@@ -54,9 +81,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let addr = format!("0.0.0.0:{}", get_env_or_default("PORT", PORT.to_string()))
         .parse::<SocketAddr>()?;
 
-    let graphql_post = async_graphql_warp::graphql(schema.0)
-        .and_then(|(schema, builder): (_, QueryBuilder)| async move {
-            let response = builder.execute(&schema).await;
+    let graphql_post = rate_limit(rate_limiter, trusted_proxies)
+        .and(async_graphql_warp::graphql(schema.0))
+        .and_then(move |(schema, builder): (_, QueryBuilder)| async move {
+            let response = builder
+                .data(DocumentLoader::default())
+                .data(QueryBudget::new(query_budget))
+                .execute(&schema)
+                .await;
             Ok::<_, Infallible>(GQLResponse::from(response))
         })
         .with(cors.clone());
@@ -77,17 +109,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .or(graphql_options)
         .or(graphql_post)
         .recover(|err: Rejection| async move {
+            if let Some(rate_limited) = err.find::<RateLimited>() {
+                return Ok::<_, Infallible>(
+                    Box::new(too_many_requests(rate_limited)) as Box<dyn Reply>
+                );
+            }
+
             if let Some(BadRequest(err)) = err.find() {
-                return Ok::<_, Infallible>(warp::reply::with_status(
+                return Ok(Box::new(warp::reply::with_status(
                     err.to_string(),
                     StatusCode::BAD_REQUEST,
-                ));
+                )) as Box<dyn Reply>);
             }
 
-            Ok(warp::reply::with_status(
+            Ok(Box::new(warp::reply::with_status(
                 "INTERNAL_SERVER_ERROR".to_string(),
                 StatusCode::INTERNAL_SERVER_ERROR,
-            ))
+            )) as Box<dyn Reply>)
         });
 
     let _ = tokio::join!(tokio::spawn(async move {
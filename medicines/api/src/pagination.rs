@@ -1,4 +1,15 @@
 use async_graphql::SimpleObject;
+use search_client::{filter::Filter, sort::OrderBy};
+use std::fmt::{Display, Formatter};
+
+pub const DEFAULT_PAGE_SIZE: i32 = 10;
+// Each result asked for may need fetching from Azure Search, so no page can be larger than this.
+pub const MAX_PAGE_SIZE: i32 = 100;
+// Azure Search won't skip past more results than this.
+pub const MAX_OFFSET: i32 = 100_000;
+
+// Changed whenever the layout of cursors changes, so that old cursors are rejected rather than misread.
+const CURSOR_VERSION: &str = "v1";
 
 // Based upon: https://relay.dev/graphql/connections.htm#sec-undefined.PageInfo
 #[SimpleObject]
@@ -6,17 +17,22 @@ use async_graphql::SimpleObject;
 pub struct PageInfo {
     pub has_previous_page: bool,
     pub has_next_page: bool,
-    pub start_cursor: String,
-    pub end_cursor: String,
+    pub start_cursor: Option<String>,
+    pub end_cursor: Option<String>,
 }
 
 impl PageInfo {
-    pub fn build(offset: i32, result_count: i32, total_count: i32) -> Self {
-        let has_previous_page = offset != 0;
+    pub fn build(fingerprint: &str, offset: i32, result_count: i32, total_count: i32) -> Self {
+        let has_previous_page = offset > 0;
         let has_next_page = offset + result_count < total_count;
-        let start_cursor = base64::encode(offset.to_string());
-        let end_cursor =
-            base64::encode(std::cmp::min(total_count, offset + result_count - 1).to_string());
+        let (start_cursor, end_cursor) = if result_count > 0 {
+            (
+                Some(encode_cursor(fingerprint, offset)),
+                Some(encode_cursor(fingerprint, offset + result_count - 1)),
+            )
+        } else {
+            (None, None)
+        };
 
         PageInfo {
             has_previous_page,
@@ -27,21 +43,170 @@ impl PageInfo {
     }
 }
 
-pub fn get_offset_or_default(skip: Option<i32>, after: Option<String>, default: i32) -> i32 {
-    match (after, skip) {
-        (Some(encoded), _) => match convert_after_to_offset(encoded) {
-            Ok(a) => a,
-            _ => default,
-        },
-        (None, Some(offset)) => offset,
-        _ => default,
+#[derive(Debug, PartialEq)]
+pub enum PaginationError {
+    InvalidCursor,
+    CursorFromAnotherQuery,
+    NegativeCount,
+    CountTooLarge,
+    OffsetTooLarge,
+    LastWithoutBefore,
+}
+
+impl Display for PaginationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaginationError::InvalidCursor => write!(f, "The cursor is not valid"),
+            PaginationError::CursorFromAnotherQuery => write!(
+                f,
+                "The cursor belongs to a query with a different search, filters or order"
+            ),
+            PaginationError::NegativeCount => write!(f, "first and last can't be negative"),
+            PaginationError::CountTooLarge => {
+                write!(f, "first and last can't be more than {}", MAX_PAGE_SIZE)
+            }
+            PaginationError::OffsetTooLarge => {
+                write!(f, "Results can't be paged past the first {}", MAX_OFFSET)
+            }
+            PaginationError::LastWithoutBefore => write!(f, "last needs a before cursor here"),
+        }
+    }
+}
+
+impl std::error::Error for PaginationError {}
+
+// Identifies a query by everything that decides which results it returns and in what order, so that a
+// cursor from one query can't be used to page through another.
+pub fn fingerprint(search: &str, filter: Option<&Filter>, order_by: &[OrderBy]) -> String {
+    let filter = filter.map(Filter::to_string).unwrap_or_default();
+    let order_by = order_by
+        .iter()
+        .map(OrderBy::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!("{:016x}", fnv1a(&[search.trim(), &filter, &order_by]))
+}
+
+// FNV-1a, which unlike `DefaultHasher` hashes the same way whichever version of Rust the API is built with.
+fn fnv1a(parts: &[&str]) -> u64 {
+    parts
+        .iter()
+        .flat_map(|part| part.bytes().chain(std::iter::once(0)))
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+pub fn encode_cursor(fingerprint: &str, offset: i32) -> String {
+    base64::encode(format!("{}:{}:{}", CURSOR_VERSION, fingerprint, offset))
+}
+
+pub fn decode_cursor(cursor: &str, fingerprint: &str) -> Result<i32, PaginationError> {
+    let bytes = base64::decode(cursor).map_err(|_| PaginationError::InvalidCursor)?;
+    let decoded = std::str::from_utf8(&bytes).map_err(|_| PaginationError::InvalidCursor)?;
+    let mut parts = decoded.splitn(3, ':');
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(CURSOR_VERSION), Some(cursor_fingerprint), Some(offset)) => {
+            if cursor_fingerprint != fingerprint {
+                return Err(PaginationError::CursorFromAnotherQuery);
+            }
+            match offset.parse::<i32>() {
+                Ok(offset) if offset >= 0 && offset <= MAX_OFFSET => Ok(offset),
+                _ => Err(PaginationError::InvalidCursor),
+            }
+        }
+        _ => Err(PaginationError::InvalidCursor),
+    }
+}
+
+// Relay's connection arguments, along with `skip` for jumping straight to an offset.
+#[derive(Debug, Default)]
+pub struct PageArgs {
+    pub first: Option<i32>,
+    pub after: Option<String>,
+    pub last: Option<i32>,
+    pub before: Option<String>,
+    pub skip: Option<i32>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Page {
+    // `count` results from `offset`.
+    Range { offset: i32, count: i32 },
+    // The last `count` results from `offset` on, which can only be found once the total is known.
+    Last { offset: i32, count: i32 },
+}
+
+impl PageArgs {
+    pub fn page(&self, fingerprint: &str) -> Result<Page, PaginationError> {
+        check_count(self.first)?;
+        check_count(self.last)?;
+
+        let start = match &self.after {
+            Some(after) => decode_cursor(after, fingerprint)?
+                .checked_add(1)
+                .ok_or(PaginationError::InvalidCursor)?,
+            None => self.skip.unwrap_or(0).max(0),
+        };
+        // Whether it came from `skip` or from the result after a cursor
+        if start > MAX_OFFSET {
+            return Err(PaginationError::OffsetTooLarge);
+        }
+        let end = match &self.before {
+            Some(before) => Some(decode_cursor(before, fingerprint)?.max(start)),
+            None => None,
+        };
+
+        // As in Relay, the results are cut down to `first` before taking the `last` of them.
+        let end = match (self.first, end) {
+            (Some(first), end) => {
+                let first_end = start
+                    .checked_add(first)
+                    .ok_or(PaginationError::OffsetTooLarge)?;
+                Some(end.map_or(first_end, |end| end.min(first_end)))
+            }
+            (None, end) => end,
+        };
+
+        Ok(match (self.last, end) {
+            (Some(last), Some(end)) => Page::Range {
+                offset: start.max(end - last),
+                count: last.min(end - start),
+            },
+            (Some(last), None) => Page::Last {
+                offset: start,
+                count: last,
+            },
+            (None, Some(end)) if self.first.is_some() => Page::Range {
+                offset: start,
+                count: end - start,
+            },
+            (None, Some(end)) => Page::Range {
+                offset: start,
+                count: DEFAULT_PAGE_SIZE.min(end - start),
+            },
+            (None, None) => Page::Range {
+                offset: start,
+                count: DEFAULT_PAGE_SIZE,
+            },
+        })
+    }
+}
+
+pub fn check_count(count: Option<i32>) -> Result<(), PaginationError> {
+    match count {
+        Some(count) if count < 0 => Err(PaginationError::NegativeCount),
+        Some(count) if count > MAX_PAGE_SIZE => Err(PaginationError::CountTooLarge),
+        _ => Ok(()),
     }
 }
 
-pub fn convert_after_to_offset(encoded: String) -> Result<i32, anyhow::Error> {
-    let bytes = base64::decode(encoded)?;
-    let string = std::str::from_utf8(&bytes)?;
-    Ok(string.parse::<i32>()? + 1)
+// The offset and count of the last `count` results from `offset` on, given how many results there are.
+pub fn last_window(offset: i32, count: i32, total_count: i32) -> (i32, i32) {
+    let start = offset.max(total_count - count);
+    (start, (total_count - start).max(0))
 }
 
 #[macro_export]
@@ -49,7 +214,14 @@ macro_rules! pagination {
     ($name:ident, $edgename:ident, $type:ty) => {
         pagination!($name, $edgename, $type, ());
     };
+    // Extra fields on the connection start out empty, and are set by the resolver which builds it.
+    ($name:ident, $edgename:ident, $type:ty, { $($(#[$meta:meta])* $field:ident: $field_type:ty),* $(,)? }) => {
+        pagination!(@connection $name, $edgename, $type, { $($(#[$meta])* $field: $field_type),* });
+    };
     ($name:ident, $edgename:ident, $type:ty, $context:ty) => {
+        pagination!(@connection $name, $edgename, $type, {});
+    };
+    (@connection $name:ident, $edgename:ident, $type:ty, { $($(#[$meta:meta])* $field:ident: $field_type:ty),* }) => {
         #[SimpleObject]
         pub struct $edgename {
             node: $type,
@@ -68,6 +240,7 @@ macro_rules! pagination {
             page_info: $crate::pagination::PageInfo,
             total_count: i32,
             edges: Vec<$edgename>,
+            $($(#[$meta])* $field: $field_type,)*
         }
 
         impl $name {
@@ -81,6 +254,7 @@ macro_rules! pagination {
                     page_info,
                     total_count,
                     edges,
+                    $($field: Default::default(),)*
                 }
             }
         }
@@ -90,14 +264,102 @@ macro_rules! pagination {
 #[cfg(test)]
 mod test {
     use super::*;
+    use test_case::test_case;
+
+    const FINGERPRINT: &str = "0123456789abcdef";
+
+    fn cursor(offset: i32) -> Option<String> {
+        Some(encode_cursor(FINGERPRINT, offset))
+    }
 
     #[test]
     fn test_page_info() {
-        let page_info = PageInfo::build(0, 10, 15);
+        let page_info = PageInfo::build(FINGERPRINT, 0, 10, 15);
+
+        assert!(page_info.has_next_page);
+        assert!(!page_info.has_previous_page);
+        assert_eq!(cursor(0), page_info.start_cursor);
+        assert_eq!(cursor(9), page_info.end_cursor);
+    }
+
+    #[test]
+    fn test_page_info_without_results() {
+        let page_info = PageInfo::build(FINGERPRINT, 20, 0, 15);
+
+        assert!(!page_info.has_next_page);
+        assert!(page_info.has_previous_page);
+        assert_eq!(None, page_info.start_cursor);
+        assert_eq!(None, page_info.end_cursor);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let filter = Filter::eq("doc_type", "Spc");
+        let order_by = [OrderBy::desc("created")];
+        let fingerprint = fingerprint("ibuprofen", Some(&filter), &order_by);
+
+        assert_eq!(fingerprint.len(), 16);
+        assert_eq!(
+            fingerprint,
+            super::fingerprint(" ibuprofen ", Some(&filter), &order_by)
+        );
+        assert_ne!(
+            fingerprint,
+            super::fingerprint("paracetamol", Some(&filter), &order_by)
+        );
+        assert_ne!(
+            fingerprint,
+            super::fingerprint("ibuprofen", None, &order_by)
+        );
+        assert_ne!(
+            fingerprint,
+            super::fingerprint("ibuprofen", Some(&filter), &[])
+        );
+    }
+
+    #[test_case(encode_cursor(FINGERPRINT, 1229), Ok(1229); "for a cursor from the same query")]
+    #[test_case(encode_cursor("fedcba9876543210", 9), Err(PaginationError::CursorFromAnotherQuery); "for a cursor from another query")]
+    #[test_case(base64::encode("9"), Err(PaginationError::InvalidCursor); "for a bare offset")]
+    #[test_case(base64::encode(format!("v0:{}:9", FINGERPRINT)), Err(PaginationError::InvalidCursor); "for an old version")]
+    #[test_case(base64::encode(format!("v1:{}:-1", FINGERPRINT)), Err(PaginationError::InvalidCursor); "for a negative offset")]
+    #[test_case(encode_cursor(FINGERPRINT, MAX_OFFSET), Ok(MAX_OFFSET); "for the largest offset")]
+    #[test_case(encode_cursor(FINGERPRINT, MAX_OFFSET + 1), Err(PaginationError::InvalidCursor); "for an offset past the largest")]
+    #[test_case(encode_cursor(FINGERPRINT, i32::MAX), Err(PaginationError::InvalidCursor); "for the largest number")]
+    #[test_case("not base64!".to_string(), Err(PaginationError::InvalidCursor); "for something else")]
+    fn test_decode_cursor(cursor: String, expected: Result<i32, PaginationError>) {
+        assert_eq!(decode_cursor(&cursor, FINGERPRINT), expected);
+    }
+
+    #[test_case(PageArgs::default(), Page::Range { offset: 0, count: 10 }; "for the default page")]
+    #[test_case(PageArgs { first: Some(5), skip: Some(10), ..PageArgs::default() }, Page::Range { offset: 10, count: 5 }; "for first with skip")]
+    #[test_case(PageArgs { first: Some(5), after: cursor(9), skip: Some(20), ..PageArgs::default() }, Page::Range { offset: 10, count: 5 }; "for first after a cursor")]
+    #[test_case(PageArgs { last: Some(5), before: cursor(20), ..PageArgs::default() }, Page::Range { offset: 15, count: 5 }; "for last before a cursor")]
+    #[test_case(PageArgs { last: Some(5), before: cursor(3), ..PageArgs::default() }, Page::Range { offset: 0, count: 3 }; "for last before a cursor near the start")]
+    #[test_case(PageArgs { after: cursor(4), before: cursor(8), ..PageArgs::default() }, Page::Range { offset: 5, count: 3 }; "for between two cursors")]
+    #[test_case(PageArgs { first: Some(10), last: Some(3), ..PageArgs::default() }, Page::Range { offset: 7, count: 3 }; "for first then last")]
+    #[test_case(PageArgs { last: Some(5), after: cursor(9), ..PageArgs::default() }, Page::Last { offset: 10, count: 5 }; "for last without before")]
+    fn test_page(args: PageArgs, expected: Page) {
+        assert_eq!(args.page(FINGERPRINT), Ok(expected));
+    }
+
+    #[test_case(PageArgs { first: Some(-1), ..PageArgs::default() }, PaginationError::NegativeCount)]
+    #[test_case(PageArgs { last: Some(-1), ..PageArgs::default() }, PaginationError::NegativeCount)]
+    #[test_case(PageArgs { first: Some(101), ..PageArgs::default() }, PaginationError::CountTooLarge)]
+    #[test_case(PageArgs { last: Some(10000), ..PageArgs::default() }, PaginationError::CountTooLarge)]
+    #[test_case(PageArgs { after: Some(base64::encode("9")), ..PageArgs::default() }, PaginationError::InvalidCursor)]
+    #[test_case(PageArgs { before: Some(encode_cursor("fedcba9876543210", 9)), ..PageArgs::default() }, PaginationError::CursorFromAnotherQuery)]
+    #[test_case(PageArgs { first: Some(10), after: cursor(i32::MAX), ..PageArgs::default() }, PaginationError::InvalidCursor)]
+    #[test_case(PageArgs { first: Some(10), after: cursor(MAX_OFFSET), ..PageArgs::default() }, PaginationError::OffsetTooLarge)]
+    #[test_case(PageArgs { first: Some(10), skip: Some(MAX_OFFSET + 1), ..PageArgs::default() }, PaginationError::OffsetTooLarge)]
+    #[test_case(PageArgs { first: Some(10), skip: Some(i32::MAX), ..PageArgs::default() }, PaginationError::OffsetTooLarge)]
+    fn test_page_errors(args: PageArgs, expected: PaginationError) {
+        assert_eq!(args.page(FINGERPRINT), Err(expected));
+    }
 
-        assert_eq!(true, page_info.has_next_page);
-        assert_eq!(false, page_info.has_previous_page);
-        assert_eq!("MA==", page_info.start_cursor);
-        assert_eq!("OQ==", page_info.end_cursor);
+    #[test_case(0, 5, 12, (7, 5); "for the last results")]
+    #[test_case(10, 5, 12, (10, 2); "for fewer results than asked for")]
+    #[test_case(20, 5, 12, (20, 0); "for an offset past the end")]
+    fn test_last_window(offset: i32, count: i32, total_count: i32, expected: (i32, i32)) {
+        assert_eq!(last_window(offset, count, total_count), expected);
     }
 }
@@ -0,0 +1,172 @@
+use crate::{
+    azure_context::AzureContext,
+    error::{field_error, ErrorCode},
+    pagination::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE},
+};
+use async_graphql::{Context, FieldResult};
+use futures::{stream::BoxStream, TryStreamExt};
+use search_client::{SearchError, STREAM_PAGE_SIZE};
+use std::{
+    fmt::{Display, Formatter},
+    sync::atomic::{AtomicI64, Ordering},
+};
+
+// Fetching one result, or everything matching a filter, makes a single call to Azure Search.
+pub const CALL_COST: u32 = 1;
+// A product's summary takes a facet search, then a search for the latest document of each type.
+pub const SUMMARY_COST: u32 = 4 * CALL_COST;
+// Looking a document up by id tries it as a key, then as a content id if no document has that key.
+pub const LOOKUP_COST: u32 = 2 * CALL_COST;
+
+#[derive(Debug, PartialEq)]
+pub struct BudgetExceeded {
+    budget: u32,
+}
+
+impl Display for BudgetExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The query would cost more than {} to run; ask for fewer results, or split it into several queries",
+            self.budget
+        )
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+// How much calling Azure Search may cost while resolving one GraphQL request. Fields are resolved
+// concurrently, so they all spend from the same counter.
+pub struct QueryBudget {
+    budget: u32,
+    remaining: AtomicI64,
+}
+
+impl QueryBudget {
+    pub fn new(budget: u32) -> Self {
+        Self {
+            budget,
+            remaining: AtomicI64::new(i64::from(budget)),
+        }
+    }
+
+    pub fn spend(&self, cost: u32) -> Result<(), BudgetExceeded> {
+        let cost = i64::from(cost);
+
+        if self.remaining.fetch_sub(cost, Ordering::SeqCst) < cost {
+            return Err(BudgetExceeded {
+                budget: self.budget,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+// A page of results costs a call, plus one for every default-sized page of results asked for.
+pub fn page_cost(first: Option<i32>, last: Option<i32>) -> u32 {
+    let count = first
+        .or(last)
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .max(0)
+        .min(MAX_PAGE_SIZE);
+
+    results_cost(count)
+}
+
+// A call for `count` results, however many of them are handed back to the client.
+pub fn results_cost(count: i32) -> u32 {
+    CALL_COST + (count.max(0) / DEFAULT_PAGE_SIZE) as u32
+}
+
+// Streams fetch a page of results at a time, so each page is paid for before it's fetched. The stream only
+// asks for another page once it has handed out every result of a full one.
+pub async fn collect_pages<T>(
+    budget: &QueryBudget,
+    mut results: BoxStream<'_, Result<T, SearchError>>,
+) -> Result<Vec<T>, anyhow::Error> {
+    let mut collected = vec![];
+
+    loop {
+        if collected.len() % STREAM_PAGE_SIZE == 0 {
+            budget.spend(CALL_COST)?;
+        }
+        match results.try_next().await? {
+            Some(result) => collected.push(result),
+            None => return Ok(collected),
+        }
+    }
+}
+
+// Every field which calls Azure Search gets its clients from here, so that none of them can skip paying for it.
+pub fn azure_context<'a>(context: &'a Context<'_>, cost: u32) -> FieldResult<&'a AzureContext> {
+    context
+        .data::<QueryBudget>()?
+        .spend(cost)
+        .map_err(|e| field_error(ErrorCode::BudgetExceeded, e))?;
+    context.data::<AzureContext>()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::StreamExt;
+    use test_case::test_case;
+
+    #[test]
+    fn test_spend_within_budget() {
+        let budget = QueryBudget::new(5);
+
+        assert!(budget.spend(2).is_ok());
+        assert!(budget.spend(3).is_ok());
+    }
+
+    #[test]
+    fn test_spend_over_budget() {
+        let budget = QueryBudget::new(5);
+        budget.spend(4).unwrap();
+
+        assert_eq!(budget.spend(2), Err(BudgetExceeded { budget: 5 }));
+        assert!(budget.spend(1).is_err());
+    }
+
+    fn when_we_collect_pages(
+        budget: &QueryBudget,
+        count: usize,
+    ) -> Result<Vec<usize>, anyhow::Error> {
+        let results = futures::stream::iter((0..count).map(Ok::<_, SearchError>)).boxed();
+        tokio_test::block_on(collect_pages(budget, results))
+    }
+
+    #[test_case(0, 1; "for no results")]
+    #[test_case(STREAM_PAGE_SIZE - 1, 1; "for part of a page")]
+    #[test_case(STREAM_PAGE_SIZE, 2; "for a full page, as the next one is asked for")]
+    #[test_case(2 * STREAM_PAGE_SIZE + 1, 3; "for several pages")]
+    fn test_collect_pages_pays_for_each_page(count: usize, cost: u32) {
+        let budget = QueryBudget::new(cost);
+
+        assert_eq!(when_we_collect_pages(&budget, count).unwrap().len(), count);
+        assert!(budget.spend(1).is_err());
+    }
+
+    #[test]
+    fn test_collect_pages_stops_when_over_budget() {
+        let budget = QueryBudget::new(1);
+        let error = when_we_collect_pages(&budget, STREAM_PAGE_SIZE).unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<BudgetExceeded>(),
+            Some(&BudgetExceeded { budget: 1 })
+        );
+    }
+
+    #[test_case(None, None, 2; "for the default page size")]
+    #[test_case(Some(0), None, 1; "for no results")]
+    #[test_case(Some(5), None, 1; "for a small page")]
+    #[test_case(None, Some(30), 4; "for the last results")]
+    #[test_case(Some(100), None, 11; "for the largest page")]
+    #[test_case(Some(10000), None, 11; "for more than the largest page")]
+    fn test_page_cost(first: Option<i32>, last: Option<i32>, expected: u32) {
+        assert_eq!(page_cost(first, last), expected);
+    }
+}
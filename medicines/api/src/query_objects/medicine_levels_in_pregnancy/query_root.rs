@@ -1,13 +1,20 @@
 use crate::{
-    azure_context::AzureContext,
-    pagination::get_offset_or_default,
+    error::{bad_user_input, to_field_error},
+    pagination::PageArgs,
+    query_budget::{azure_context, page_cost, QueryBudget, CALL_COST, LOOKUP_COST},
     query_objects::medicine_levels_in_pregnancy::{
-        report::{get_reports, Reports},
+        report::{
+            get_report, get_reports, Report, ReportFacetField, ReportFilter, ReportOrderBy, Reports,
+        },
         substance::{get_substance, SubstanceReports},
     },
-    query_objects::shared::substances_index::{get_substances_index, SubstanceIndex},
+    query_objects::products::licence::parse_licence,
+    query_objects::shared::{
+        created::CreatedRange,
+        order_by::to_order_by,
+        substances_index::{get_substances_index, parse_letter, SubstanceIndex},
+    },
 };
-use anyhow::anyhow;
 use async_graphql::{Context, FieldResult, Object};
 
 pub struct MedicineLevelsInPregnancy {}
@@ -21,14 +28,10 @@ impl MedicineLevelsInPregnancy {
         name: Option<String>,
     ) -> FieldResult<SubstanceReports> {
         match name {
-            Some(name) => get_substance(name).await.map_err(|e| {
-                tracing::error!("Error fetching results from Azure search service: {:?}", e);
-                anyhow!("Error retrieving results").into()
-            }),
-            None => Err(anyhow::anyhow!(
-                "Getting a substance without providing a substance name is not supported."
-            )
-            .into()),
+            Some(name) => get_substance(name).await.map_err(|e| to_field_error(&e)),
+            None => Err(bad_user_input(
+                "Getting a substance without providing a substance name is not supported.",
+            )),
         }
     }
     #[field(
@@ -39,15 +42,24 @@ impl MedicineLevelsInPregnancy {
         context: &Context<'_>,
         letter: String,
     ) -> FieldResult<Vec<SubstanceIndex>> {
-        let context = context.data::<AzureContext>()?;
-        get_substances_index(&context.bmgf_client, letter.chars().next().unwrap())
+        let context = azure_context(context, CALL_COST)?;
+        let letter = parse_letter(&letter).map_err(bad_user_input)?;
+        get_substances_index(&context.bmgf_client, letter)
             .await
-            .map_err(|e| {
-                tracing::error!("Error fetching results from Azure search service: {:?}", e);
-                anyhow!("Error retrieving results").into()
-            })
+            .map_err(|e| to_field_error(&e))
+    }
+
+    #[field(
+        desc = "A report related to medicine levels in pregnancy, found by its id or its file name"
+    )]
+    async fn report(&self, context: &Context<'_>, id: String) -> FieldResult<Option<Report>> {
+        let context = azure_context(context, LOOKUP_COST)?;
+        get_report(&context.bmgf_client, &id)
+            .await
+            .map_err(|e| to_field_error(&e))
     }
 
+    #[allow(clippy::too_many_arguments)]
     #[field(desc = "Reports related to medicine levels in pregnancy")]
     async fn reports(
         &self,
@@ -56,22 +68,58 @@ impl MedicineLevelsInPregnancy {
         first: Option<i32>,
         skip: Option<i32>,
         after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        pregnancy_trimesters: Option<Vec<String>>,
+        matrices: Option<Vec<String>>,
+        pbpk_models: Option<Vec<String>>,
+        pl_numbers: Option<Vec<String>>,
+        created_after: Option<String>,
+        created_before: Option<String>,
+        order_by: Option<Vec<ReportOrderBy>>,
+        facets: Option<Vec<ReportFacetField>>,
     ) -> FieldResult<Reports> {
-        let context = context.data::<AzureContext>()?;
-        let offset = get_offset_or_default(skip, after, 0);
+        let budget = context.data::<QueryBudget>()?;
+        let context = azure_context(context, page_cost(first, last))?;
+        let pl_numbers = pl_numbers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|number| {
+                parse_licence(&number)
+                    .map(|licence| licence.canonical())
+                    .ok_or_else(|| {
+                        bad_user_input(format!("{} is not a recognised licence number", number))
+                    })
+            })
+            .collect::<Result<Vec<String>, _>>()?;
+        let filter = ReportFilter {
+            substance_name: None,
+            pregnancy_trimesters: pregnancy_trimesters.unwrap_or_default(),
+            matrices: matrices.unwrap_or_default(),
+            pbpk_models: pbpk_models.unwrap_or_default(),
+            pl_numbers,
+            created: CreatedRange::parse(created_after.as_deref(), created_before.as_deref())
+                .map_err(bad_user_input)?,
+        };
+        let page = PageArgs {
+            first,
+            after,
+            last,
+            before,
+            skip,
+        };
 
         get_reports(
             &context.bmgf_client,
+            budget,
             search.as_deref().unwrap_or(" "),
-            first,
-            offset,
-            None,
+            &page,
+            &filter,
+            &to_order_by(order_by),
+            &facets.unwrap_or_default(),
         )
         .await
         .map(Into::into)
-        .map_err(|e| {
-            tracing::error!("Error fetching results from Azure search service: {:?}", e);
-            anyhow!("Error retrieving results").into()
-        })
+        .map_err(|e| to_field_error(&e))
     }
 }
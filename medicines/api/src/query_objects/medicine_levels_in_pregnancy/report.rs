@@ -1,42 +1,146 @@
-use crate::{pagination, pagination::PageInfo};
-use async_graphql::SimpleObject;
+use crate::{
+    error::to_field_error,
+    pagination,
+    pagination::{fingerprint, last_window, Page, PageArgs, PageInfo},
+    query_budget::{azure_context, QueryBudget, CALL_COST},
+    query_objects::products::{
+        licence::parse_licence,
+        product::{get_related_products, Product},
+    },
+    query_objects::shared::{created::CreatedRange, facet::FacetCount, order_by::SortDirection},
+};
+use async_graphql::{Context, Enum, FieldResult, InputObject, Object, SimpleObject};
 use search_client::{
-    models::{ReportResult, ReportResults},
-    Search,
+    facet::FacetRequest,
+    filter::{Filter, Operator},
+    models::{FacetCounts, ReportResult, ReportResults},
+    sort::{OrderBy, RELEVANCE},
+    AzurePagination, Search,
 };
+use std::fmt::{Display, Formatter};
+
+// As many reports as Azure returns in one request. `stream_documents` only walks the products index, so
+// lists of reports which aren't paged through have to fit in one request.
+const MAX_REPORTS: i32 = 1000;
+
+#[derive(Debug, PartialEq)]
+pub struct TooManyReports {
+    count: i32,
+}
+
+impl Display for TooManyReports {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} reports match, more than the {} that can be listed at once; page through them with medicineLevelsInPregnancy.reports instead",
+            self.count, MAX_REPORTS
+        )
+    }
+}
+
+impl std::error::Error for TooManyReports {}
 
-#[SimpleObject(desc = "A report related to medicine levels in pregnancy")]
 #[derive(Debug, Clone, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Report {
-    #[field(desc = "Products associated with report")]
+    pub id: Option<String>,
     pub products: Option<Vec<String>>,
-    #[field(desc = "Active substances associated with report")]
     pub active_substances: Option<Vec<String>>,
-    #[field(desc = "Report name")]
     pub title: Option<String>,
-    #[field(desc = "Highlights")]
     pub highlights: Option<Vec<String>>,
-    #[field(desc = "File size")]
     pub file_size_in_bytes: Option<i32>,
-    #[field(desc = "PDF file name")]
     pub file_name: Option<String>,
-    #[field(desc = "PDF file url")]
     pub file_url: Option<String>,
-    #[field(desc = "Summary")]
     pub summary: Option<String>,
-    #[field(desc = "Matrices")]
     pub matrices: Option<Vec<String>>,
-    #[field(desc = "PL numbers")]
     pub pl_numbers: Option<Vec<String>>,
-    #[field(desc = "Pregnancy trimesters")]
     pub pregnancy_trimesters: Option<Vec<String>>,
-    #[field(desc = "PBPK models")]
     pub pbpk_models: Option<Vec<String>>,
 }
 
+#[Object(desc = "A report related to medicine levels in pregnancy")]
+impl Report {
+    #[field(desc = "Unique identifier of the report in the index")]
+    async fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    #[field(desc = "Products associated with report")]
+    async fn products(&self) -> Option<Vec<String>> {
+        self.products.clone()
+    }
+
+    #[field(desc = "Active substances associated with report")]
+    async fn active_substances(&self) -> Option<Vec<String>> {
+        self.active_substances.clone()
+    }
+
+    #[field(desc = "Report name")]
+    async fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    #[field(desc = "Highlights")]
+    async fn highlights(&self) -> Option<Vec<String>> {
+        self.highlights.clone()
+    }
+
+    #[field(desc = "File size")]
+    async fn file_size_in_bytes(&self) -> Option<i32> {
+        self.file_size_in_bytes
+    }
+
+    #[field(desc = "PDF file name")]
+    async fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    #[field(desc = "PDF file url")]
+    async fn file_url(&self) -> Option<&str> {
+        self.file_url.as_deref()
+    }
+
+    #[field(desc = "Summary")]
+    async fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    #[field(desc = "Matrices")]
+    async fn matrices(&self) -> Option<Vec<String>> {
+        self.matrices.clone()
+    }
+
+    #[field(desc = "PL numbers")]
+    async fn pl_numbers(&self) -> Option<Vec<String>> {
+        self.pl_numbers.clone()
+    }
+
+    #[field(desc = "Pregnancy trimesters")]
+    async fn pregnancy_trimesters(&self) -> Option<Vec<String>> {
+        self.pregnancy_trimesters.clone()
+    }
+
+    #[field(desc = "PBPK models")]
+    async fn pbpk_models(&self) -> Option<Vec<String>> {
+        self.pbpk_models.clone()
+    }
+
+    #[field(desc = "Products sharing a licence with the report, or named in it")]
+    async fn related_products(&self, context: &Context<'_>) -> FieldResult<Vec<Product>> {
+        let context = azure_context(context, CALL_COST)?;
+        get_related_products(
+            &context.products_client,
+            self.pl_numbers.as_deref().unwrap_or_default(),
+            self.products.as_deref().unwrap_or_default(),
+        )
+        .await
+        .map_err(|e| to_field_error(&e))
+    }
+}
+
 impl From<ReportResult> for Report {
     fn from(r: ReportResult) -> Self {
         Self {
+            id: r.id,
             products: r.products,
             active_substances: r.active_substances,
             title: Some(r.report_name),
@@ -56,58 +160,167 @@ impl From<ReportResult> for Report {
     }
 }
 
-pagination! {Reports, ReportEdge, Report}
+pagination! {Reports, ReportEdge, Report, {
+    #[field(desc = "Number of matching reports for each value of the requested fields")]
+    facets: Vec<ReportFacet>,
+}}
+
+#[Enum(desc = "A field reports can be sorted by")]
+#[derive(Debug)]
+pub enum ReportSortField {
+    #[item(desc = "How well the report matches the search")]
+    Relevance,
+    #[item(desc = "Created date")]
+    Created,
+    #[item(desc = "Report name")]
+    Title,
+}
+
+#[InputObject(desc = "A key to sort reports by")]
+#[derive(Clone, Debug)]
+pub struct ReportOrderBy {
+    #[field(desc = "Field to sort by")]
+    pub field: ReportSortField,
+    #[field(desc = "Direction to sort in")]
+    pub direction: SortDirection,
+}
+
+#[Enum(desc = "A field reports can be counted by")]
+#[derive(Debug)]
+pub enum ReportFacetField {
+    #[item(desc = "Pregnancy trimesters")]
+    PregnancyTrimester,
+    #[item(desc = "Matrices")]
+    Matrix,
+    #[item(desc = "PBPK models")]
+    PbpkModel,
+    #[item(desc = "PL numbers")]
+    ProductLicence,
+}
+
+impl ReportFacetField {
+    fn index_field(self) -> &'static str {
+        match self {
+            ReportFacetField::PregnancyTrimester => "pregnancy_trimesters",
+            ReportFacetField::Matrix => "matrices",
+            ReportFacetField::PbpkModel => "pbpk_models",
+            ReportFacetField::ProductLicence => "pl_numbers",
+        }
+    }
+}
+
+#[SimpleObject(desc = "The number of reports with each of the most common values of a field")]
+#[derive(Debug, PartialEq)]
+pub struct ReportFacet {
+    #[field(desc = "Field the reports are counted by")]
+    pub field: ReportFacetField,
+    #[field(desc = "Number of reports with each value, most common first")]
+    pub counts: Vec<FacetCount>,
+}
 
-fn get_report_edges(reports: Vec<Report>, offset: i32) -> Vec<ReportEdge> {
+// Reports match a filter when they have any of the values given for each of its fields.
+#[derive(Debug, Default)]
+pub struct ReportFilter {
+    pub substance_name: Option<String>,
+    pub pregnancy_trimesters: Vec<String>,
+    pub matrices: Vec<String>,
+    pub pbpk_models: Vec<String>,
+    pub pl_numbers: Vec<String>,
+    pub created: CreatedRange,
+}
+
+impl From<ReportOrderBy> for OrderBy {
+    fn from(order_by: ReportOrderBy) -> Self {
+        let field = match order_by.field {
+            ReportSortField::Relevance => RELEVANCE,
+            ReportSortField::Created => "created",
+            ReportSortField::Title => "report_name",
+        };
+        OrderBy {
+            field: field.to_string(),
+            direction: order_by.direction.into(),
+        }
+    }
+}
+
+fn get_report_edges(reports: Vec<Report>, fingerprint: &str, offset: i32) -> Vec<ReportEdge> {
     reports
         .into_iter()
         .enumerate()
         .map(|(i, report)| ReportEdge {
             node: report,
-            cursor: base64::encode((i as i32 + offset).to_string()),
+            cursor: pagination::encode_cursor(fingerprint, i as i32 + offset),
         })
         .collect()
 }
 
-fn get_reports_from_edges(edges: Vec<ReportEdge>, offset: i32, total_count: i32) -> Reports {
+fn get_reports_from_edges(
+    edges: Vec<ReportEdge>,
+    fingerprint: &str,
+    offset: i32,
+    total_count: i32,
+) -> Reports {
     let result_count = edges.len() as i32;
 
     Reports {
         edges,
         total_count,
-        page_info: PageInfo::build(offset, result_count, total_count),
+        page_info: PageInfo::build(fingerprint, offset, result_count, total_count),
+        facets: vec![],
     }
 }
 
 pub fn get_reports_graph_from_reports_vector(
     reports: Vec<Report>,
+    fingerprint: &str,
     offset: i32,
     total_count: i32,
 ) -> Reports {
-    let edges = get_report_edges(reports, offset);
-    get_reports_from_edges(edges, offset, total_count)
+    let edges = get_report_edges(reports, fingerprint, offset);
+    get_reports_from_edges(edges, fingerprint, offset, total_count)
 }
 
 pub struct AzureReportResult {
     reports: Vec<Report>,
+    fingerprint: String,
     offset: i32,
     total_count: i32,
+    facets: Vec<ReportFacet>,
 }
 
 impl Into<Reports> for AzureReportResult {
     fn into(self) -> Reports {
-        get_reports_graph_from_reports_vector(self.reports, self.offset, self.total_count)
+        Reports {
+            facets: self.facets,
+            ..get_reports_graph_from_reports_vector(
+                self.reports,
+                &self.fingerprint,
+                self.offset,
+                self.total_count,
+            )
+        }
     }
 }
 
 pub async fn get_reports(
     client: &impl Search,
+    budget: &QueryBudget,
     search: &str,
-    first: Option<i32>,
-    offset: i32,
-    substance_name: Option<&str>,
+    page: &PageArgs,
+    filter: &ReportFilter,
+    order_by: &[OrderBy],
+    facets: &[ReportFacetField],
 ) -> Result<AzureReportResult, anyhow::Error> {
-    let result_count = first.unwrap_or(10);
+    let filter = build_filter(filter);
+    let fingerprint = fingerprint(search, filter.as_ref(), order_by);
+    let (offset, result_count) = match page.page(&fingerprint)? {
+        Page::Range { offset, count } => (offset, count),
+        Page::Last { offset, count } => {
+            budget.spend(CALL_COST)?;
+            let total_count = count_reports(client, search, filter.as_ref()).await?;
+            last_window(offset, count, total_count)
+        }
+    };
 
     let azure_result = client
         .search_with_pagination_and_filter::<ReportResults>(
@@ -117,11 +330,135 @@ pub async fn get_reports(
                 offset,
             },
             true,
-            build_filter(substance_name).as_deref(),
+            filter.as_ref(),
+            order_by,
         )
         .await?;
 
-    Ok(map_azure_result(azure_result, offset))
+    let mut result = map_azure_result(azure_result, offset);
+    result.fingerprint = fingerprint;
+    if result.total_count > 0 && !facets.is_empty() {
+        budget.spend(CALL_COST)?;
+        result.facets = get_facets(client, search, filter.as_ref(), facets).await?;
+    }
+
+    Ok(result)
+}
+
+async fn count_reports(
+    client: &impl Search,
+    search: &str,
+    filter: Option<&Filter>,
+) -> Result<i32, anyhow::Error> {
+    let azure_result = client
+        .search_with_pagination_and_filter::<ReportResults>(
+            &search,
+            search_client::AzurePagination {
+                result_count: 0,
+                offset: 0,
+            },
+            true,
+            filter,
+            &[],
+        )
+        .await?;
+
+    Ok(azure_result.count.unwrap_or(0))
+}
+
+// The fingerprint of the reports `get_reports` would page through, for building pages of them another way.
+pub fn get_reports_fingerprint(
+    search: &str,
+    filter: &ReportFilter,
+    order_by: &[OrderBy],
+) -> String {
+    fingerprint(search, build_filter(filter).as_ref(), order_by)
+}
+
+async fn get_facets(
+    client: &impl Search,
+    search: &str,
+    filter: Option<&Filter>,
+    fields: &[ReportFacetField],
+) -> Result<Vec<ReportFacet>, anyhow::Error> {
+    let requests: Vec<FacetRequest> = fields
+        .iter()
+        .map(|field| FacetRequest::new(field.index_field()))
+        .collect();
+    let counts = client.search_facets(search, filter, &requests).await?;
+
+    Ok(map_facet_counts(&counts, fields))
+}
+
+fn map_facet_counts(counts: &FacetCounts, fields: &[ReportFacetField]) -> Vec<ReportFacet> {
+    fields
+        .iter()
+        .map(|&field| ReportFacet {
+            field,
+            counts: counts
+                .buckets(field.index_field())
+                .iter()
+                .map(FacetCount::from)
+                .collect(),
+        })
+        .collect()
+}
+
+// Reports cover a product when they share one of its licences, or name it.
+pub async fn get_pregnancy_reports(
+    client: &impl Search,
+    product_name: &str,
+    licences: &[String],
+) -> Result<Vec<Report>, anyhow::Error> {
+    let filter = Filter::or(
+        licences
+            .iter()
+            .filter_map(|licence| parse_licence(licence))
+            .map(|licence| Filter::any("pl_numbers", Operator::Eq, licence.canonical()))
+            .chain(std::iter::once(Filter::any(
+                "products",
+                Operator::Eq,
+                product_name,
+            ))),
+    );
+
+    get_all_reports(client, &filter).await
+}
+
+// Every report matching the filter, or an error rather than only some of them.
+pub async fn get_all_reports(
+    client: &impl Search,
+    filter: &Filter,
+) -> Result<Vec<Report>, anyhow::Error> {
+    let results = client
+        .search_with_pagination_and_filter::<ReportResults>(
+            "",
+            AzurePagination {
+                result_count: MAX_REPORTS,
+                offset: 0,
+            },
+            true,
+            Some(filter),
+            &[OrderBy::asc("report_name")],
+        )
+        .await?;
+
+    let count = results.count.unwrap_or(0);
+    if count > MAX_REPORTS {
+        return Err(TooManyReports { count }.into());
+    }
+
+    Ok(results
+        .search_results
+        .into_iter()
+        .map(Report::from)
+        .collect())
+}
+
+pub async fn get_report(client: &impl Search, id: &str) -> Result<Option<Report>, anyhow::Error> {
+    let result = client.get_document::<ReportResult>(id).await?;
+
+    Ok(result.map(Report::from))
 }
 
 fn map_azure_result(result: ReportResults, offset: i32) -> AzureReportResult {
@@ -135,32 +472,73 @@ fn map_azure_result(result: ReportResults, offset: i32) -> AzureReportResult {
 
     AzureReportResult {
         reports,
+        fingerprint: String::new(),
         total_count,
         offset,
+        facets: vec![],
     }
 }
 
-fn build_filter(substance_name: Option<&str>) -> Option<String> {
-    match substance_name {
-        Some(substance) => Some(build_substance_name_filter(substance)),
-        None => None,
+fn build_filter(filter: &ReportFilter) -> Option<Filter> {
+    let filters: Vec<Filter> = filter
+        .substance_name
+        .as_deref()
+        .map(build_substance_name_filter)
+        .into_iter()
+        .chain(build_collection_filter(
+            "pregnancy_trimesters",
+            &filter.pregnancy_trimesters,
+        ))
+        .chain(build_collection_filter("matrices", &filter.matrices))
+        .chain(build_collection_filter("pbpk_models", &filter.pbpk_models))
+        .chain(build_collection_filter("pl_numbers", &filter.pl_numbers))
+        .chain(filter.created.filter())
+        .collect();
+
+    if filters.is_empty() {
+        None
+    } else {
+        Some(Filter::and(filters))
     }
 }
 
-fn build_substance_name_filter(substance_name: &str) -> String {
-    format!(
-        "active_substances/any(substance: substance eq '{}')",
-        substance_name
-    )
+fn build_substance_name_filter(substance_name: &str) -> Filter {
+    Filter::any("active_substances", Operator::Eq, substance_name)
+}
+
+fn build_collection_filter(field: &str, values: &[String]) -> Option<Filter> {
+    if values.is_empty() {
+        return None;
+    }
+
+    Some(Filter::or(values.iter().map(|value| {
+        Filter::any(field, Operator::Eq, value.as_str())
+    })))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use search_client::models::{AzureHighlight, ReportResults};
+    use search_client::{
+        models::{AzureHighlight, ReportResults},
+        InMemorySearchClient,
+    };
+    use serde_json::json;
+    use test_case::test_case;
+
+    #[test]
+    fn sorts_by_report_name_for_title() {
+        let order_by: OrderBy = ReportOrderBy {
+            field: ReportSortField::Title,
+            direction: SortDirection::Asc,
+        }
+        .into();
+        assert_eq!(order_by.to_string(), "report_name asc");
+    }
 
     fn given_a_search_result(report_name: &str) -> ReportResult {
         ReportResult {
+            id: Some("id".to_string()),
             products: Some(vec!["product".to_string()]),
             metadata_storage_name: "storage_name".to_string(),
             metadata_storage_path: "test/path".to_string(),
@@ -272,4 +650,194 @@ mod test {
         let response = when_we_map_the_results(search_results);
         then_we_have_the_expected_output(response);
     }
+
+    #[test]
+    fn test_build_filter_escapes_substance_name() {
+        let filter = ReportFilter {
+            substance_name: Some("ST JOHN'S WORT".to_string()),
+            ..ReportFilter::default()
+        };
+        assert_eq!(
+            build_filter(&filter).map(|filter| filter.to_string()),
+            Some("active_substances/any(f: f eq 'ST JOHN''S WORT')".to_string())
+        );
+        assert_eq!(build_filter(&ReportFilter::default()), None);
+    }
+
+    #[test]
+    fn test_build_filter_with_created_range() {
+        let filter = ReportFilter {
+            substance_name: Some("IBUPROFEN".to_string()),
+            created: CreatedRange::parse(None, Some("2021-01-01")).unwrap(),
+            ..ReportFilter::default()
+        };
+        assert_eq!(
+            build_filter(&filter).map(|filter| filter.to_string()),
+            Some(
+                "(active_substances/any(f: f eq 'IBUPROFEN') and created lt 2021-01-01T00:00:00Z)"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_build_filter_with_collection_fields() {
+        let filter = ReportFilter {
+            pregnancy_trimesters: vec!["Third".to_string()],
+            matrices: vec!["Plasma".to_string(), "Cord blood".to_string()],
+            ..ReportFilter::default()
+        };
+        assert_eq!(
+            build_filter(&filter).map(|filter| filter.to_string()),
+            Some(
+                "(pregnancy_trimesters/any(f: f eq 'Third') and (matrices/any(f: f eq 'Plasma') or matrices/any(f: f eq 'Cord blood')))"
+                    .to_string()
+            )
+        );
+    }
+
+    fn given_a_report(
+        id: &str,
+        trimesters: &[&str],
+        matrices: &[&str],
+        pl_numbers: &[&str],
+    ) -> serde_json::Value {
+        json!({
+            "id": id,
+            "metadata_storage_name": id,
+            "metadata_storage_path": "test/path",
+            "metadata_storage_size": 300,
+            "file_name": format!("{}.pdf", id),
+            "report_name": id,
+            "summary": "summary",
+            "active_substances": ["IBUPROFEN"],
+            "products": ["NUROFEN 200MG TABLETS"],
+            "pregnancy_trimesters": trimesters,
+            "matrices": matrices,
+            "pbpk_models": [],
+            "pl_numbers": pl_numbers,
+        })
+    }
+
+    fn given_a_search_client() -> InMemorySearchClient {
+        InMemorySearchClient::with_documents(
+            "id",
+            vec![
+                given_a_report("first", &["First", "Third"], &["Plasma"], &["PL123450010"]),
+                given_a_report("second", &["Third"], &["Plasma", "Cord blood"], &[]),
+                given_a_report(
+                    "third",
+                    &["Second"],
+                    &["Urine"],
+                    &["PL123450010", "PL543210001"],
+                ),
+            ],
+        )
+    }
+
+    fn when_we_get_reports(
+        filter: &ReportFilter,
+        facets: &[ReportFacetField],
+    ) -> AzureReportResult {
+        tokio_test::block_on(get_reports(
+            &given_a_search_client(),
+            &QueryBudget::new(1),
+            "",
+            &PageArgs::default(),
+            filter,
+            &[],
+            facets,
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_get_third_trimester_plasma_reports() {
+        let filter = ReportFilter {
+            pregnancy_trimesters: vec!["Third".to_string()],
+            matrices: vec!["Plasma".to_string()],
+            ..ReportFilter::default()
+        };
+        let result = when_we_get_reports(&filter, &[]);
+
+        assert_eq!(result.total_count, 2);
+        assert_eq!(result.facets, vec![]);
+    }
+
+    #[test]
+    fn test_get_reports_with_facets() {
+        let filter = ReportFilter {
+            pregnancy_trimesters: vec!["Third".to_string()],
+            ..ReportFilter::default()
+        };
+        let result = when_we_get_reports(&filter, &[ReportFacetField::Matrix]);
+
+        assert_eq!(
+            result.facets,
+            vec![ReportFacet {
+                field: ReportFacetField::Matrix,
+                counts: vec![
+                    FacetCount {
+                        value: "Plasma".to_string(),
+                        count: 2
+                    },
+                    FacetCount {
+                        value: "Cord blood".to_string(),
+                        count: 1
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test_case("second", Some("second"); "by id")]
+    #[test_case("second.pdf", Some("second"); "by file name")]
+    #[test_case("fourth", None; "when it isn't there")]
+    fn test_get_report(id: &str, expected: Option<&str>) {
+        let report = tokio_test::block_on(get_report(&given_a_search_client(), id)).unwrap();
+        assert_eq!(
+            report.and_then(|report| report.id),
+            expected.map(String::from)
+        );
+    }
+
+    #[test_case("IBUPROFEN 200MG TABLETS", &["PL 12345/0010"], &["first", "third"])]
+    #[test_case("IBUPROFEN 200MG TABLETS", &["PL 99999/0001"], &[])]
+    #[test_case("NUROFEN 200MG TABLETS", &[], &["first", "second", "third"])]
+    fn test_get_pregnancy_reports(product_name: &str, licences: &[&str], expected: &[&str]) {
+        let licences: Vec<String> = licences.iter().map(|l| l.to_string()).collect();
+        let reports = tokio_test::block_on(get_pregnancy_reports(
+            &given_a_search_client(),
+            product_name,
+            &licences,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            reports
+                .iter()
+                .filter_map(|report| report.id.as_deref())
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_get_pregnancy_reports_rejects_more_than_can_be_listed() {
+        let reports = (0..=MAX_REPORTS)
+            .map(|i| given_a_report(&i.to_string(), &[], &[], &[]))
+            .collect();
+        let client = InMemorySearchClient::with_documents("id", reports);
+
+        let error =
+            tokio_test::block_on(get_pregnancy_reports(&client, "NUROFEN 200MG TABLETS", &[]))
+                .unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<TooManyReports>(),
+            Some(&TooManyReports {
+                count: MAX_REPORTS + 1
+            })
+        );
+    }
 }
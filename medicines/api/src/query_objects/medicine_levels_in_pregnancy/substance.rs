@@ -1,10 +1,12 @@
 use crate::{
-    azure_context::AzureContext,
+    error::{bad_user_input, to_field_error},
+    pagination::{last_window, Page, PageArgs},
+    query_budget::{azure_context, page_cost, QueryBudget},
     query_objects::medicine_levels_in_pregnancy::report::{
-        get_reports, get_reports_graph_from_reports_vector, Report, Reports,
+        get_reports, get_reports_fingerprint, get_reports_graph_from_reports_vector, Report,
+        ReportFilter, Reports,
     },
 };
-use anyhow::anyhow;
 use async_graphql::{Context, FieldResult, Object};
 
 #[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
@@ -32,40 +34,55 @@ impl SubstanceReports {
         context: &Context<'_>,
         first: Option<i32>,
         offset: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
     ) -> FieldResult<Reports> {
-        let context = context.data::<AzureContext>()?;
-
-        let offset = match offset {
-            Some(a) => a,
-            None => 0,
+        let filter = ReportFilter {
+            substance_name: Some(self.name.clone()),
+            ..ReportFilter::default()
+        };
+        let page = PageArgs {
+            first,
+            after,
+            last,
+            before,
+            skip: offset,
         };
 
         if let Some(reports) = self.reports.clone() {
+            let fingerprint = get_reports_fingerprint("", &filter, &[]);
             let total_count = reports.len() as i32;
-
-            let reports = match first {
-                Some(t) => reports.into_iter().take(t as usize).collect(),
-                None => reports,
+            let (offset, count) = match page.page(&fingerprint).map_err(bad_user_input)? {
+                Page::Range { offset, count } => (offset, count),
+                Page::Last { offset, count } => last_window(offset, count, total_count),
             };
 
+            let reports = reports
+                .into_iter()
+                .skip(offset as usize)
+                .take(count as usize)
+                .collect();
+
             Ok(get_reports_graph_from_reports_vector(
                 reports,
+                &fingerprint,
                 offset,
                 total_count,
             ))
         } else {
-            get_reports(&context.bmgf_client, "", first, offset, Some(&self.name))
+            let budget = context.data::<QueryBudget>()?;
+            let context = azure_context(context, page_cost(first, last))?;
+
+            get_reports(&context.bmgf_client, budget, "", &page, &filter, &[], &[])
                 .await
                 .map(Into::into)
-                .map_err(|e| {
-                    tracing::error!("Error fetching reeports from Azure search service: {:?}", e);
-                    anyhow!("Error retrieving results").into()
-                })
+                .map_err(|e| to_field_error(&e))
         }
     }
 }
 
-pub async fn get_substance(substance_name: String) -> Result<SubstanceReports, reqwest::Error> {
+pub async fn get_substance(substance_name: String) -> Result<SubstanceReports, anyhow::Error> {
     Ok(SubstanceReports::new(substance_name, None))
 }
 
@@ -1,4 +1,5 @@
 // mod medicine_levels_in_pregnancy;
 pub mod medicine_levels_in_pregnancy;
 pub mod products;
+pub mod search;
 pub mod shared;
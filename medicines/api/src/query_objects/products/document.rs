@@ -1,13 +1,23 @@
-use crate::{pagination, pagination::PageInfo};
-use async_graphql::SimpleObject;
+use crate::{
+    pagination,
+    pagination::{fingerprint, last_window, Page, PageArgs, PageInfo},
+    query_budget::{QueryBudget, CALL_COST},
+    query_objects::shared::{created::CreatedRange, facet::FacetCount, order_by::SortDirection},
+};
+use async_graphql::{Enum, InputObject, SimpleObject};
 use search_client::{
-    models::{DocumentType, IndexResult, IndexResults, TerritoryType},
+    facet::FacetRequest,
+    filter::{Filter, Literal},
+    models::{DocumentType, FacetCounts, IndexResult, IndexResults, TerritoryType},
+    sort::{OrderBy, RELEVANCE},
     Search,
 };
 
 #[SimpleObject(desc = "An SPC, PIL or PAR document")]
 #[derive(Debug, Clone, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Document {
+    #[field(desc = "Unique identifier of the document in the index")]
+    pub id: Option<String>,
     #[field(desc = "Product associated with document")]
     pub product_name: Option<String>,
     #[field(desc = "Active substances associated with document")]
@@ -28,6 +38,12 @@ pub struct Document {
     pub name: Option<String>,
     #[field(desc = "PDF file url")]
     pub url: Option<String>,
+    #[field(desc = "Keywords")]
+    pub keywords: Option<String>,
+    #[field(desc = "Release state")]
+    pub release_state: Option<String>,
+    #[field(desc = "Revision label")]
+    pub rev_label: Option<String>,
 }
 
 impl Document {
@@ -38,11 +54,16 @@ impl Document {
     pub fn is_territory_type(&self, territory_type: TerritoryType) -> bool {
         self.territory_type == Some(territory_type)
     }
+
+    pub fn is_created_within(&self, created: &CreatedRange) -> bool {
+        created.contains(self.created.as_deref())
+    }
 }
 
 impl From<IndexResult> for Document {
     fn from(r: IndexResult) -> Self {
         Self {
+            id: Some(r.metadata_storage_name),
             product_name: r.product_name,
             active_substances: Some(r.substance_name),
             title: Some(r.title),
@@ -56,63 +77,180 @@ impl From<IndexResult> for Document {
                 Some(a) => Some(a.content),
                 _ => None,
             },
+            keywords: r.keywords,
+            release_state: r.release_state,
+            rev_label: r.rev_label,
+        }
+    }
+}
+
+pagination! {Documents, DocumentEdge, Document, {
+    #[field(desc = "Spelling suggestions for the search, when it doesn't match any documents")]
+    did_you_mean: Vec<String>,
+    #[field(desc = "Number of matching documents for each value of the requested fields")]
+    facets: Vec<DocumentFacet>,
+}}
+
+// The most spelling suggestions given for a search.
+const DID_YOU_MEAN_COUNT: usize = 5;
+
+#[Enum(desc = "A field documents can be sorted by")]
+#[derive(Debug)]
+pub enum DocumentSortField {
+    #[item(desc = "How well the document matches the search")]
+    Relevance,
+    #[item(desc = "Created date")]
+    Created,
+    #[item(desc = "Title")]
+    Title,
+    #[item(desc = "Product associated with document")]
+    ProductName,
+}
+
+#[InputObject(desc = "A key to sort documents by")]
+#[derive(Clone, Debug)]
+pub struct DocumentOrderBy {
+    #[field(desc = "Field to sort by")]
+    pub field: DocumentSortField,
+    #[field(desc = "Direction to sort in")]
+    pub direction: SortDirection,
+}
+
+#[Enum(desc = "A field documents can be counted by")]
+#[derive(Debug)]
+pub enum DocumentFacetField {
+    #[item(desc = "Document type")]
+    DocType,
+    #[item(desc = "Territory type")]
+    TerritoryType,
+    #[item(desc = "Active substances associated with document")]
+    ActiveSubstance,
+    #[item(desc = "Product associated with document")]
+    ProductName,
+    #[item(desc = "Licence numbers of the product associated with document")]
+    ProductLicence,
+}
+
+impl DocumentFacetField {
+    fn index_field(self) -> &'static str {
+        match self {
+            DocumentFacetField::DocType => "doc_type",
+            DocumentFacetField::TerritoryType => "territory",
+            DocumentFacetField::ActiveSubstance => "substance_name",
+            DocumentFacetField::ProductName => "product_name",
+            DocumentFacetField::ProductLicence => "pl_number",
         }
     }
 }
 
-pagination! {Documents, DocumentEdge, Document}
+#[SimpleObject(desc = "The number of documents with each of the most common values of a field")]
+#[derive(Debug, PartialEq)]
+pub struct DocumentFacet {
+    #[field(desc = "Field the documents are counted by")]
+    pub field: DocumentFacetField,
+    #[field(desc = "Number of documents with each value, most common first")]
+    pub counts: Vec<FacetCount>,
+}
+
+impl From<DocumentOrderBy> for OrderBy {
+    fn from(order_by: DocumentOrderBy) -> Self {
+        let field = match order_by.field {
+            DocumentSortField::Relevance => RELEVANCE,
+            DocumentSortField::Created => "created",
+            DocumentSortField::Title => "title",
+            DocumentSortField::ProductName => "product_name",
+        };
+        OrderBy {
+            field: field.to_string(),
+            direction: order_by.direction.into(),
+        }
+    }
+}
 
-fn get_document_edges(docs: Vec<Document>, offset: i32) -> Vec<DocumentEdge> {
+fn get_document_edges(docs: Vec<Document>, fingerprint: &str, offset: i32) -> Vec<DocumentEdge> {
     docs.into_iter()
         .enumerate()
         .map(|(i, document)| DocumentEdge {
             node: document,
-            cursor: base64::encode((i as i32 + offset).to_string()),
+            cursor: pagination::encode_cursor(fingerprint, i as i32 + offset),
         })
         .collect()
 }
 
-fn get_documents_from_edges(edges: Vec<DocumentEdge>, offset: i32, total_count: i32) -> Documents {
+fn get_documents_from_edges(
+    edges: Vec<DocumentEdge>,
+    fingerprint: &str,
+    offset: i32,
+    total_count: i32,
+) -> Documents {
     let result_count = edges.len() as i32;
 
     Documents {
         edges,
         total_count,
-        page_info: PageInfo::build(offset, result_count, total_count),
+        page_info: PageInfo::build(fingerprint, offset, result_count, total_count),
+        did_you_mean: vec![],
+        facets: vec![],
     }
 }
 
 pub fn get_documents_graph_from_documents_vector(
     docs: Vec<Document>,
+    fingerprint: &str,
     offset: i32,
     total_count: i32,
 ) -> Documents {
-    let edges = get_document_edges(docs, offset);
-    get_documents_from_edges(edges, offset, total_count)
+    let edges = get_document_edges(docs, fingerprint, offset);
+    get_documents_from_edges(edges, fingerprint, offset, total_count)
 }
 
 pub struct AzureDocumentResult {
     docs: Vec<Document>,
+    fingerprint: String,
     offset: i32,
     total_count: i32,
+    did_you_mean: Vec<String>,
+    facets: Vec<DocumentFacet>,
 }
 
 impl Into<Documents> for AzureDocumentResult {
     fn into(self) -> Documents {
-        get_documents_graph_from_documents_vector(self.docs, self.offset, self.total_count)
+        Documents {
+            did_you_mean: self.did_you_mean,
+            facets: self.facets,
+            ..get_documents_graph_from_documents_vector(
+                self.docs,
+                &self.fingerprint,
+                self.offset,
+                self.total_count,
+            )
+        }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn get_documents(
     client: &impl Search,
+    budget: &QueryBudget,
     search: &str,
-    first: Option<i32>,
-    offset: i32,
+    page: &PageArgs,
     document_types: Option<Vec<DocumentType>>,
     territory_types: Option<Vec<TerritoryType>>,
     product_name: Option<&str>,
+    created: &CreatedRange,
+    order_by: &[OrderBy],
+    facets: &[DocumentFacetField],
 ) -> Result<AzureDocumentResult, anyhow::Error> {
-    let result_count = first.unwrap_or(10);
+    let filter = build_filter(document_types, territory_types, product_name, created);
+    let fingerprint = fingerprint(search, filter.as_ref(), order_by);
+    let (offset, result_count) = match page.page(&fingerprint)? {
+        Page::Range { offset, count } => (offset, count),
+        Page::Last { offset, count } => {
+            budget.spend(CALL_COST)?;
+            let total_count = count_documents(client, search, filter.as_ref()).await?;
+            last_window(offset, count, total_count)
+        }
+    };
 
     let azure_result = client
         .search_with_pagination_and_filter::<IndexResults>(
@@ -122,11 +260,108 @@ pub async fn get_documents(
                 offset,
             },
             true,
-            build_filter(document_types, territory_types, product_name).as_deref(),
+            filter.as_ref(),
+            order_by,
+        )
+        .await?;
+
+    let mut result = map_azure_result(azure_result, offset);
+    result.fingerprint = fingerprint;
+    if result.total_count == 0 {
+        result.did_you_mean = get_did_you_mean(client, budget, search).await;
+    } else if !facets.is_empty() {
+        budget.spend(CALL_COST)?;
+        result.facets = get_facets(client, search, filter.as_ref(), facets).await?;
+    }
+
+    Ok(result)
+}
+
+async fn count_documents(
+    client: &impl Search,
+    search: &str,
+    filter: Option<&Filter>,
+) -> Result<i32, anyhow::Error> {
+    let azure_result = client
+        .search_with_pagination_and_filter::<IndexResults>(
+            &search,
+            search_client::AzurePagination {
+                result_count: 0,
+                offset: 0,
+            },
+            true,
+            filter,
+            &[],
         )
         .await?;
 
-    Ok(map_azure_result(azure_result, offset))
+    Ok(azure_result.count.unwrap_or(0))
+}
+
+// The fingerprint of the documents `get_documents` would page through, for building pages of them another way.
+pub fn get_documents_fingerprint(
+    search: &str,
+    document_types: Option<Vec<DocumentType>>,
+    territory_types: Option<Vec<TerritoryType>>,
+    product_name: Option<&str>,
+    created: &CreatedRange,
+    order_by: &[OrderBy],
+) -> String {
+    let filter = build_filter(document_types, territory_types, product_name, created);
+    fingerprint(search, filter.as_ref(), order_by)
+}
+
+async fn get_facets(
+    client: &impl Search,
+    search: &str,
+    filter: Option<&Filter>,
+    fields: &[DocumentFacetField],
+) -> Result<Vec<DocumentFacet>, anyhow::Error> {
+    let requests: Vec<FacetRequest> = fields
+        .iter()
+        .map(|field| FacetRequest::new(field.index_field()))
+        .collect();
+    let counts = client.search_facets(search, filter, &requests).await?;
+
+    Ok(map_facet_counts(&counts, fields))
+}
+
+fn map_facet_counts(counts: &FacetCounts, fields: &[DocumentFacetField]) -> Vec<DocumentFacet> {
+    fields
+        .iter()
+        .map(|&field| DocumentFacet {
+            field,
+            counts: counts
+                .buckets(field.index_field())
+                .iter()
+                .map(FacetCount::from)
+                .collect(),
+        })
+        .collect()
+}
+
+pub async fn get_document(
+    client: &impl Search,
+    id: &str,
+) -> Result<Option<Document>, anyhow::Error> {
+    let result = client.get_document::<IndexResult>(id).await?;
+
+    Ok(result.map(Document::from))
+}
+
+// Suggestions are a nice to have, so the search still succeeds without them, or if they can't be paid for.
+async fn get_did_you_mean(client: &impl Search, budget: &QueryBudget, search: &str) -> Vec<String> {
+    if search.trim().is_empty() || budget.spend(CALL_COST).is_err() {
+        return vec![];
+    }
+
+    client
+        .did_you_mean(search, DID_YOU_MEAN_COUNT)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("Error fetching spelling suggestions: {:?}", e);
+            vec![]
+        })
 }
 
 fn map_azure_result(result: IndexResults, offset: i32) -> AzureDocumentResult {
@@ -140,8 +375,11 @@ fn map_azure_result(result: IndexResults, offset: i32) -> AzureDocumentResult {
 
     AzureDocumentResult {
         docs,
+        fingerprint: String::new(),
         total_count,
         offset,
+        did_you_mean: vec![],
+        facets: vec![],
     }
 }
 
@@ -149,40 +387,37 @@ fn build_filter(
     document_types: Option<Vec<DocumentType>>,
     territory_types: Option<Vec<TerritoryType>>,
     product_name: Option<&str>,
-) -> Option<String> {
+    created: &CreatedRange,
+) -> Option<Filter> {
     let docs_filter = document_types.and_then(build_document_types_filter);
     let products_filter = product_name.map(build_product_name_filter);
     let territories_filter = territory_types.and_then(build_territory_types_filter);
 
-    let filters: Vec<String> = products_filter
+    let filters: Vec<Filter> = products_filter
         .into_iter()
         .chain(docs_filter)
         .chain(territories_filter)
+        .chain(created.filter())
         .collect();
 
-    match &filters[..] {
-        [] => None,
-        [filter] => Some(filter.clone()),
-        _ => Some(format!("({})", filters.join(" and "))),
+    if filters.is_empty() {
+        None
+    } else {
+        Some(Filter::and(filters))
     }
 }
 
-fn build_document_types_filter(document_types: Vec<DocumentType>) -> Option<String> {
+fn build_document_types_filter(document_types: Vec<DocumentType>) -> Option<Filter> {
     if document_types.is_empty() {
         return None;
     }
 
-    Some(format!(
-        "({})",
-        document_types
-            .into_iter()
-            .map(|document_type| format!("doc_type eq '{}'", document_type))
-            .collect::<Vec<_>>()
-            .join(" or ")
-    ))
+    Some(Filter::or(document_types.into_iter().map(
+        |document_type| Filter::eq("doc_type", document_type.to_string()),
+    )))
 }
 
-fn build_territory_types_filter(territory_types: Vec<TerritoryType>) -> Option<String> {
+fn build_territory_types_filter(territory_types: Vec<TerritoryType>) -> Option<Filter> {
     if territory_types.is_empty() {
         return None;
     }
@@ -190,27 +425,52 @@ fn build_territory_types_filter(territory_types: Vec<TerritoryType>) -> Option<S
     let mut initial_query = territory_types
         .into_iter()
         .flat_map(|territory_type| match territory_type {
-            TerritoryType::GB => Some("territory eq 'GB'"),
-            TerritoryType::NI => Some("territory eq 'NI'"),
+            TerritoryType::GB | TerritoryType::NI => {
+                Some(Filter::eq("territory", territory_type.to_string()))
+            }
             _ => None,
         })
         .collect::<Vec<_>>();
 
-    initial_query.extend(vec!["territory eq 'UK'", "territory eq null"]);
+    initial_query.extend(vec![
+        Filter::eq("territory", TerritoryType::UK.to_string()),
+        Filter::eq("territory", Literal::Null),
+    ]);
 
-    Some(format!("({})", initial_query.join(" or ")))
+    Some(Filter::or(initial_query))
 }
 
-fn build_product_name_filter(product_name: &str) -> String {
-    format!("(product_name eq '{}')", product_name)
+pub(crate) fn build_product_name_filter(product_name: &str) -> Filter {
+    Filter::eq("product_name", product_name)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use search_client::models::{AzureHighlight, IndexResult};
+    use crate::{pagination::PaginationError, query_budget::BudgetExceeded};
+    use search_client::{
+        models::{AzureHighlight, IndexResult},
+        InMemorySearchClient,
+    };
+    use serde_json::json;
     use test_case::test_case;
 
+    #[test_case(
+        DocumentSortField::Relevance,
+        SortDirection::Desc,
+        "search.score() desc"
+    )]
+    #[test_case(DocumentSortField::Created, SortDirection::Desc, "created desc")]
+    #[test_case(DocumentSortField::ProductName, SortDirection::Asc, "product_name asc")]
+    fn converts_order_by_to_index_fields(
+        field: DocumentSortField,
+        direction: SortDirection,
+        expected: &str,
+    ) {
+        let order_by: OrderBy = DocumentOrderBy { field, direction }.into();
+        assert_eq!(order_by.to_string(), expected);
+    }
+
     fn given_a_search_result(product_name: &str) -> IndexResult {
         IndexResult {
             product_name: Some(product_name.to_string()),
@@ -225,8 +485,8 @@ mod test {
             facets: vec!["facet".to_string()],
             keywords: None,
             metadata_storage_size: 300,
-            release_state: None,
-            rev_label: None,
+            release_state: Some("Y".to_string()),
+            rev_label: Some("2".to_string()),
             suggestions: vec!["suggestion".to_string()],
             score: 1.0,
             highlights: Some(AzureHighlight {
@@ -270,6 +530,7 @@ mod test {
 
     fn then_all_fields_map_correctly(reports_response: AzureDocumentResult) {
         let first_result = reports_response.docs[0].clone();
+        assert_eq!(first_result.id.unwrap(), "storage_name");
         assert_eq!(first_result.product_name.unwrap(), "first");
         assert_eq!(
             first_result.active_substances.unwrap().first().unwrap(),
@@ -281,12 +542,63 @@ mod test {
         assert_eq!(first_result.doc_type.unwrap(), DocumentType::Spc);
         assert_eq!(first_result.name.unwrap(), "our_id");
         assert_eq!(first_result.url.unwrap(), "test/path");
+        assert_eq!(first_result.release_state.unwrap(), "Y");
+        assert_eq!(first_result.rev_label.unwrap(), "2");
         assert_eq!(
             first_result.highlights.unwrap().first().unwrap(),
             "highlight"
         );
     }
 
+    fn given_a_search_client() -> InMemorySearchClient {
+        InMemorySearchClient::with_documents(
+            "metadata_storage_name",
+            vec![json!({
+                "metadata_storage_name": "first",
+                "metadata_storage_path": "test/path",
+                "metadata_storage_size": 300,
+                "file_name": "our_id",
+                "doc_type": "Spc",
+                "title": "title",
+                "product_name": "IBUPROFEN 200MG TABLETS",
+                "substance_name": ["IBUPROFEN"],
+                "facets": ["I", "I, IBUPROFEN", "I, IBUPROFEN, IBUPROFEN 200MG TABLETS"],
+                "suggestions": [],
+            })],
+        )
+    }
+
+    fn when_we_search_for(search: &str) -> AzureDocumentResult {
+        when_we_search_with_facets_for(search, &[])
+    }
+
+    fn when_we_search_with_facets_for(
+        search: &str,
+        facets: &[DocumentFacetField],
+    ) -> AzureDocumentResult {
+        when_we_search_within_budget_for(&QueryBudget::new(2), search, facets).unwrap()
+    }
+
+    fn when_we_search_within_budget_for(
+        budget: &QueryBudget,
+        search: &str,
+        facets: &[DocumentFacetField],
+    ) -> anyhow::Result<AzureDocumentResult> {
+        let client = given_a_search_client();
+        tokio_test::block_on(get_documents(
+            &client,
+            budget,
+            search,
+            &PageArgs::default(),
+            None,
+            None,
+            None,
+            &CreatedRange::default(),
+            &[],
+            facets,
+        ))
+    }
+
     fn then_we_have_the_expected_output(documents_response: AzureDocumentResult) {
         let expected_names = vec![
             "first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth",
@@ -316,6 +628,166 @@ mod test {
         then_we_have_the_expected_output(response);
     }
 
+    #[test_case("first", Some("first"); "by id")]
+    #[test_case("our_id", Some("first"); "by content id")]
+    #[test_case("second", None; "when it isn't there")]
+    fn test_get_document(id: &str, expected: Option<&str>) {
+        let client = given_a_search_client();
+        let document = tokio_test::block_on(get_document(&client, id)).unwrap();
+        assert_eq!(
+            document.and_then(|document| document.id),
+            expected.map(String::from)
+        );
+    }
+
+    #[test_case("ibuprophen", 0, &["ibuprofen"])]
+    #[test_case("ibuprofen", 1, &[])]
+    #[test_case("zzzzzzzz", 0, &[])]
+    fn test_did_you_mean(search: &str, total_count: i32, did_you_mean: &[&str]) {
+        let result = when_we_search_for(search);
+        assert_eq!(result.total_count, total_count);
+        assert_eq!(result.did_you_mean, did_you_mean);
+    }
+
+    #[test]
+    fn test_facets() {
+        let result = when_we_search_with_facets_for(
+            "ibuprofen",
+            &[
+                DocumentFacetField::DocType,
+                DocumentFacetField::ActiveSubstance,
+            ],
+        );
+        assert_eq!(
+            result.facets,
+            vec![
+                DocumentFacet {
+                    field: DocumentFacetField::DocType,
+                    counts: vec![FacetCount {
+                        value: "Spc".to_string(),
+                        count: 1
+                    }],
+                },
+                DocumentFacet {
+                    field: DocumentFacetField::ActiveSubstance,
+                    counts: vec![FacetCount {
+                        value: "IBUPROFEN".to_string(),
+                        count: 1
+                    }],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_did_you_mean_is_left_out_when_over_budget() {
+        let result = when_we_search_within_budget_for(&QueryBudget::new(0), "ibuprophen", &[]);
+
+        assert_eq!(result.unwrap().did_you_mean, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_facets_are_paid_for() {
+        let budget = QueryBudget::new(0);
+        let error =
+            when_we_search_within_budget_for(&budget, "ibuprofen", &[DocumentFacetField::DocType])
+                .unwrap_err();
+
+        assert!(error.downcast_ref::<BudgetExceeded>().is_some());
+    }
+
+    fn when_we_page_through(search: &str, page: PageArgs) -> anyhow::Result<AzureDocumentResult> {
+        let client = given_a_search_client();
+        tokio_test::block_on(get_documents(
+            &client,
+            &QueryBudget::new(2),
+            search,
+            &page,
+            None,
+            None,
+            None,
+            &CreatedRange::default(),
+            &[],
+            &[],
+        ))
+    }
+
+    #[test]
+    fn test_last_documents() {
+        let page = PageArgs {
+            last: Some(5),
+            ..PageArgs::default()
+        };
+
+        let result = when_we_page_through("ibuprofen", page).unwrap();
+
+        assert_eq!(result.offset, 0);
+        assert_eq!(result.docs.len(), 1);
+        assert_eq!(result.total_count, 1);
+    }
+
+    #[test]
+    fn test_cursor_from_another_search() {
+        let cursor = when_we_page_through("ibuprofen", PageArgs::default())
+            .map(Into::<Documents>::into)
+            .unwrap()
+            .edges[0]
+            .cursor
+            .clone();
+        let page = PageArgs {
+            after: Some(cursor),
+            ..PageArgs::default()
+        };
+
+        let error = when_we_page_through("paracetamol", page).unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<PaginationError>(),
+            Some(&PaginationError::CursorFromAnotherQuery)
+        );
+    }
+
+    #[test]
+    fn test_map_facet_counts() {
+        let counts: FacetCounts = serde_json::from_value(json!({
+            "@odata.count": 22,
+            "@search.facets": {
+                "doc_type": [
+                    { "value": "Spc", "count": 12 },
+                    { "value": "Pil", "count": 8 },
+                    { "value": "Par", "count": 2 }
+                ]
+            }
+        }))
+        .unwrap();
+
+        let facets = map_facet_counts(
+            &counts,
+            &[
+                DocumentFacetField::DocType,
+                DocumentFacetField::TerritoryType,
+            ],
+        );
+
+        let counts = |facet: &DocumentFacet| -> Vec<(String, i32)> {
+            facet
+                .counts
+                .iter()
+                .map(|count| (count.value.clone(), count.count))
+                .collect()
+        };
+        assert_eq!(
+            counts(&facets[0]),
+            vec![
+                ("Spc".to_string(), 12),
+                ("Pil".to_string(), 8),
+                ("Par".to_string(), 2)
+            ]
+        );
+        assert_eq!(facets[1].field, DocumentFacetField::TerritoryType);
+        assert!(facets[1].counts.is_empty());
+    }
+
     #[test_case(None, None, None, None)]
     #[test_case(
         Some(vec![]),
@@ -327,7 +799,7 @@ mod test {
         Some(vec![DocumentType::Spc, DocumentType::Pil,DocumentType::Par,]),
         Some(vec![TerritoryType::UK, TerritoryType::GB, TerritoryType::NI,]),
         Some("IBUPROFEN 100MG CAPLETS"),
-        Some("((product_name eq 'IBUPROFEN 100MG CAPLETS') and (doc_type eq 'Spc' or doc_type eq 'Pil' or doc_type eq 'Par') and (territory eq 'GB' or territory eq 'NI' or territory eq 'UK' or territory eq null))")
+        Some("(product_name eq 'IBUPROFEN 100MG CAPLETS' and (doc_type eq 'Spc' or doc_type eq 'Pil' or doc_type eq 'Par') and (territory eq 'GB' or territory eq 'NI' or territory eq 'UK' or territory eq null))")
     )]
     #[test_case(
         Some(vec![DocumentType::Spc,  DocumentType::Pil,DocumentType::Par,]),
@@ -339,31 +811,37 @@ mod test {
         None,
         None,
         Some("IBUPROFEN 100MG CAPLETS"),
-        Some("(product_name eq 'IBUPROFEN 100MG CAPLETS')")
+        Some("product_name eq 'IBUPROFEN 100MG CAPLETS'")
     )]
     #[test_case(
         None,
         Some(vec![TerritoryType::UK]),
         Some("IBUPROFEN 100MG CAPLETS"),
-        Some("((product_name eq 'IBUPROFEN 100MG CAPLETS') and (territory eq 'UK' or territory eq null))")
+        Some("(product_name eq 'IBUPROFEN 100MG CAPLETS' and (territory eq 'UK' or territory eq null))")
     )]
     #[test_case(
         None,
         Some(vec![TerritoryType::GB]),
         Some("IBUPROFEN 100MG CAPLETS"),
-        Some("((product_name eq 'IBUPROFEN 100MG CAPLETS') and (territory eq 'GB' or territory eq 'UK' or territory eq null))")
+        Some("(product_name eq 'IBUPROFEN 100MG CAPLETS' and (territory eq 'GB' or territory eq 'UK' or territory eq null))")
     )]
     #[test_case(
         None,
         Some(vec![TerritoryType::NI]),
         Some("IBUPROFEN 100MG CAPLETS"),
-        Some("((product_name eq 'IBUPROFEN 100MG CAPLETS') and (territory eq 'NI' or territory eq 'UK' or territory eq null))")
+        Some("(product_name eq 'IBUPROFEN 100MG CAPLETS' and (territory eq 'NI' or territory eq 'UK' or territory eq null))")
+    )]
+    #[test_case(
+        None,
+        None,
+        Some("ST JOHN'S WORT"),
+        Some("product_name eq 'ST JOHN''S WORT'")
     )]
     #[test_case(
         Some(vec![]),
         Some(vec![]),
         Some("IBUPROFEN 100MG CAPLETS"),
-        Some("(product_name eq 'IBUPROFEN 100MG CAPLETS')")
+        Some("product_name eq 'IBUPROFEN 100MG CAPLETS'")
     )]
     fn test_build_filter(
         document_types: Option<Vec<DocumentType>>,
@@ -373,7 +851,23 @@ mod test {
     ) {
         assert_eq!(
             expected_filter.map(|s| s.to_string()),
-            build_filter(document_types, territory_types, product_name)
+            build_filter(
+                document_types,
+                territory_types,
+                product_name,
+                &CreatedRange::default()
+            )
+            .map(|filter| filter.to_string())
+        );
+    }
+
+    #[test]
+    fn test_build_filter_with_created_range() {
+        let created = CreatedRange::parse(Some("2021-01-01"), None).unwrap();
+        assert_eq!(
+            build_filter(Some(vec![DocumentType::Par]), None, None, &created)
+                .map(|filter| filter.to_string()),
+            Some("(doc_type eq 'Par' and created ge 2021-01-01T00:00:00Z)".to_string())
         );
     }
 }
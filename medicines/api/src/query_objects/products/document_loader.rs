@@ -0,0 +1,245 @@
+use crate::{
+    query_budget::{collect_pages, QueryBudget},
+    query_objects::products::document::{build_product_name_filter, Document},
+};
+use anyhow::anyhow;
+use futures::{
+    channel::oneshot,
+    future::{FutureExt, Shared},
+    lock::Mutex,
+};
+use search_client::{filter::Filter, models::IndexResult, Search};
+use std::{collections::HashMap, sync::Arc};
+
+// Errors are shared by every product in the batch, and keep their type so they can be told apart.
+type Loaded = Result<Vec<Document>, Arc<anyhow::Error>>;
+
+// Loads the documents of every product asked for while resolving one GraphQL request with a
+// single query, and keeps them for the rest of that request. Each page of the query is paid for once,
+// by whichever product happens to send it.
+#[derive(Default)]
+pub struct DocumentLoader {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    documents: HashMap<String, Shared<oneshot::Receiver<Loaded>>>,
+    pending: Vec<(String, oneshot::Sender<Loaded>)>,
+    dispatching: bool,
+}
+
+impl DocumentLoader {
+    pub async fn load(
+        &self,
+        client: &impl Search,
+        budget: &QueryBudget,
+        product_name: &str,
+    ) -> Loaded {
+        let (documents, dispatch) = self.enqueue(product_name).await;
+
+        if dispatch {
+            // Give the rest of the request the chance to ask for its products first.
+            tokio::task::yield_now().await;
+            self.dispatch(client, budget).await;
+        }
+
+        documents.await.unwrap_or_else(|oneshot::Canceled| {
+            Err(Arc::new(anyhow!(
+                "Loading the documents of {} was cancelled",
+                product_name
+            )))
+        })
+    }
+
+    async fn enqueue(&self, product_name: &str) -> (Shared<oneshot::Receiver<Loaded>>, bool) {
+        let mut state = self.state.lock().await;
+
+        if let Some(documents) = state.documents.get(product_name) {
+            return (documents.clone(), false);
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let documents = receiver.shared();
+
+        state
+            .documents
+            .insert(product_name.to_string(), documents.clone());
+        state.pending.push((product_name.to_string(), sender));
+
+        let dispatch = !state.dispatching;
+        state.dispatching = true;
+
+        (documents, dispatch)
+    }
+
+    async fn dispatch(&self, client: &impl Search, budget: &QueryBudget) {
+        let pending = {
+            let mut state = self.state.lock().await;
+            state.dispatching = false;
+            std::mem::take(&mut state.pending)
+        };
+
+        let product_names: Vec<&str> = pending.iter().map(|(name, _)| name.as_str()).collect();
+        let mut documents = load_documents(client, budget, &product_names)
+            .await
+            .map_err(Arc::new);
+
+        for (product_name, sender) in pending {
+            let loaded = match documents.as_mut() {
+                Ok(documents) => Ok(documents.remove(&product_name).unwrap_or_default()),
+                Err(e) => Err(e.clone()),
+            };
+
+            // Nobody is left waiting if the request has been dropped.
+            let _ = sender.send(loaded);
+        }
+    }
+}
+
+async fn load_documents(
+    client: &impl Search,
+    budget: &QueryBudget,
+    product_names: &[&str],
+) -> Result<HashMap<String, Vec<Document>>, anyhow::Error> {
+    let filter = Filter::or(
+        product_names
+            .iter()
+            .map(|&name| build_product_name_filter(name)),
+    );
+    let results: Vec<IndexResult> =
+        collect_pages(budget, client.stream_documents(Some(filter))).await?;
+
+    let mut documents = HashMap::<String, Vec<Document>>::new();
+
+    for result in results {
+        let document = Document::from(result);
+
+        if let Some(product_name) = document.product_name.clone() {
+            documents.entry(product_name).or_default().push(document);
+        }
+    }
+
+    Ok(documents)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::query_budget::BudgetExceeded;
+    use search_client::InMemorySearchClient;
+    use serde_json::json;
+
+    fn given_a_search_client() -> InMemorySearchClient {
+        let document = |name: &str, product_name: &str| {
+            json!({
+                "metadata_storage_name": name,
+                "metadata_storage_path": "test/path",
+                "metadata_storage_size": 300,
+                "file_name": name,
+                "doc_type": "Spc",
+                "title": "title",
+                "product_name": product_name,
+                "substance_name": ["IBUPROFEN"],
+                "facets": [],
+                "suggestions": [],
+            })
+        };
+
+        InMemorySearchClient::with_documents(
+            "metadata_storage_name",
+            vec![
+                document("first", "IBUPROFEN 200MG TABLETS"),
+                document("second", "IBUPROFEN 400MG TABLETS"),
+                document("third", "IBUPROFEN 200MG TABLETS"),
+                document("fourth", "NUROFEN 200MG TABLETS"),
+            ],
+        )
+    }
+
+    fn ids(documents: Loaded) -> Vec<String> {
+        let mut ids: Vec<String> = documents
+            .unwrap()
+            .into_iter()
+            .filter_map(|document| document.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_load_distributes_documents_to_each_product() {
+        let client = given_a_search_client();
+        let budget = QueryBudget::new(1);
+        let loader = DocumentLoader::default();
+
+        let (first, second, unknown) = tokio_test::block_on(async {
+            futures::join!(
+                loader.load(&client, &budget, "IBUPROFEN 200MG TABLETS"),
+                loader.load(&client, &budget, "IBUPROFEN 400MG TABLETS"),
+                loader.load(&client, &budget, "PARACETAMOL 500MG TABLETS"),
+            )
+        });
+
+        assert_eq!(ids(first), vec!["first", "third"]);
+        assert_eq!(ids(second), vec!["second"]);
+        assert_eq!(ids(unknown), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_enqueue_batches_and_deduplicates_products() {
+        let loader = DocumentLoader::default();
+
+        tokio_test::block_on(async {
+            let (_, first) = loader.enqueue("IBUPROFEN 200MG TABLETS").await;
+            let (_, second) = loader.enqueue("IBUPROFEN 400MG TABLETS").await;
+            let (_, repeated) = loader.enqueue("IBUPROFEN 200MG TABLETS").await;
+
+            assert!(first);
+            assert!(!second);
+            assert!(!repeated);
+            assert_eq!(loader.state.lock().await.pending.len(), 2);
+        });
+    }
+
+    #[test]
+    fn test_load_caches_documents_for_the_request() {
+        let client = given_a_search_client();
+        let budget = QueryBudget::new(1);
+        let loader = DocumentLoader::default();
+
+        tokio_test::block_on(async {
+            loader
+                .load(&client, &budget, "IBUPROFEN 200MG TABLETS")
+                .await
+                .unwrap();
+            let (documents, dispatch) = loader.enqueue("IBUPROFEN 200MG TABLETS").await;
+
+            assert!(!dispatch);
+            assert_eq!(ids(documents.await.unwrap()).len(), 2);
+        });
+    }
+
+    #[test]
+    fn test_load_charges_once_for_each_query() {
+        let client = given_a_search_client();
+        let budget = QueryBudget::new(1);
+        let loader = DocumentLoader::default();
+
+        let error = tokio_test::block_on(async {
+            let (first, second) = futures::join!(
+                loader.load(&client, &budget, "IBUPROFEN 200MG TABLETS"),
+                loader.load(&client, &budget, "IBUPROFEN 400MG TABLETS"),
+            );
+            first.unwrap();
+            second.unwrap();
+
+            loader
+                .load(&client, &budget, "NUROFEN 200MG TABLETS")
+                .await
+                .unwrap_err()
+        });
+
+        assert!(error.downcast_ref::<BudgetExceeded>().is_some());
+    }
+}
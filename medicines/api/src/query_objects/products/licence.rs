@@ -0,0 +1,175 @@
+use crate::{
+    query_budget::{collect_pages, QueryBudget, CALL_COST},
+    query_objects::{
+        medicine_levels_in_pregnancy::report::{get_all_reports, Report},
+        products::product::{handle_doc, Product},
+    },
+};
+use async_graphql::SimpleObject;
+use search_client::{
+    filter::{Filter, Operator},
+    models::{IndexResult, ProductLicence},
+    Search,
+};
+
+#[SimpleObject(desc = "A product licence, with the products and reports that refer to it")]
+#[derive(Debug, PartialEq)]
+pub struct Licence {
+    #[field(desc = "Licence number, e.g. PL 12345/0001")]
+    number: String,
+    #[field(desc = "Products with SPCs, PILs or PARs under the licence")]
+    products: Vec<Product>,
+    #[field(desc = "Reports related to medicine levels in pregnancy that cover the licence")]
+    reports: Vec<Report>,
+}
+
+// Licence numbers are written in all sorts of ways, e.g. `PL 12345/0001`, `pl12345-0001` or `12345/0001`.
+pub fn parse_licence(number: &str) -> Option<ProductLicence> {
+    ProductLicence::find_all_assuming_pl(number)
+        .into_iter()
+        .next()
+}
+
+pub async fn get_licence(
+    licence: &ProductLicence,
+    products_client: &impl Search,
+    bmgf_client: &impl Search,
+    budget: &QueryBudget,
+) -> Result<Licence, anyhow::Error> {
+    // Both indexes hold licences in their canonical form.
+    let canonical = licence.canonical();
+    let documents_filter = Filter::any("pl_number", Operator::Eq, canonical.as_str());
+    let documents = collect_pages(
+        budget,
+        products_client.stream_documents(Some(documents_filter)),
+    );
+    let reports_filter = Filter::any("pl_numbers", Operator::Eq, canonical.as_str());
+    let reports = get_licence_reports(bmgf_client, budget, &reports_filter);
+    let (documents, reports): (Vec<IndexResult>, _) = futures::try_join!(documents, reports)?;
+
+    let mut products = Vec::<Product>::new();
+
+    for result in documents {
+        let document = result.into();
+
+        handle_doc(&document, &mut products);
+    }
+
+    products.sort();
+
+    Ok(Licence {
+        number: licence.to_string(),
+        products,
+        reports,
+    })
+}
+
+async fn get_licence_reports(
+    client: &impl Search,
+    budget: &QueryBudget,
+    filter: &Filter,
+) -> Result<Vec<Report>, anyhow::Error> {
+    budget.spend(CALL_COST)?;
+    get_all_reports(client, filter).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use search_client::InMemorySearchClient;
+    use serde_json::json;
+    use test_case::test_case;
+
+    #[test_case("PL 12345/0010", Some("PL123450010"))]
+    #[test_case("pl12345-0010", Some("PL123450010"))]
+    #[test_case("12345/0010", Some("PL123450010"))]
+    #[test_case("PLGB 12345/0010", Some("PLGB123450010"))]
+    #[test_case("THR 12345 0010", Some("THR123450010"))]
+    #[test_case("ibuprofen", None)]
+    fn test_parse_licence(number: &str, expected: Option<&str>) {
+        assert_eq!(
+            parse_licence(number).map(|licence| licence.canonical()),
+            expected.map(String::from)
+        );
+    }
+
+    fn given_a_products_client() -> InMemorySearchClient {
+        let document = |name: &str, product_name: &str, pl_number: &str| {
+            json!({
+                "metadata_storage_name": name,
+                "metadata_storage_path": "test/path",
+                "metadata_storage_size": 300,
+                "file_name": name,
+                "doc_type": "Spc",
+                "title": "title",
+                "product_name": product_name,
+                "substance_name": ["IBUPROFEN"],
+                "pl_number": [pl_number],
+                "facets": [],
+                "suggestions": [],
+            })
+        };
+
+        InMemorySearchClient::with_documents(
+            "metadata_storage_name",
+            vec![
+                document("first", "IBUPROFEN 400MG TABLETS", "PL123450010"),
+                document("second", "IBUPROFEN 200MG TABLETS", "PL123450010"),
+                document("third", "IBUPROFEN 200MG TABLETS", "PL123450010"),
+                document("fourth", "NUROFEN 200MG TABLETS", "PL543210001"),
+            ],
+        )
+    }
+
+    fn given_a_bmgf_client() -> InMemorySearchClient {
+        InMemorySearchClient::with_documents(
+            "metadata_storage_name",
+            vec![json!({
+                "metadata_storage_name": "report",
+                "metadata_storage_path": "test/path",
+                "metadata_storage_size": 300,
+                "file_name": "report",
+                "report_name": "Ibuprofen report",
+                "summary": "summary",
+                "active_substances": ["IBUPROFEN"],
+                "products": ["IBUPROFEN 200MG TABLETS"],
+                "pl_numbers": ["PL123450010", "PL999990001"],
+            })],
+        )
+    }
+
+    fn when_we_get_the_licence(number: &str) -> Licence {
+        let licence = parse_licence(number).unwrap();
+        tokio_test::block_on(get_licence(
+            &licence,
+            &given_a_products_client(),
+            &given_a_bmgf_client(),
+            &QueryBudget::new(2),
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_get_licence() {
+        let licence = when_we_get_the_licence("PL 12345/0010");
+
+        assert_eq!(licence.number, "PL 12345/0010");
+        assert_eq!(licence.products.len(), 2);
+        assert_eq!(
+            licence
+                .reports
+                .iter()
+                .map(|report| report.title.as_deref())
+                .collect::<Vec<_>>(),
+            vec![Some("Ibuprofen report")]
+        );
+    }
+
+    #[test]
+    fn test_get_licence_without_products_or_reports() {
+        let licence = when_we_get_the_licence("PL 11111/0001");
+
+        assert_eq!(licence.products, vec![]);
+        assert_eq!(licence.reports, vec![]);
+    }
+}
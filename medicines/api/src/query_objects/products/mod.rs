@@ -1,5 +1,8 @@
 pub mod document;
+pub mod document_loader;
+pub mod licence;
 pub mod product;
 pub mod products_index;
 pub mod query_root;
 pub mod substance;
+pub mod suggestion;
@@ -1,22 +1,85 @@
 use crate::{
-    azure_context::AzureContext,
-    query_objects::products::document::{
-        self, get_documents, get_documents_graph_from_documents_vector, Document,
+    error::{bad_user_input, not_found, to_field_error},
+    pagination::{last_window, Page, PageArgs},
+    query_budget::{azure_context, QueryBudget, CALL_COST, SUMMARY_COST},
+    query_objects::medicine_levels_in_pregnancy::report::{get_pregnancy_reports, Report},
+    query_objects::products::{
+        document::{
+            self, get_documents_fingerprint, get_documents_graph_from_documents_vector, Document,
+        },
+        document_loader::DocumentLoader,
     },
+    query_objects::shared::created::CreatedRange,
 };
-use anyhow::anyhow;
-use async_graphql::{Context, FieldResult, Object};
-use search_client::models::{DocumentType, TerritoryType};
+use async_graphql::{Context, FieldError, FieldResult, Object, SimpleObject};
+use futures::{future::try_join_all, lock::Mutex};
+use search_client::{
+    facet::{FacetRequest, FacetSort},
+    filter::{Filter, Operator},
+    models::{DocumentType, FacetCounts, IndexResults, ProductLicence, TerritoryType},
+    sort::{OrderBy, SortDirection},
+    AzurePagination, Search,
+};
+use std::cmp::Ordering;
+
+// Enough facet values for every licence and active substance of a product.
+const MAX_FACET_VALUES: usize = 1000;
+
+#[SimpleObject(desc = "The documents of one type associated with a product")]
+#[derive(Debug, Clone, Eq, Ord, PartialEq, PartialOrd)]
+pub struct ProductDocumentSummary {
+    #[field(desc = "Document type")]
+    pub doc_type: DocumentType,
+    #[field(desc = "Number of documents")]
+    pub count: i32,
+    #[field(desc = "Created date of the latest document")]
+    pub latest_created: Option<String>,
+}
 
-#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct ProductSummary {
+    licences: Vec<String>,
+    active_substances: Vec<String>,
+    territories: Vec<TerritoryType>,
+    documents: Vec<ProductDocumentSummary>,
+}
+
+#[derive(Debug)]
 pub struct Product {
     name: String,
     documents: Option<Vec<Document>>,
+    // Kept once fetched, as several fields are resolved from the same summary.
+    summary: Mutex<Option<ProductSummary>>,
+}
+
+// Products are told apart by name and documents, whether or not their summary has been fetched yet.
+impl PartialEq for Product {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.documents == other.documents
+    }
+}
+
+impl Eq for Product {}
+
+impl PartialOrd for Product {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Product {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.name, &self.documents).cmp(&(&other.name, &other.documents))
+    }
 }
 
 impl Product {
     pub fn new(name: String, documents: Option<Vec<Document>>) -> Self {
-        Self { name, documents }
+        Self {
+            name,
+            documents,
+            summary: Mutex::new(None),
+        }
     }
 
     pub fn add(&mut self, document: Document) {
@@ -26,6 +89,23 @@ impl Product {
             self.documents = Some(vec![document])
         }
     }
+
+    // Products listed under a substance are only loaded with their documents, so their summary is fetched when asked for.
+    // The lock is held while fetching, so that fields resolved at the same time wait for it rather than fetch it again.
+    async fn summary(&self, context: &Context<'_>) -> FieldResult<ProductSummary> {
+        let mut summary = self.summary.lock().await;
+        if let Some(summary) = &*summary {
+            return Ok(summary.clone());
+        }
+
+        let context = azure_context(context, SUMMARY_COST)?;
+        let fetched = get_product_summary(&context.products_client, &self.name)
+            .await
+            .map_err(|e| to_field_error(&e))?;
+        *summary = Some(fetched.clone());
+
+        Ok(fetched)
+    }
 }
 
 #[Object(desc = "A medical product containing active ingredients")]
@@ -35,71 +115,131 @@ impl Product {
         &self.name
     }
 
+    #[field(desc = "Licence numbers of the product")]
+    async fn licences(&self, context: &Context<'_>) -> FieldResult<Vec<String>> {
+        Ok(self.summary(context).await?.licences)
+    }
+
+    #[field(desc = "Active substances of the product")]
+    async fn active_substances(&self, context: &Context<'_>) -> FieldResult<Vec<String>> {
+        Ok(self.summary(context).await?.active_substances)
+    }
+
+    #[field(desc = "Territories the product's documents cover")]
+    async fn territories(&self, context: &Context<'_>) -> FieldResult<Vec<TerritoryType>> {
+        Ok(self.summary(context).await?.territories)
+    }
+
+    #[field(desc = "Number of documents of each type, and when the latest one was created")]
+    async fn document_summaries(
+        &self,
+        context: &Context<'_>,
+    ) -> FieldResult<Vec<ProductDocumentSummary>> {
+        Ok(self.summary(context).await?.documents)
+    }
+
+    #[field(
+        desc = "Reports related to medicine levels in pregnancy that cover one of the product's licences, or name it"
+    )]
+    async fn pregnancy_reports(&self, context: &Context<'_>) -> FieldResult<Vec<Report>> {
+        let licences = self.summary(context).await?.licences;
+        let context = azure_context(context, CALL_COST)?;
+
+        get_pregnancy_reports(&context.bmgf_client, &self.name, &licences)
+            .await
+            .map_err(|e| to_field_error(&e))
+    }
+
+    #[allow(clippy::too_many_arguments)]
     #[field(desc = "Documents related to product")]
     async fn documents(
         &self,
         context: &Context<'_>,
         first: Option<i32>,
         offset: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
         document_types: Option<Vec<DocumentType>>,
         territory_types: Option<Vec<TerritoryType>>,
+        created_after: Option<String>,
+        created_before: Option<String>,
     ) -> FieldResult<document::Documents> {
-        let context = context.data::<AzureContext>()?;
+        let created = CreatedRange::parse(created_after.as_deref(), created_before.as_deref())
+            .map_err(bad_user_input)?;
+        let fingerprint = get_documents_fingerprint(
+            "",
+            document_types.clone(),
+            territory_types.clone(),
+            Some(&self.name),
+            &created,
+            &[],
+        );
+        let page = PageArgs {
+            first,
+            after,
+            last,
+            before,
+            skip: offset,
+        }
+        .page(&fingerprint)
+        .map_err(bad_user_input)?;
 
-        let offset = match offset {
-            Some(a) => a,
-            None => 0,
+        let docs = match self.documents.clone() {
+            Some(docs) => docs,
+            None => {
+                let loader = context.data::<DocumentLoader>()?;
+                let budget = context.data::<QueryBudget>()?;
+                // The loader pays for each query it makes, however many products it loads with it.
+                let context = azure_context(context, 0)?;
+
+                loader
+                    .load(&context.products_client, budget, &self.name)
+                    .await
+                    .map_err(|e| to_field_error(&e))?
+            }
         };
 
-        if let Some(docs) = self.documents.clone() {
-            let docs = match document_types {
-                Some(document_types) => docs
-                    .into_iter()
-                    .filter(|x| document_types.iter().any(|&f| x.is_doc_type(f)))
-                    .collect(),
-                None => docs,
-            };
-
-            let docs = match territory_types {
-                Some(territory_types) => docs
-                    .into_iter()
-                    .filter(|x| territory_types.iter().any(|&f| x.is_territory_type(f)))
-                    .collect(),
-                None => docs,
-            };
-
-            let total_count = docs.len() as i32;
-
-            let docs = match first {
-                Some(t) => docs.into_iter().take(t as usize).collect(),
-                None => docs,
-            };
-
-            Ok(get_documents_graph_from_documents_vector(
-                docs,
-                offset,
-                total_count,
-            ))
-        } else {
-            get_documents(
-                &context.products_client,
-                "",
-                first,
-                offset,
-                document_types,
-                territory_types,
-                Some(&self.name),
-            )
-            .await
-            .map(Into::into)
-            .map_err(|e| {
-                tracing::error!(
-                    "Error fetching documents from Azure search service: {:?}",
-                    e
-                );
-                anyhow!("Error retrieving results").into()
-            })
-        }
+        let docs = match document_types {
+            Some(document_types) => docs
+                .into_iter()
+                .filter(|x| document_types.iter().any(|&f| x.is_doc_type(f)))
+                .collect(),
+            None => docs,
+        };
+
+        let docs = match territory_types {
+            Some(territory_types) => docs
+                .into_iter()
+                .filter(|x| territory_types.iter().any(|&f| x.is_territory_type(f)))
+                .collect(),
+            None => docs,
+        };
+
+        let docs: Vec<Document> = docs
+            .into_iter()
+            .filter(|x| x.is_created_within(&created))
+            .collect();
+
+        let total_count = docs.len() as i32;
+        // All of the product's documents are loaded already, so the last of them can be found straight away.
+        let (offset, count) = match page {
+            Page::Range { offset, count } => (offset, count),
+            Page::Last { offset, count } => last_window(offset, count, total_count),
+        };
+
+        let docs = docs
+            .into_iter()
+            .skip(offset as usize)
+            .take(count as usize)
+            .collect();
+
+        Ok(get_documents_graph_from_documents_vector(
+            docs,
+            &fingerprint,
+            offset,
+            total_count,
+        ))
     }
 }
 
@@ -120,14 +260,164 @@ pub fn handle_doc(document: &Document, products: &mut Vec<Product>) {
     }
 }
 
-pub async fn get_product(product_name: String) -> Result<Product, reqwest::Error> {
-    Ok(Product::new(product_name, None))
+// Product names are indexed in upper case.
+pub async fn get_product(
+    client: &impl Search,
+    product_name: &str,
+) -> Result<Option<Product>, anyhow::Error> {
+    let name = product_name.to_uppercase();
+    let summary = get_product_summary(client, &name).await?;
+    if summary.documents.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(Product {
+        name,
+        documents: None,
+        summary: Mutex::new(Some(summary)),
+    }))
+}
+
+// Products with documents under any of the licences, or with any of the names. Only their names are
+// fetched, so that their documents are all of theirs, loaded like those of any other product.
+pub async fn get_related_products(
+    client: &impl Search,
+    licences: &[String],
+    product_names: &[String],
+) -> Result<Vec<Product>, anyhow::Error> {
+    let filters: Vec<Filter> = licences
+        .iter()
+        .map(|licence| Filter::any("pl_number", Operator::Eq, licence.as_str()))
+        .chain(
+            product_names
+                .iter()
+                .map(|product_name| Filter::eq("product_name", product_name.to_uppercase())),
+        )
+        .collect();
+
+    if filters.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let facets = [FacetRequest::new("product_name")
+        .count(MAX_FACET_VALUES)
+        .sort(FacetSort::Value(SortDirection::Asc))];
+    let counts = client
+        .search_facets("", Some(&Filter::or(filters)), &facets)
+        .await?;
+
+    Ok(facet_values(&counts, "product_name")
+        .into_iter()
+        .map(|product_name| Product::new(product_name, None))
+        .collect())
+}
+
+pub fn product_not_found(product_name: &str) -> FieldError {
+    not_found(format!("Product {} was not found", product_name))
+}
+
+async fn get_product_summary(
+    client: &impl Search,
+    product_name: &str,
+) -> Result<ProductSummary, anyhow::Error> {
+    let filter = Filter::eq("product_name", product_name);
+    let facets = [
+        FacetRequest::new("pl_number")
+            .count(MAX_FACET_VALUES)
+            .sort(FacetSort::Value(SortDirection::Asc)),
+        FacetRequest::new("substance_name")
+            .count(MAX_FACET_VALUES)
+            .sort(FacetSort::Value(SortDirection::Asc)),
+        FacetRequest::new("territory").sort(FacetSort::Value(SortDirection::Asc)),
+        FacetRequest::new("doc_type").sort(FacetSort::Value(SortDirection::Asc)),
+    ];
+    let counts = client.search_facets("", Some(&filter), &facets).await?;
+
+    let documents = try_join_all(
+        parse_buckets::<DocumentType>(&counts, "doc_type")
+            .into_iter()
+            .map(|(doc_type, count)| get_document_summary(client, &filter, doc_type, count)),
+    )
+    .await?;
+
+    Ok(ProductSummary {
+        licences: facet_values(&counts, "pl_number")
+            .into_iter()
+            .map(|licence| match licence.parse::<ProductLicence>() {
+                Ok(licence) => licence.to_string(),
+                Err(_) => licence,
+            })
+            .collect(),
+        active_substances: facet_values(&counts, "substance_name"),
+        territories: parse_buckets::<TerritoryType>(&counts, "territory")
+            .into_iter()
+            .map(|(territory, _)| territory)
+            .collect(),
+        documents,
+    })
+}
+
+async fn get_document_summary(
+    client: &impl Search,
+    product_filter: &Filter,
+    doc_type: DocumentType,
+    count: i32,
+) -> Result<ProductDocumentSummary, anyhow::Error> {
+    let filter = Filter::and(vec![
+        product_filter.clone(),
+        Filter::eq("doc_type", doc_type.to_string()),
+    ]);
+    let latest = client
+        .search_with_pagination_and_filter::<IndexResults>(
+            "",
+            AzurePagination {
+                result_count: 1,
+                offset: 0,
+            },
+            false,
+            Some(&filter),
+            &[OrderBy::desc("created")],
+        )
+        .await?;
+
+    Ok(ProductDocumentSummary {
+        doc_type,
+        count,
+        latest_created: latest
+            .search_results
+            .into_iter()
+            .next()
+            .and_then(|document| document.created),
+    })
+}
+
+fn facet_values(counts: &FacetCounts, field: &str) -> Vec<String> {
+    counts
+        .buckets(field)
+        .iter()
+        .map(|bucket| bucket.value.to_string())
+        .collect()
+}
+
+fn parse_buckets<T>(counts: &FacetCounts, field: &str) -> Vec<(T, i32)>
+where
+    T: std::str::FromStr,
+{
+    counts
+        .buckets(field)
+        .iter()
+        .filter_map(|bucket| {
+            let value = bucket.value.to_string().parse().ok()?;
+            Some((value, bucket.count))
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use search_client::models::IndexResult;
+    use search_client::{models::IndexResult, InMemorySearchClient};
+    use serde_json::{json, Value};
 
     fn azure_result_factory(product_name: Option<String>) -> Document {
         let result = IndexResult {
@@ -191,4 +481,102 @@ mod test {
         assert_eq!(products[1].name, "B");
         assert_eq!(products[2].name, "C");
     }
+
+    fn given_a_document(name: &str, doc_type: &str, created: &str, pl_number: &str) -> Value {
+        json!({
+            "metadata_storage_name": name,
+            "metadata_storage_path": "test/path",
+            "metadata_storage_size": 300,
+            "file_name": name,
+            "doc_type": doc_type,
+            "title": "title",
+            "created": created,
+            "product_name": "IBUPROFEN 200MG TABLETS",
+            "substance_name": ["IBUPROFEN"],
+            "pl_number": [pl_number],
+            "territory": "UK",
+            "facets": [],
+            "suggestions": [],
+        })
+    }
+
+    fn given_a_search_client() -> InMemorySearchClient {
+        InMemorySearchClient::with_documents(
+            "metadata_storage_name",
+            vec![
+                given_a_document("first", "Spc", "2019-01-01T00:00:00Z", "PL123450010"),
+                given_a_document("second", "Spc", "2020-06-01T00:00:00Z", "PL123450010"),
+                given_a_document("third", "Pil", "2018-03-01T00:00:00Z", "PL123450011"),
+            ],
+        )
+    }
+
+    fn when_we_get_the_product(name: &str) -> Option<Product> {
+        let client = given_a_search_client();
+        tokio_test::block_on(get_product(&client, name)).unwrap()
+    }
+
+    #[test]
+    fn test_get_product() {
+        let product = when_we_get_the_product("Ibuprofen 200mg tablets").unwrap();
+
+        assert_eq!(product.name, "IBUPROFEN 200MG TABLETS");
+        assert_eq!(
+            product.summary.into_inner().unwrap(),
+            ProductSummary {
+                licences: vec!["PL 12345/0010".to_string(), "PL 12345/0011".to_string()],
+                active_substances: vec!["IBUPROFEN".to_string()],
+                territories: vec![TerritoryType::UK],
+                documents: vec![
+                    ProductDocumentSummary {
+                        doc_type: DocumentType::Pil,
+                        count: 1,
+                        latest_created: Some("2018-03-01T00:00:00Z".to_string()),
+                    },
+                    ProductDocumentSummary {
+                        doc_type: DocumentType::Spc,
+                        count: 2,
+                        latest_created: Some("2020-06-01T00:00:00Z".to_string()),
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_unknown_product() {
+        assert_eq!(when_we_get_the_product("PARACETAMOL 500MG TABLETS"), None);
+    }
+
+    fn when_we_get_related_products(licences: &[&str], product_names: &[&str]) -> Vec<Product> {
+        let client = given_a_search_client();
+        let licences: Vec<String> = licences.iter().map(|l| l.to_string()).collect();
+        let product_names: Vec<String> = product_names.iter().map(|p| p.to_string()).collect();
+        tokio_test::block_on(get_related_products(&client, &licences, &product_names)).unwrap()
+    }
+
+    #[test]
+    fn test_get_related_products_by_licence() {
+        let products = when_we_get_related_products(&["PL123450011"], &[]);
+
+        assert_eq!(
+            products,
+            vec![Product::new("IBUPROFEN 200MG TABLETS".to_string(), None)]
+        );
+    }
+
+    #[test]
+    fn test_get_related_products_by_name() {
+        let products = when_we_get_related_products(&[], &["Ibuprofen 200mg tablets"]);
+
+        assert_eq!(
+            products,
+            vec![Product::new("IBUPROFEN 200MG TABLETS".to_string(), None)]
+        );
+    }
+
+    #[test]
+    fn test_get_related_products_without_licences_or_names() {
+        assert_eq!(when_we_get_related_products(&[], &[]), vec![]);
+    }
 }
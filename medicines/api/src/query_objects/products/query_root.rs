@@ -1,15 +1,23 @@
 use crate::{
-    azure_context::AzureContext,
-    pagination::get_offset_or_default,
+    error::{bad_user_input, to_field_error},
+    pagination::PageArgs,
+    query_budget::{azure_context, page_cost, QueryBudget, CALL_COST, LOOKUP_COST, SUMMARY_COST},
     query_objects::products::{
-        document::{get_documents, Documents},
-        product::{get_product, Product},
+        document::{
+            get_document, get_documents, Document, DocumentFacetField, DocumentOrderBy, Documents,
+        },
+        licence::{get_licence, parse_licence, Licence},
+        product::{get_product, product_not_found, Product},
         products_index::{get_products_index, ProductIndex},
         substance::{get_substance_with_products, Substance},
+        suggestion::{get_suggestions, Suggestion},
+    },
+    query_objects::shared::{
+        created::CreatedRange,
+        order_by::to_order_by,
+        substances_index::{get_substances_index, parse_letter, SubstanceIndex},
     },
-    query_objects::shared::substances_index::{get_substances_index, SubstanceIndex},
 };
-use anyhow::anyhow;
 use async_graphql::{Context, FieldResult, Object};
 use search_client::models::{DocumentType, TerritoryType};
 
@@ -23,26 +31,44 @@ impl Products {
         context: &Context<'_>,
         name: Option<String>,
     ) -> FieldResult<Substance> {
-        let context = context.data::<AzureContext>()?;
+        let context = azure_context(context, CALL_COST)?;
         match name {
             Some(name) => get_substance_with_products(&name, &context.products_client)
                 .await
-                .map_err(|e| {
-                    tracing::error!("Error fetching results from Azure search service: {:?}", e);
-                    anyhow!("Error retrieving results").into()
-                }),
-            None => Err(anyhow::anyhow!(
-                "Getting a substance without providing a substance name is not supported."
-            )
-            .into()),
+                .map_err(|e| to_field_error(&e)),
+            None => Err(bad_user_input(
+                "Getting a substance without providing a substance name is not supported.",
+            )),
         }
     }
     #[field(desc = "Retrieves all documents associated with the queried product")]
-    async fn product(&self, _context: &Context<'_>, name: String) -> FieldResult<Product> {
-        get_product(name).await.map_err(|e| {
-            tracing::error!("Error fetching results from Azure search service: {:?}", e);
-            anyhow!("Error retrieving results").into()
-        })
+    async fn product(&self, context: &Context<'_>, name: String) -> FieldResult<Product> {
+        let context = azure_context(context, SUMMARY_COST)?;
+        get_product(&context.products_client, &name)
+            .await
+            .map_err(|e| to_field_error(&e))?
+            .ok_or_else(|| product_not_found(&name))
+    }
+
+    #[field(
+        desc = "Retrieves the products and reports related to medicine levels in pregnancy that refer to the queried licence number"
+    )]
+    async fn by_licence(&self, context: &Context<'_>, number: String) -> FieldResult<Licence> {
+        let licence = parse_licence(&number).ok_or_else(|| {
+            bad_user_input(format!("{} is not a recognised licence number", number))
+        })?;
+        let budget = context.data::<QueryBudget>()?;
+        // Each page of products and reports is paid for as it's fetched.
+        let context = azure_context(context, 0)?;
+
+        get_licence(
+            &licence,
+            &context.products_client,
+            &context.bmgf_client,
+            budget,
+        )
+        .await
+        .map_err(|e| to_field_error(&e))
     }
 
     #[field(
@@ -53,13 +79,11 @@ impl Products {
         context: &Context<'_>,
         letter: String,
     ) -> FieldResult<Vec<SubstanceIndex>> {
-        let context = context.data::<AzureContext>()?;
-        get_substances_index(&context.products_client, letter.chars().next().unwrap())
+        let context = azure_context(context, CALL_COST)?;
+        let letter = parse_letter(&letter).map_err(bad_user_input)?;
+        get_substances_index(&context.products_client, letter)
             .await
-            .map_err(|e| {
-                tracing::error!("Error fetching results from Azure search service: {:?}", e);
-                anyhow!("Error retrieving results").into()
-            })
+            .map_err(|e| to_field_error(&e))
     }
 
     #[field(
@@ -70,13 +94,31 @@ impl Products {
         context: &Context<'_>,
         substance: String,
     ) -> FieldResult<Vec<ProductIndex>> {
-        let context = context.data::<AzureContext>()?;
+        let context = azure_context(context, CALL_COST)?;
         get_products_index(&context.products_client, &substance)
             .await
-            .map_err(|e| {
-                tracing::error!("Error fetching results from Azure search service: {:?}", e);
-                anyhow!("Error retrieving results").into()
-            })
+            .map_err(|e| to_field_error(&e))
+    }
+
+    #[field(desc = "Type-ahead suggestions for the provided partial search term")]
+    async fn suggestions(
+        &self,
+        context: &Context<'_>,
+        term: String,
+        first: Option<i32>,
+    ) -> FieldResult<Vec<Suggestion>> {
+        let context = azure_context(context, CALL_COST)?;
+        get_suggestions(&context.products_client, &term, first)
+            .await
+            .map_err(|e| to_field_error(&e))
+    }
+
+    #[field(desc = "An SPC, PIL or PAR document, found by its id or its content id")]
+    async fn document(&self, context: &Context<'_>, id: String) -> FieldResult<Option<Document>> {
+        let context = azure_context(context, LOOKUP_COST)?;
+        get_document(&context.products_client, &id)
+            .await
+            .map_err(|e| to_field_error(&e))
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -88,26 +130,41 @@ impl Products {
         first: Option<i32>,
         skip: Option<i32>,
         after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
         document_types: Option<Vec<DocumentType>>,
         territory_types: Option<Vec<TerritoryType>>,
+        created_after: Option<String>,
+        created_before: Option<String>,
+        order_by: Option<Vec<DocumentOrderBy>>,
+        facets: Option<Vec<DocumentFacetField>>,
     ) -> FieldResult<Documents> {
-        let context = context.data::<AzureContext>()?;
-        let offset = get_offset_or_default(skip, after, 0);
+        let budget = context.data::<QueryBudget>()?;
+        let context = azure_context(context, page_cost(first, last))?;
+        let created = CreatedRange::parse(created_after.as_deref(), created_before.as_deref())
+            .map_err(bad_user_input)?;
+        let page = PageArgs {
+            first,
+            after,
+            last,
+            before,
+            skip,
+        };
 
         get_documents(
             &context.products_client,
+            budget,
             search.as_deref().unwrap_or(" "),
-            first,
-            offset,
+            &page,
             document_types,
             territory_types,
             None,
+            &created,
+            &to_order_by(order_by),
+            &facets.unwrap_or_default(),
         )
         .await
         .map(Into::into)
-        .map_err(|e| {
-            tracing::error!("Error fetching results from Azure search service: {:?}", e);
-            anyhow!("Error retrieving results").into()
-        })
+        .map_err(|e| to_field_error(&e))
     }
 }
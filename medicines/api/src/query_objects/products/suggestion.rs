@@ -0,0 +1,114 @@
+use async_graphql::SimpleObject;
+use search_client::{models::SuggestResults, Search, SuggestOptions};
+
+const HIGHLIGHT_PRE_TAG: &str = "<em>";
+const HIGHLIGHT_POST_TAG: &str = "</em>";
+// Azure Search rejects suggestion requests for more than 100 results.
+const MAX_SUGGESTIONS: i32 = 100;
+
+#[SimpleObject(desc = "A suggested search term for the products search box")]
+#[derive(Debug, PartialEq)]
+pub struct Suggestion {
+    #[field(desc = "Suggested search term")]
+    text: String,
+    #[field(
+        desc = "Suggested search term with the parts matching the typed term wrapped in <em> tags"
+    )]
+    highlighted_text: String,
+}
+
+impl Suggestion {
+    pub fn new(highlighted_text: String) -> Self {
+        Self {
+            text: highlighted_text
+                .replace(HIGHLIGHT_PRE_TAG, "")
+                .replace(HIGHLIGHT_POST_TAG, ""),
+            highlighted_text,
+        }
+    }
+}
+
+pub async fn get_suggestions(
+    client: &impl Search,
+    term: &str,
+    first: Option<i32>,
+) -> anyhow::Result<Vec<Suggestion>> {
+    let term = term.trim();
+    if term.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let first = first.unwrap_or(5).max(0).min(MAX_SUGGESTIONS) as usize;
+
+    // Many documents share a product name or title, so ask for extra results to make up for the duplicates.
+    let options = SuggestOptions {
+        fuzzy: true,
+        highlight_pre_tag: Some(HIGHLIGHT_PRE_TAG.to_string()),
+        highlight_post_tag: Some(HIGHLIGHT_POST_TAG.to_string()),
+        top: MAX_SUGGESTIONS,
+    };
+    let azure_result = client.suggest(term, &options).await?;
+
+    Ok(format_suggestions(azure_result, first))
+}
+
+fn format_suggestions(results: SuggestResults, first: usize) -> Vec<Suggestion> {
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+
+    for result in results.suggest_results {
+        let suggestion = Suggestion::new(result.text);
+        if !suggestions
+            .iter()
+            .any(|existing| existing.text.eq_ignore_ascii_case(&suggestion.text))
+        {
+            suggestions.push(suggestion);
+        }
+    }
+
+    suggestions.truncate(first);
+    suggestions
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use search_client::models::SuggestResult;
+
+    fn given_suggest_results(texts: Vec<&str>) -> SuggestResults {
+        SuggestResults {
+            suggest_results: texts
+                .into_iter()
+                .map(|text| SuggestResult {
+                    text: text.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn formats_suggestions_without_duplicates() {
+        let results = given_suggest_results(vec![
+            "<em>IBUPROFEN</em> 200MG TABLETS",
+            "<em>IBUPROFEN</em> 200MG TABLETS",
+            "<em>Ibuprofen</em> 200mg tablets",
+            "<em>IBUPROFEN</em> 400MG TABLETS",
+            "<em>IBUPROFEN</em>",
+        ]);
+
+        let formatted = format_suggestions(results, 2);
+
+        assert_eq!(
+            formatted,
+            vec![
+                Suggestion {
+                    text: "IBUPROFEN 200MG TABLETS".to_string(),
+                    highlighted_text: "<em>IBUPROFEN</em> 200MG TABLETS".to_string(),
+                },
+                Suggestion {
+                    text: "IBUPROFEN 400MG TABLETS".to_string(),
+                    highlighted_text: "<em>IBUPROFEN</em> 400MG TABLETS".to_string(),
+                },
+            ]
+        );
+    }
+}
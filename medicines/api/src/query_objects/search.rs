@@ -0,0 +1,368 @@
+use crate::{
+    pagination::{encode_cursor, fingerprint, Page, PageArgs, PageInfo, PaginationError},
+    query_budget::{results_cost, QueryBudget},
+    query_objects::{medicine_levels_in_pregnancy::report::Report, products::document::Document},
+};
+use async_graphql::{Enum, SimpleObject, Union};
+use search_client::{
+    models::{IndexResults, ReportResults},
+    AzurePagination, Search,
+};
+use std::cmp::Ordering;
+
+// Azure Search returns at most this many results ($top) from one request. As each source's results are
+// fetched from the start to merge them, only the first of them from each source can be paged through.
+const MAX_RESULTS_PER_SOURCE: i32 = 1000;
+
+#[Union(desc = "An SPC, PIL or PAR document, or a report related to medicine levels in pregnancy")]
+pub enum SearchResult {
+    Document(Document),
+    Report(Report),
+}
+
+#[Enum(desc = "An index searched")]
+#[derive(Debug)]
+pub enum SearchSource {
+    #[item(desc = "SPC, PIL and PAR documents")]
+    Documents,
+    #[item(desc = "Reports related to medicine levels in pregnancy")]
+    Reports,
+}
+
+#[SimpleObject(desc = "The number of results matching a search in one of the indexes searched")]
+#[derive(Debug, PartialEq)]
+pub struct SearchSourceCount {
+    #[field(desc = "Index searched")]
+    pub source: SearchSource,
+    #[field(desc = "Number of matching results")]
+    pub count: i32,
+}
+
+#[SimpleObject]
+pub struct SearchResultEdge {
+    node: SearchResult,
+    cursor: String,
+    #[field(
+        desc = "How well the result matches the search, from 0 to 1, comparable across indexes"
+    )]
+    score: f64,
+}
+
+#[SimpleObject]
+pub struct SearchResults {
+    page_info: PageInfo,
+    total_count: i32,
+    edges: Vec<SearchResultEdge>,
+    #[field(desc = "Number of matching results in each of the indexes searched")]
+    facets: Vec<SearchSourceCount>,
+}
+
+pub async fn search(
+    products_client: &impl Search,
+    bmgf_client: &impl Search,
+    budget: &QueryBudget,
+    term: &str,
+    page: &PageArgs,
+) -> Result<SearchResults, anyhow::Error> {
+    let fingerprint = get_search_fingerprint(term);
+    let (offset, count) = match page.page(&fingerprint)? {
+        Page::Range { offset, count } => (offset, count),
+        // The total is only known after fetching the results, so there is no way to tell where the last ones start.
+        Page::Last { .. } => return Err(PaginationError::LastWithoutBefore.into()),
+    };
+
+    // Each source's best results are needed to know which of them come first overall, so each of them is paid
+    // for every result up to the end of the page, not just for the page.
+    let result_count = (offset + count).min(MAX_RESULTS_PER_SOURCE);
+    budget.spend(2 * results_cost(result_count))?;
+    let pagination = || AzurePagination {
+        result_count,
+        offset: 0,
+    };
+    let (documents, reports) = futures::try_join!(
+        products_client.search_with_pagination::<IndexResults>(term, pagination(), true),
+        bmgf_client.search_with_pagination::<ReportResults>(term, pagination(), true),
+    )?;
+
+    let document_count = documents.count.unwrap_or(0);
+    let report_count = reports.count.unwrap_or(0);
+    let reachable_count =
+        document_count.min(MAX_RESULTS_PER_SOURCE) + report_count.min(MAX_RESULTS_PER_SOURCE);
+
+    let results = merge(
+        normalise(
+            documents
+                .search_results
+                .into_iter()
+                .map(|result| (result.score, SearchResult::Document(result.into())))
+                .collect(),
+        ),
+        normalise(
+            reports
+                .search_results
+                .into_iter()
+                .map(|result| (result.score, SearchResult::Report(result.into())))
+                .collect(),
+        ),
+    );
+
+    let edges: Vec<SearchResultEdge> = results
+        .into_iter()
+        .enumerate()
+        .skip(offset as usize)
+        .take(count as usize)
+        .map(|(i, (score, node))| SearchResultEdge {
+            node,
+            cursor: encode_cursor(&fingerprint, i as i32),
+            score,
+        })
+        .collect();
+
+    Ok(SearchResults {
+        page_info: PageInfo::build(&fingerprint, offset, edges.len() as i32, reachable_count),
+        total_count: document_count + report_count,
+        edges,
+        facets: vec![
+            SearchSourceCount {
+                source: SearchSource::Documents,
+                count: document_count,
+            },
+            SearchSourceCount {
+                source: SearchSource::Reports,
+                count: report_count,
+            },
+        ],
+    })
+}
+
+// Kept apart from the fingerprints of documents and reports, whose cursors would otherwise be accepted here.
+fn get_search_fingerprint(term: &str) -> String {
+    fingerprint(&format!("search:{}", term.trim()), None, &[])
+}
+
+// Azure's scores only mean anything relative to others from the same index, so they're scaled by the best of them.
+fn normalise<T>(results: Vec<(f32, T)>) -> Vec<(f64, T)> {
+    let best = results
+        .iter()
+        .map(|(score, _)| *score)
+        .fold(0.0_f32, f32::max);
+
+    results
+        .into_iter()
+        .map(|(score, result)| {
+            let score = if best > 0.0 { score / best } else { 0.0 };
+            (f64::from(score), result)
+        })
+        .collect()
+}
+
+// Both lists are already in order of score, which the stable sort keeps between equal scores.
+fn merge<T>(first: Vec<(f64, T)>, second: Vec<(f64, T)>) -> Vec<(f64, T)> {
+    let mut results: Vec<(f64, T)> = first.into_iter().chain(second).collect();
+    results.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+    results
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::query_budget::BudgetExceeded;
+    use search_client::InMemorySearchClient;
+    use serde_json::json;
+
+    #[test]
+    fn test_normalise() {
+        assert_eq!(
+            normalise(vec![(4.0, "a"), (2.0, "b"), (1.0, "c")]),
+            vec![(1.0, "a"), (0.5, "b"), (0.25, "c")]
+        );
+        assert_eq!(normalise(vec![(0.0, "a")]), vec![(0.0, "a")]);
+    }
+
+    #[test]
+    fn test_merge() {
+        assert_eq!(
+            merge(
+                vec![(1.0, "first document"), (0.5, "second document")],
+                vec![(1.0, "first report"), (0.75, "second report")]
+            ),
+            vec![
+                (1.0, "first document"),
+                (1.0, "first report"),
+                (0.75, "second report"),
+                (0.5, "second document"),
+            ]
+        );
+    }
+
+    fn given_a_products_client() -> InMemorySearchClient {
+        let document = |name: &str, product_name: &str, substance_name: &str| {
+            json!({
+                "metadata_storage_name": name,
+                "metadata_storage_path": "test/path",
+                "metadata_storage_size": 300,
+                "file_name": name,
+                "doc_type": "Spc",
+                "title": "title",
+                "product_name": product_name,
+                "substance_name": [substance_name],
+                "facets": [],
+                "suggestions": [],
+            })
+        };
+
+        InMemorySearchClient::with_documents(
+            "metadata_storage_name",
+            vec![
+                document("first", "IBUPROFEN 200MG TABLETS", "IBUPROFEN"),
+                document("second", "NUROFEN 200MG TABLETS", "IBUPROFEN"),
+                document("third", "PARACETAMOL 500MG TABLETS", "PARACETAMOL"),
+            ],
+        )
+    }
+
+    fn given_a_bmgf_client() -> InMemorySearchClient {
+        InMemorySearchClient::with_documents(
+            "id",
+            vec![json!({
+                "id": "report",
+                "metadata_storage_name": "report",
+                "metadata_storage_path": "test/path",
+                "metadata_storage_size": 300,
+                "file_name": "report",
+                "report_name": "Ibuprofen report",
+                "summary": "summary",
+                "active_substances": ["IBUPROFEN"],
+            })],
+        )
+    }
+
+    fn when_we_search_for(term: &str, page: &PageArgs) -> Result<SearchResults, anyhow::Error> {
+        when_we_search_within_budget(term, page, &QueryBudget::new(100))
+    }
+
+    fn when_we_search_within_budget(
+        term: &str,
+        page: &PageArgs,
+        budget: &QueryBudget,
+    ) -> Result<SearchResults, anyhow::Error> {
+        tokio_test::block_on(search(
+            &given_a_products_client(),
+            &given_a_bmgf_client(),
+            budget,
+            term,
+            page,
+        ))
+    }
+
+    #[test]
+    fn test_search_across_both_indexes() {
+        let results = when_we_search_for("ibuprofen", &PageArgs::default()).unwrap();
+
+        assert_eq!(results.total_count, 3);
+        assert_eq!(
+            results.facets,
+            vec![
+                SearchSourceCount {
+                    source: SearchSource::Documents,
+                    count: 2,
+                },
+                SearchSourceCount {
+                    source: SearchSource::Reports,
+                    count: 1,
+                },
+            ]
+        );
+        assert_eq!(results.edges.len(), 3);
+        assert!(results.edges.iter().any(|edge| match &edge.node {
+            SearchResult::Report(report) => report.id.as_deref() == Some("report"),
+            _ => false,
+        }));
+        assert!(results
+            .edges
+            .windows(2)
+            .all(|edges| edges[0].score >= edges[1].score));
+    }
+
+    #[test]
+    fn test_search_pages_through_the_merged_results() {
+        let first_page = when_we_search_for(
+            "ibuprofen",
+            &PageArgs {
+                first: Some(2),
+                ..PageArgs::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(first_page.edges.len(), 2);
+        assert!(first_page.page_info.has_next_page);
+
+        let second_page = when_we_search_for(
+            "ibuprofen",
+            &PageArgs {
+                first: Some(2),
+                after: first_page.page_info.end_cursor,
+                ..PageArgs::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(second_page.edges.len(), 1);
+        assert!(!second_page.page_info.has_next_page);
+    }
+
+    #[test]
+    fn test_search_pays_for_the_results_before_the_page() {
+        // The page after the 490th result needs the first 500 results of each index.
+        let page = || PageArgs {
+            first: Some(10),
+            after: Some(encode_cursor(&get_search_fingerprint("ibuprofen"), 489)),
+            ..PageArgs::default()
+        };
+
+        let error = when_we_search_within_budget("ibuprofen", &page(), &QueryBudget::new(101))
+            .err()
+            .unwrap();
+        assert!(error.downcast::<BudgetExceeded>().is_ok());
+
+        let results =
+            when_we_search_within_budget("ibuprofen", &page(), &QueryBudget::new(102)).unwrap();
+        assert_eq!(results.edges.len(), 0);
+    }
+
+    #[test]
+    fn test_search_rejects_last_without_before() {
+        let error = when_we_search_for(
+            "ibuprofen",
+            &PageArgs {
+                last: Some(2),
+                ..PageArgs::default()
+            },
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(
+            error.downcast::<PaginationError>().unwrap(),
+            PaginationError::LastWithoutBefore
+        );
+    }
+
+    #[test]
+    fn test_search_rejects_a_cursor_from_documents() {
+        let cursor = encode_cursor(&fingerprint("ibuprofen", None, &[]), 0);
+        let error = when_we_search_for(
+            "ibuprofen",
+            &PageArgs {
+                after: Some(cursor),
+                ..PageArgs::default()
+            },
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(
+            error.downcast::<PaginationError>().unwrap(),
+            PaginationError::CursorFromAnotherQuery
+        );
+    }
+}
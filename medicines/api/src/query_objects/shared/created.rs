@@ -0,0 +1,160 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use search_client::filter::Filter;
+use std::fmt::{Display, Formatter};
+
+const CREATED_FIELD: &str = "created";
+
+// Documents created from `after` up to, but not including, `before`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CreatedRange {
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CreatedRangeError {
+    InvalidDate {
+        argument: &'static str,
+        value: String,
+    },
+    Empty,
+}
+
+impl Display for CreatedRangeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CreatedRangeError::InvalidDate { argument, value } => write!(
+                f,
+                "{} must be an ISO-8601 date, or date and time, such as 2021-01-01 or 2021-01-01T09:30:00Z, not {}",
+                argument, value
+            ),
+            CreatedRangeError::Empty => write!(f, "createdAfter must be before createdBefore"),
+        }
+    }
+}
+
+impl std::error::Error for CreatedRangeError {}
+
+impl CreatedRange {
+    pub fn parse(after: Option<&str>, before: Option<&str>) -> Result<Self, CreatedRangeError> {
+        let range = CreatedRange {
+            after: after
+                .map(|after| parse_date("createdAfter", after))
+                .transpose()?,
+            before: before
+                .map(|before| parse_date("createdBefore", before))
+                .transpose()?,
+        };
+
+        match (range.after, range.before) {
+            (Some(after), Some(before)) if after >= before => Err(CreatedRangeError::Empty),
+            _ => Ok(range),
+        }
+    }
+
+    pub fn filter(&self) -> Option<Filter> {
+        let filters: Vec<Filter> = self
+            .after
+            .map(|after| Filter::ge(CREATED_FIELD, after))
+            .into_iter()
+            .chain(self.before.map(|before| Filter::lt(CREATED_FIELD, before)))
+            .collect();
+
+        if filters.is_empty() {
+            None
+        } else {
+            Some(Filter::and(filters))
+        }
+    }
+
+    // For results which have already been fetched, where `created` is as stored in the index.
+    pub fn contains(&self, created: Option<&str>) -> bool {
+        if self.after.is_none() && self.before.is_none() {
+            return true;
+        }
+
+        match created.and_then(|created| DateTime::parse_from_rfc3339(created).ok()) {
+            Some(created) => {
+                let created = created.with_timezone(&Utc);
+                self.after.iter().all(|&after| created >= after)
+                    && self.before.iter().all(|&before| created < before)
+            }
+            None => false,
+        }
+    }
+}
+
+// Dates without a time are taken to start at midnight UTC.
+fn parse_date(argument: &'static str, value: &str) -> Result<DateTime<Utc>, CreatedRangeError> {
+    let trimmed = value.trim();
+    let date_time = match DateTime::parse_from_rfc3339(trimmed) {
+        Ok(date_time) => Some(date_time.with_timezone(&Utc)),
+        Err(_) => NaiveDate::parse_from_str(trimmed, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|date_time| Utc.from_utc_datetime(&date_time)),
+    };
+
+    date_time.ok_or_else(|| CreatedRangeError::InvalidDate {
+        argument,
+        value: value.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(Some("2021-01-01"), None, Some("created ge 2021-01-01T00:00:00Z"))]
+    #[test_case(
+        None,
+        Some("2021-02-01T09:30:00+01:00"),
+        Some("created lt 2021-02-01T08:30:00Z")
+    )]
+    #[test_case(
+        Some("2021-01-01T00:00:00Z"),
+        Some("2021-02-01"),
+        Some("(created ge 2021-01-01T00:00:00Z and created lt 2021-02-01T00:00:00Z)")
+    )]
+    #[test_case(None, None, None)]
+    fn test_filter(after: Option<&str>, before: Option<&str>, expected: Option<&str>) {
+        let range = CreatedRange::parse(after, before).unwrap();
+        assert_eq!(
+            range.filter().map(|filter| filter.to_string()),
+            expected.map(String::from)
+        );
+    }
+
+    #[test_case(Some("01/02/2021"), None; "for a date in another format")]
+    #[test_case(Some("2021-02-30"), None; "for a date that doesn't exist")]
+    #[test_case(None, Some("yesterday"); "for something else")]
+    fn test_invalid_dates(after: Option<&str>, before: Option<&str>) {
+        assert!(matches!(
+            CreatedRange::parse(after, before),
+            Err(CreatedRangeError::InvalidDate { .. })
+        ));
+    }
+
+    #[test]
+    fn test_empty_range() {
+        assert_eq!(
+            CreatedRange::parse(Some("2021-02-01"), Some("2021-01-01")),
+            Err(CreatedRangeError::Empty)
+        );
+    }
+
+    #[test_case(Some("2021-01-15T10:00:00+00:00"), true)]
+    #[test_case(Some("2021-02-01T00:00:00+00:00"), false)]
+    #[test_case(Some("2020-12-31T23:59:59+00:00"), false)]
+    #[test_case(None, false)]
+    fn test_contains(created: Option<&str>, expected: bool) {
+        let range = CreatedRange::parse(Some("2021-01-01"), Some("2021-02-01")).unwrap();
+        assert_eq!(range.contains(created), expected);
+    }
+
+    #[test]
+    fn test_contains_without_a_range() {
+        assert!(CreatedRange::default().contains(None));
+    }
+}
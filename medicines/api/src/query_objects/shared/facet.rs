@@ -0,0 +1,20 @@
+use async_graphql::SimpleObject;
+use search_client::models::FacetBucket;
+
+#[SimpleObject(desc = "The number of results with a value of a field")]
+#[derive(Debug, PartialEq)]
+pub struct FacetCount {
+    #[field(desc = "Value of the field")]
+    pub value: String,
+    #[field(desc = "Number of results with the value")]
+    pub count: i32,
+}
+
+impl From<&FacetBucket> for FacetCount {
+    fn from(bucket: &FacetBucket) -> Self {
+        Self {
+            value: bucket.value.to_string(),
+            count: bucket.count,
+        }
+    }
+}
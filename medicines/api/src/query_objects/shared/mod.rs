@@ -1 +1,4 @@
+pub mod created;
+pub mod facet;
+pub mod order_by;
 pub mod substances_index;
@@ -0,0 +1,31 @@
+use async_graphql::Enum;
+use search_client::sort;
+
+#[Enum(desc = "The direction to sort results in")]
+#[derive(Debug)]
+pub enum SortDirection {
+    #[item(desc = "Ascending, e.g. A to Z or oldest first")]
+    Asc,
+    #[item(desc = "Descending, e.g. Z to A or newest first")]
+    Desc,
+}
+
+impl From<SortDirection> for sort::SortDirection {
+    fn from(direction: SortDirection) -> Self {
+        match direction {
+            SortDirection::Asc => sort::SortDirection::Asc,
+            SortDirection::Desc => sort::SortDirection::Desc,
+        }
+    }
+}
+
+pub fn to_order_by<T>(order_by: Option<Vec<T>>) -> Vec<sort::OrderBy>
+where
+    T: Into<sort::OrderBy>,
+{
+    order_by
+        .unwrap_or_default()
+        .into_iter()
+        .map(Into::into)
+        .collect()
+}
@@ -1,5 +1,6 @@
 use async_graphql::SimpleObject;
 use search_client::{models::FacetResults, Search};
+use std::fmt::{Display, Formatter};
 
 #[SimpleObject(desc = "The number of documents associated with an active substance")]
 #[derive(Debug, PartialEq)]
@@ -14,6 +15,30 @@ impl SubstanceIndex {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub struct InvalidLetter(String);
+
+impl Display for InvalidLetter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "letter must be a single letter or digit, not \"{}\"",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidLetter {}
+
+pub fn parse_letter(letter: &str) -> Result<char, InvalidLetter> {
+    let mut chars = letter.trim().chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphanumeric() => Ok(c),
+        _ => Err(InvalidLetter(letter.to_string())),
+    }
+}
+
 pub async fn get_substances_index(
     client: &impl Search,
     letter: char,
@@ -58,6 +83,19 @@ mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
     use search_client::models::{Facet, FacetResult};
+    use test_case::test_case;
+
+    #[test_case("a", Some('a'); "for a lower case letter")]
+    #[test_case(" Z ", Some('Z'); "for a letter with spaces around it")]
+    #[test_case("7", Some('7'); "for a digit")]
+    #[test_case("", None; "for nothing")]
+    #[test_case("  ", None; "for spaces")]
+    #[test_case("ab", None; "for more than one letter")]
+    #[test_case("*", None; "for punctuation")]
+    #[test_case("é", None; "for an accented letter")]
+    fn test_parse_letter(letter: &str, expected: Option<char>) {
+        assert_eq!(parse_letter(letter).ok(), expected);
+    }
 
     #[test]
     fn formats_substance_index_results() {
@@ -0,0 +1,291 @@
+use crate::error::ErrorCode;
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    net::{AddrParseError, IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use warp::{http::StatusCode, Filter, Rejection, Reply};
+
+// How often clients whose buckets have filled back up are forgotten, as a new bucket would be no different.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+impl warp::reject::Reject for RateLimited {}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Buckets {
+    clients: HashMap<IpAddr, Bucket>,
+    evicted: Instant,
+}
+
+// A token bucket for each client: every request takes a token, and tokens come back at a steady
+// rate up to `capacity`, so that clients can make short bursts of requests but not keep them up.
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_second: f64,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(capacity: u32, refill_per_second: f64) -> Self {
+        Self {
+            capacity: f64::from(capacity),
+            refill_per_second,
+            buckets: Mutex::new(Buckets {
+                clients: HashMap::new(),
+                evicted: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn check(&self, client: IpAddr, now: Instant) -> Result<(), RateLimited> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if now.saturating_duration_since(buckets.evicted) >= EVICTION_INTERVAL {
+            buckets
+                .clients
+                .retain(|_, bucket| self.refill(bucket, now) < self.capacity);
+            buckets.evicted = now;
+        }
+
+        let capacity = self.capacity;
+        let bucket = buckets.clients.entry(client).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.tokens = self.refill(bucket, now);
+        bucket.updated = now;
+
+        if bucket.tokens < 1.0 {
+            let wait = (1.0 - bucket.tokens) / self.refill_per_second;
+            return Err(RateLimited {
+                retry_after: Duration::from_secs(wait.ceil().max(1.0) as u64),
+            });
+        }
+
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.refill_per_second).min(self.capacity)
+    }
+}
+
+// A comma separated list of the addresses of proxies in front of the API, such as the service mesh sidecar.
+pub fn parse_trusted_proxies(addresses: &str) -> Result<Vec<IpAddr>, AddrParseError> {
+    addresses
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+// `X-Forwarded-For` is only believed when the connection comes from a trusted proxy. Each proxy appends
+// the address it was connected to from, so the client is the last address that isn't a trusted proxy;
+// anything before that is whatever the client sent.
+fn client_address(
+    forwarded_for: Option<&str>,
+    remote: Option<SocketAddr>,
+    trusted_proxies: &[IpAddr],
+) -> Option<IpAddr> {
+    let mut client = remote?.ip();
+
+    for address in forwarded_for.unwrap_or_default().rsplit(',') {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+        match address.trim().parse() {
+            Ok(address) => client = address,
+            Err(_) => break,
+        }
+    }
+
+    Some(client)
+}
+
+pub fn rate_limit(
+    limiter: Arc<RateLimiter>,
+    trusted_proxies: Arc<Vec<IpAddr>>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-forwarded-for")
+        .and(warp::addr::remote())
+        .and_then(
+            move |forwarded_for: Option<String>, remote: Option<SocketAddr>| {
+                let limiter = limiter.clone();
+                let trusted_proxies = trusted_proxies.clone();
+                async move {
+                    match client_address(forwarded_for.as_deref(), remote, &trusted_proxies) {
+                        Some(client) => limiter
+                            .check(client, Instant::now())
+                            .map_err(warp::reject::custom),
+                        None => Ok(()),
+                    }
+                }
+            },
+        )
+        .untuple_one()
+}
+
+pub fn too_many_requests(rate_limited: &RateLimited) -> impl Reply {
+    let seconds = rate_limited.retry_after.as_secs();
+    let body = warp::reply::json(&json!({
+        "errors": [{
+            "message": format!("Too many requests, try again in {} seconds", seconds),
+            "extensions": { "code": ErrorCode::RateLimited.to_string() },
+        }],
+    }));
+
+    // Rejected requests never reach the CORS filter, but browsers can't read the response without this.
+    let reply = warp::reply::with_header(body, "retry-after", seconds.to_string());
+    let reply = warp::reply::with_header(reply, "access-control-allow-origin", "*");
+    warp::reply::with_status(reply, StatusCode::TOO_MANY_REQUESTS)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    fn client(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn test_check_allows_a_burst_up_to_capacity() {
+        let limiter = RateLimiter::new(3, 1.0);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check(client("10.0.0.1"), now).is_ok());
+        }
+
+        let error = limiter.check(client("10.0.0.1"), now).unwrap_err();
+        assert_eq!(error.retry_after, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_check_refills_over_time() {
+        let limiter = RateLimiter::new(2, 0.5);
+        let now = Instant::now();
+        limiter.check(client("10.0.0.1"), now).unwrap();
+        limiter.check(client("10.0.0.1"), now).unwrap();
+
+        let error = limiter.check(client("10.0.0.1"), now).unwrap_err();
+        assert_eq!(error.retry_after, Duration::from_secs(2));
+
+        assert!(limiter
+            .check(client("10.0.0.1"), now + Duration::from_secs(2))
+            .is_ok());
+        assert!(limiter
+            .check(client("10.0.0.1"), now + Duration::from_secs(2))
+            .is_err());
+    }
+
+    #[test]
+    fn test_check_limits_each_client_separately() {
+        let limiter = RateLimiter::new(1, 1.0);
+        let now = Instant::now();
+
+        assert!(limiter.check(client("10.0.0.1"), now).is_ok());
+        assert!(limiter.check(client("10.0.0.1"), now).is_err());
+        assert!(limiter.check(client("10.0.0.2"), now).is_ok());
+    }
+
+    fn tracked_clients(limiter: &RateLimiter) -> usize {
+        limiter.buckets.lock().unwrap().clients.len()
+    }
+
+    #[test]
+    fn test_check_forgets_clients_whose_buckets_have_refilled() {
+        let limiter = RateLimiter::new(1, 1.0);
+        let now = Instant::now();
+
+        for i in 0..100u32 {
+            limiter.check(IpAddr::from(i.to_be_bytes()), now).unwrap();
+        }
+        limiter
+            .check(client("10.0.0.1"), now + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(tracked_clients(&limiter), 101);
+
+        limiter
+            .check(client("10.0.0.2"), now + EVICTION_INTERVAL)
+            .unwrap();
+        assert_eq!(tracked_clients(&limiter), 1);
+    }
+
+    #[test]
+    fn test_check_remembers_clients_still_refilling() {
+        let limiter = RateLimiter::new(10, 0.01);
+        let now = Instant::now();
+        limiter.check(client("10.0.0.1"), now).unwrap();
+
+        limiter
+            .check(client("10.0.0.2"), now + EVICTION_INTERVAL)
+            .unwrap();
+
+        assert_eq!(tracked_clients(&limiter), 2);
+    }
+
+    #[test_case("", &[]; "for none")]
+    #[test_case("127.0.0.1", &["127.0.0.1"]; "for one")]
+    #[test_case("127.0.0.1, 127.0.0.6,", &["127.0.0.1", "127.0.0.6"]; "for several")]
+    fn test_parse_trusted_proxies(addresses: &str, expected: &[&str]) {
+        assert_eq!(
+            parse_trusted_proxies(addresses).unwrap(),
+            expected
+                .iter()
+                .map(|&address| client(address))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_parse_trusted_proxies_rejects_invalid_addresses() {
+        assert!(parse_trusted_proxies("127.0.0.1, localhost").is_err());
+    }
+
+    const PROXY: &str = "127.0.0.6:15006";
+
+    #[test_case(Some("203.0.113.7"), Some("192.0.2.1:443"), &[], Some("192.0.2.1"); "for an untrusted connection")]
+    #[test_case(None, Some("192.0.2.1:443"), &[], Some("192.0.2.1"); "for a direct connection")]
+    #[test_case(Some("203.0.113.7"), Some(PROXY), &["127.0.0.6"], Some("203.0.113.7"); "for a forwarded address")]
+    #[test_case(Some("198.51.100.1, 203.0.113.7"), Some(PROXY), &["127.0.0.6"], Some("203.0.113.7"); "for the address appended by the proxy")]
+    #[test_case(Some("198.51.100.1, 203.0.113.7, 10.0.0.5"), Some(PROXY), &["127.0.0.6", "10.0.0.5"], Some("203.0.113.7"); "through several proxies")]
+    #[test_case(Some("not an address"), Some(PROXY), &["127.0.0.6"], Some("127.0.0.6"); "for an invalid forwarded address")]
+    #[test_case(None, Some(PROXY), &["127.0.0.6"], Some("127.0.0.6"); "for a proxy without a forwarded address")]
+    #[test_case(None, None, &[], None; "for no address")]
+    fn test_client_address(
+        forwarded_for: Option<&str>,
+        remote: Option<&str>,
+        trusted_proxies: &[&str],
+        expected: Option<&str>,
+    ) {
+        let trusted_proxies: Vec<IpAddr> = trusted_proxies
+            .iter()
+            .map(|&address| client(address))
+            .collect();
+
+        assert_eq!(
+            client_address(
+                forwarded_for,
+                remote.map(|remote| remote.parse().unwrap()),
+                &trusted_proxies
+            ),
+            expected.map(client)
+        );
+    }
+}
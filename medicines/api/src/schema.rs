@@ -1,19 +1,24 @@
 use crate::{
     azure_context::AzureContext,
-    pagination::get_offset_or_default,
+    error::{bad_user_input, to_field_error},
+    pagination::PageArgs,
+    query_budget::{azure_context, page_cost, QueryBudget, CALL_COST, SUMMARY_COST},
     query_objects::medicine_levels_in_pregnancy::query_root::MedicineLevelsInPregnancy,
     query_objects::{
         products::{
             document::{get_documents, Documents},
-            product::{get_product, Product},
+            product::{get_product, product_not_found, Product},
             products_index::{get_products_index, ProductIndex},
             query_root::Products,
             substance::{get_substance_with_products, Substance},
         },
-        shared::substances_index::{get_substances_index, SubstanceIndex},
+        search::{search, SearchResults},
+        shared::{
+            created::CreatedRange,
+            substances_index::{get_substances_index, parse_letter, SubstanceIndex},
+        },
     },
 };
-use anyhow::anyhow;
 use async_graphql::{Context, EmptyMutation, EmptySubscription, FieldResult, Object, Schema};
 use search_client::models::{DocumentType, TerritoryType};
 
@@ -27,26 +32,23 @@ impl QueryRoot {
         context: &Context<'_>,
         name: Option<String>,
     ) -> FieldResult<Substance> {
-        let context = context.data::<AzureContext>()?;
+        let context = azure_context(context, CALL_COST)?;
         match name {
             Some(name) => get_substance_with_products(&name, &context.products_client)
                 .await
-                .map_err(|e| {
-                    tracing::error!("Error fetching results from Azure search service: {:?}", e);
-                    anyhow!("Error retrieving results").into()
-                }),
-            None => Err(anyhow::anyhow!(
-                "Getting a substance without providing a substance name is not supported."
-            )
-            .into()),
+                .map_err(|e| to_field_error(&e)),
+            None => Err(bad_user_input(
+                "Getting a substance without providing a substance name is not supported.",
+            )),
         }
     }
     #[field(deprecation = "Please use `products::product` instead")]
-    async fn product(&self, _context: &Context<'_>, name: String) -> FieldResult<Product> {
-        get_product(name).await.map_err(|e| {
-            tracing::error!("Error fetching results from Azure search service: {:?}", e);
-            anyhow!("Error retrieving results").into()
-        })
+    async fn product(&self, context: &Context<'_>, name: String) -> FieldResult<Product> {
+        let context = azure_context(context, SUMMARY_COST)?;
+        get_product(&context.products_client, &name)
+            .await
+            .map_err(|e| to_field_error(&e))?
+            .ok_or_else(|| product_not_found(&name))
     }
 
     #[field(deprecation = "Please use `products::substances_index` instead")]
@@ -55,13 +57,11 @@ impl QueryRoot {
         context: &Context<'_>,
         letter: String,
     ) -> FieldResult<Vec<SubstanceIndex>> {
-        let context = context.data::<AzureContext>()?;
-        get_substances_index(&context.products_client, letter.chars().next().unwrap())
+        let context = azure_context(context, CALL_COST)?;
+        let letter = parse_letter(&letter).map_err(bad_user_input)?;
+        get_substances_index(&context.products_client, letter)
             .await
-            .map_err(|e| {
-                tracing::error!("Error fetching results from Azure search service: {:?}", e);
-                anyhow!("Error retrieving results").into()
-            })
+            .map_err(|e| to_field_error(&e))
     }
 
     #[field(deprecation = "Please use `products::products_index` instead")]
@@ -70,13 +70,10 @@ impl QueryRoot {
         context: &Context<'_>,
         substance: String,
     ) -> FieldResult<Vec<ProductIndex>> {
-        let context = context.data::<AzureContext>()?;
+        let context = azure_context(context, CALL_COST)?;
         get_products_index(&context.products_client, &substance)
             .await
-            .map_err(|e| {
-                tracing::error!("Error fetching results from Azure search service: {:?}", e);
-                anyhow!("Error retrieving results").into()
-            })
+            .map_err(|e| to_field_error(&e))
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -91,24 +88,63 @@ impl QueryRoot {
         document_types: Option<Vec<DocumentType>>,
         territory_types: Option<Vec<TerritoryType>>,
     ) -> FieldResult<Documents> {
-        let context = context.data::<AzureContext>()?;
-        let offset = get_offset_or_default(skip, after, 0);
+        let budget = context.data::<QueryBudget>()?;
+        let context = azure_context(context, page_cost(first, None))?;
+        let page = PageArgs {
+            first,
+            after,
+            skip,
+            ..PageArgs::default()
+        };
 
         get_documents(
             &context.products_client,
+            budget,
             search.as_deref().unwrap_or(" "),
-            first,
-            offset,
+            &page,
             document_types,
             territory_types,
             None,
+            &CreatedRange::default(),
+            &[],
+            &[],
         )
         .await
         .map(Into::into)
-        .map_err(|e| {
-            tracing::error!("Error fetching results from Azure search service: {:?}", e);
-            anyhow!("Error retrieving results").into()
-        })
+        .map_err(|e| to_field_error(&e))
+    }
+
+    #[field(
+        desc = "Searches SPC, PIL and PAR documents and reports related to medicine levels in pregnancy together, best matches first"
+    )]
+    async fn search(
+        &self,
+        context: &Context<'_>,
+        term: String,
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> FieldResult<SearchResults> {
+        let budget = context.data::<QueryBudget>()?;
+        let context = azure_context(context, 0)?;
+        let page = PageArgs {
+            first,
+            after,
+            last,
+            before,
+            ..PageArgs::default()
+        };
+
+        search(
+            &context.products_client,
+            &context.bmgf_client,
+            budget,
+            &term,
+            &page,
+        )
+        .await
+        .map_err(|e| to_field_error(&e))
     }
 
     async fn products(&self, _context: &Context<'_>) -> FieldResult<Products> {
@@ -127,41 +163,15 @@ type QuerySchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
 pub struct ApiSchema(pub QuerySchema);
 
 impl ApiSchema {
-    pub fn new(context: AzureContext) -> ApiSchema {
+    // Queries nested deeper than `max_depth`, or selecting more than `max_complexity` fields, are
+    // rejected before any of them is resolved.
+    pub fn new(context: AzureContext, max_depth: usize, max_complexity: usize) -> ApiSchema {
         ApiSchema(
             Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
                 .data(context)
+                .limit_depth(max_depth)
+                .limit_complexity(max_complexity)
                 .finish(),
         )
     }
 }
-
-#[cfg(test)]
-mod test {
-    use super::*;
-
-    use crate::pagination::convert_after_to_offset;
-    use test_case::test_case;
-
-    #[test_case("LTE=".to_string(), 0; "for the first page of results")]
-    #[test_case("MA==".to_string(), 1; "for grabbing the second result onwards")]
-    #[test_case("OQ==".to_string(), 10; "for the second page of results when pagesize is 10")]
-    #[test_case(base64::encode("1229".to_string()), 1230; "for as late as page 124")]
-    fn test_convert_after_to_offset(encoded: String, expected: i32) {
-        assert_eq!(convert_after_to_offset(encoded).unwrap(), expected);
-    }
-
-    #[test_case(Some(10), Some("LTE=".to_string()), 15, 0; "matches after when only after is provided")]
-    #[test_case(Some(10), None, 15, 10; "matches skip when only skip is provided")]
-    #[test_case(None, Some("LTE=".to_string()), 15, 0; "matches after when both are provided")]
-    #[test_case(None, None, 10, 10; "matches default when neither are provided")]
-    fn test_get_offset_or_default(
-        skip: Option<i32>,
-        after: Option<String>,
-        default: i32,
-        expected: i32,
-    ) {
-        let offset = get_offset_or_default(skip, after, default);
-        assert_eq!(offset, expected);
-    }
-}
@@ -66,21 +66,33 @@ async fn handle_processing_error_for_create_message<T>(
 where
     T: RemovableMessage<CreateMessage>,
 {
-    if let ProcessMessageError::StorageClientError(StorageClientError::SftpError(
-        SftpError::CouldNotRetrieveFile,
-    )) = error
-    {
-        tracing::warn!("Couldn't find file. Updating state to Error and removing message.");
-        let _ = state_manager
-            .set_status(
-                removable_message.get_message().job_id,
-                JobStatus::Error {
-                    message: "Couldn't find file".to_string(),
-                    code: "404".to_string(),
-                },
-            )
-            .await?;
-        let _ = removable_message.remove().await?;
+    match error {
+        ProcessMessageError::StorageClientError(StorageClientError::SftpError(
+            SftpError::CouldNotRetrieveFile,
+        )) => {
+            tracing::warn!("Couldn't find file. Updating state to Error and removing message.");
+            let _ = state_manager
+                .set_status(
+                    removable_message.get_message().job_id,
+                    JobStatus::Error {
+                        message: "Couldn't find file".to_string(),
+                        code: "404".to_string(),
+                    },
+                )
+                .await?;
+            let _ = removable_message.remove().await?;
+        }
+        ProcessMessageError::Search(e) if !e.is_retryable() => {
+            tracing::error!(
+                "Couldn't add to search index ({}). Updating state to Error and removing message.",
+                e
+            );
+            let _ = state_manager
+                .set_status(removable_message.get_message().job_id, (&e).into())
+                .await?;
+            let _ = removable_message.remove().await?;
+        }
+        _ => {}
     }
     Ok(())
 }
@@ -168,6 +180,15 @@ mod test {
         ))
     }
 
+    fn given_a_search_error(status: u16) -> ProcessMessageError {
+        search_client::SearchError::Http {
+            status: reqwest::StatusCode::from_u16(status).unwrap(),
+            body: String::from("Forbidden"),
+            retry_after: None,
+        }
+        .into()
+    }
+
     fn given_we_have_a_create_message() -> TestRemovableMessage<CreateMessage> {
         TestRemovableMessage::<CreateMessage> {
             message: get_test_create_message(Uuid::new_v4()),
@@ -219,4 +240,37 @@ mod test {
             "Message should be removed"
         );
     }
+
+    #[test]
+    fn test_a_permanent_search_error_removes_create_message() {
+        let mut removable_message = given_we_have_a_create_message();
+        let error = given_a_search_error(403);
+
+        let result = when_we_handle_the_error(
+            &mut removable_message,
+            error,
+            TestJobStatusClient::accepted(),
+        );
+
+        assert!(result.is_ok());
+        assert!(
+            removable_message.remove_was_called,
+            "Message should be removed"
+        );
+    }
+
+    #[test]
+    fn test_a_retryable_search_error_does_not_remove_create_message() {
+        let mut removable_message = given_we_have_a_create_message();
+        let error = given_a_search_error(429);
+
+        let result = when_we_handle_the_error(
+            &mut removable_message,
+            error,
+            TestJobStatusClient::accepted(),
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(removable_message.remove_was_called, false);
+    }
 }
@@ -1,8 +1,7 @@
 use super::sanitiser::{SanitisedString, VecSanitisedString};
 use crate::{create_manager::Blob, models::Document};
 use chrono::{SecondsFormat, Utc};
-use regex::Regex;
-use search_client::models::{DocumentType, IndexEntry, TerritoryType};
+use search_client::models::{DocumentType, IndexEntry, ProductLicence, TerritoryType};
 use std::{collections::HashMap, str};
 
 #[derive(Clone, Debug, PartialEq)]
@@ -19,25 +18,11 @@ pub struct BlobMetadata {
 }
 
 fn derive_territory(pl_number: &str, territory: Option<TerritoryType>) -> Option<TerritoryType> {
-    match territory {
-        Some(t) => Some(t),
-        None => match pl_number
-            .chars()
-            .filter(|c| c.is_alphabetic())
-            .collect::<String>()
-            .as_ref()
-        {
-            "PL" => Some(TerritoryType::UK),
-            "PLPI" => Some(TerritoryType::UK),
-            "PLNI" => Some(TerritoryType::NI),
-            "PLGB" => Some(TerritoryType::GB),
-            "THRNI" => Some(TerritoryType::NI),
-            "THRGB" => Some(TerritoryType::GB),
-            "NRNI" => Some(TerritoryType::NI),
-            "NRGB" => Some(TerritoryType::GB),
-            _ => None,
-        },
-    }
+    territory.or_else(|| {
+        ProductLicence::find_all(pl_number)
+            .first()
+            .and_then(ProductLicence::territory)
+    })
 }
 
 impl BlobMetadata {
@@ -199,18 +184,9 @@ pub fn create_facets_by_active_substance(
 }
 
 pub fn format_product_licence(input: &str) -> String {
-    lazy_static! {
-        static ref RE_WHITESPACE: Regex = Regex::new(r"(\s+|/|_|-)").expect("cannot compile regex");
-        static ref RE_PL: Regex = Regex::new(r"(?i:[A-Z]+)(\s+|/|_|-)*\d{5}(\s+|/|_|-)*\d{4}")
-            .expect("cannot compile regex");
-    }
-    let product_licences: Vec<String> = RE_PL
-        .find_iter(input)
-        .map(|m| {
-            RE_WHITESPACE
-                .replace_all(m.as_str(), "")
-                .to_ascii_uppercase()
-        })
+    let product_licences: Vec<String> = ProductLicence::find_all(input)
+        .iter()
+        .map(ProductLicence::canonical)
         .collect();
 
     to_json(product_licences)
@@ -328,7 +304,8 @@ mod test {
     #[test_case("PLNI 12345/1234", "[\"PLNI123451234\"]")]
     #[test_case("THR 12345/1234", "[\"THR123451234\"]")]
     #[test_case("NR 12345/1234", "[\"NR123451234\"]")]
-    #[test_case("NEW 12345/1234", "[\"NEW123451234\"]")]
+    #[test_case("NEW 12345/1234", "[\"NEW123451234\"]"; "passes an unknown prefix through")]
+    #[test_case("PL 12345/1234, new-12345-5678", "[\"PL123451234\",\"NEW123455678\"]")]
     #[test_case("12345/1234", "[]")]
     #[test_case("NO PL", "[]")]
     fn format_product_licence_test(input: &str, output: &str) {
@@ -375,18 +352,34 @@ mod test {
         )
     }
 
-    #[test_case(None, "PL123456", Some(TerritoryType::UK))]
-    #[test_case(None, "PLPI123456", Some(TerritoryType::UK))]
-    #[test_case(None, "PLNI123456", Some(TerritoryType::NI))]
-    #[test_case(None, "PLGB123456", Some(TerritoryType::GB))]
-    #[test_case(None, "THRNI123456", Some(TerritoryType::NI))]
-    #[test_case(None, "THRGB123456", Some(TerritoryType::GB))]
-    #[test_case(None, "NRNI123456", Some(TerritoryType::NI))]
-    #[test_case(None, "NRGB123456", Some(TerritoryType::GB))]
-    #[test_case(Some(TerritoryType::UK), "PLNI123456", Some(TerritoryType::UK))]
-    #[test_case(Some(TerritoryType::NI), "PL123456", Some(TerritoryType::NI))]
-    #[test_case(Some(TerritoryType::NI), "PLPI123456", Some(TerritoryType::NI))]
-    #[test_case(Some(TerritoryType::GB), "PLNI123456", Some(TerritoryType::GB))]
+    #[test_case(None, "[\"PL123451234\"]", Some(TerritoryType::UK))]
+    #[test_case(None, "[\"PLPI123451234\"]", Some(TerritoryType::UK))]
+    #[test_case(None, "[\"PLNI123451234\"]", Some(TerritoryType::NI))]
+    #[test_case(None, "[\"PLGB123451234\"]", Some(TerritoryType::GB))]
+    #[test_case(None, "[\"THR123451234\"]", Some(TerritoryType::UK))]
+    #[test_case(None, "[\"THRNI123451234\"]", Some(TerritoryType::NI))]
+    #[test_case(None, "[\"THRGB123451234\"]", Some(TerritoryType::GB))]
+    #[test_case(None, "[\"NRNI123451234\"]", Some(TerritoryType::NI))]
+    #[test_case(None, "[\"NRGB123451234\"]", Some(TerritoryType::GB))]
+    #[test_case(None, "THRGB 12345/1234", Some(TerritoryType::GB))]
+    #[test_case(None, "[\"NEW123451234\"]", None)]
+    #[test_case(None, "[]", None)]
+    #[test_case(
+        Some(TerritoryType::UK),
+        "[\"PLNI123451234\"]",
+        Some(TerritoryType::UK)
+    )]
+    #[test_case(Some(TerritoryType::NI), "[\"PL123451234\"]", Some(TerritoryType::NI))]
+    #[test_case(
+        Some(TerritoryType::NI),
+        "[\"PLPI123451234\"]",
+        Some(TerritoryType::NI)
+    )]
+    #[test_case(
+        Some(TerritoryType::GB),
+        "[\"PLNI123451234\"]",
+        Some(TerritoryType::GB)
+    )]
     fn test_derive_territory(
         territory: Option<TerritoryType>,
         pl_number: &str,
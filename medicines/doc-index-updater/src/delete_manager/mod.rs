@@ -12,7 +12,7 @@ use anyhow::anyhow;
 use async_trait::async_trait;
 use search_client::{
     models::{IndexEntry, IndexResult},
-    AzureSearchClient, CreateIndexEntry, DeleteIndexEntry,
+    CreateIndexEntry, DeleteIndexEntry, SearchClient, SearchError,
 };
 use std::time::Duration;
 use storage_client::{AzureBlobStorage, DeleteBlob};
@@ -99,6 +99,13 @@ where
         ProcessMessageError::FailedDeletingBlob(_, _) => {
             tracing::error!("{}", error_message);
         }
+        ProcessMessageError::Search(e) if !e.is_retryable() => {
+            tracing::error!("{}", error_message);
+            state_manager
+                .set_status(removable_message.get_message().job_id, (&e).into())
+                .await?;
+            let _remove = removable_message.remove().await?;
+        }
         _ => {}
     }
 
@@ -108,7 +115,7 @@ where
 pub async fn process_message(message: DeleteMessage) -> Result<Uuid, ProcessMessageError> {
     tracing::info!("Message received: {:?} ", message);
 
-    let search_client = AzureSearchClient::new();
+    let search_client = SearchClient::new();
     let storage_client = AzureBlobStorage::permanent();
 
     process_delete_message(message, storage_client, search_client, AuditLogger {}).await
@@ -170,44 +177,22 @@ pub async fn get_index_record_from_unique_identifier(
     unique_document_identifier: &UniqueDocumentIdentifier,
     search_client: &impl SearchIndex,
 ) -> Result<IndexResult, ProcessMessageError> {
-    match unique_document_identifier {
-        UniqueDocumentIdentifier::ContentId(content_id) => {
-            get_index_record_from_content_id(content_id, search_client).await
-        }
-        UniqueDocumentIdentifier::MetadataStorageName(metadata_storage_name) => {
-            get_index_record_from_metadata_storage_name(metadata_storage_name, search_client).await
-        }
-    }
-}
-
-pub async fn get_index_record_from_content_id(
-    content_id: &str,
-    search_client: &impl SearchIndex,
-) -> Result<IndexResult, ProcessMessageError> {
-    let search_results = search_client.search_index(content_id).await?;
-    for result in search_results.search_results {
-        if result.file_name == content_id {
-            return Ok(result);
-        }
-    }
-    Err(ProcessMessageError::DocumentNotFoundInIndex(
-        content_id.to_string(),
-    ))
-}
+    let (id, index_record) = match unique_document_identifier {
+        UniqueDocumentIdentifier::ContentId(content_id) => (
+            content_id,
+            search_client
+                .get_index_record_by_content_id(content_id)
+                .await?,
+        ),
+        UniqueDocumentIdentifier::MetadataStorageName(metadata_storage_name) => (
+            metadata_storage_name,
+            search_client
+                .get_index_record(metadata_storage_name)
+                .await?,
+        ),
+    };
 
-pub async fn get_index_record_from_metadata_storage_name(
-    metadata_storage_name: &str,
-    search_client: &impl SearchIndex,
-) -> Result<IndexResult, ProcessMessageError> {
-    let search_results = search_client.search_index(metadata_storage_name).await?;
-    for result in search_results.search_results {
-        if result.metadata_storage_name == metadata_storage_name {
-            return Ok(result);
-        }
-    }
-    Err(ProcessMessageError::DocumentNotFoundInIndex(
-        metadata_storage_name.to_string(),
-    ))
+    index_record.ok_or_else(|| ProcessMessageError::DocumentNotFoundInIndex(id.to_string()))
 }
 
 #[cfg(test)]
@@ -221,12 +206,13 @@ mod test {
         state_manager::test::TestJobStatusClient,
     };
     use search_client::models::{
-        AzureIndexChangedResult, AzureIndexChangedResults, DocumentType, IndexResult, IndexResults,
-        TerritoryType,
+        AzureIndexChangedResult, AzureIndexChangedResults, DocumentType, IndexEntryAction,
+        IndexResult, TerritoryType,
     };
 
     use std::env;
     use storage_client::test::TestAzureStorageClient;
+    use test_case::test_case;
     use tokio_test::block_on;
 
     #[test]
@@ -393,6 +379,57 @@ mod test {
         assert_eq!(result.status, expected);
     }
 
+    #[test]
+    fn permanent_search_error_during_delete_removes_message_and_sets_job_status_as_error() {
+        let state_manager = given_a_state_manager();
+        let mut removable_message = given_we_have_a_delete_message();
+        let error = given_a_search_error(404);
+
+        block_on(handle_processing_error_for_delete_message(
+            &mut removable_message,
+            error,
+            &state_manager,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            removable_message.remove_was_called, true,
+            "Didn't remove message, but should"
+        );
+        let result =
+            block_on(state_manager.get_status(removable_message.get_message().job_id)).unwrap();
+        assert_eq!(
+            result.status,
+            JobStatus::Error {
+                message: String::from("Azure Search responded with 404 Not Found: No index"),
+                code: String::from("404"),
+            },
+        );
+    }
+
+    #[test]
+    fn throttled_search_error_during_delete_leaves_message_to_be_retried() {
+        let state_manager = given_a_state_manager();
+        let mut removable_message = given_we_have_a_delete_message();
+        given_the_delete_job_is_accepted(removable_message.get_message().job_id, &state_manager);
+        let error = given_a_search_error(503);
+
+        block_on(handle_processing_error_for_delete_message(
+            &mut removable_message,
+            error,
+            &state_manager,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            removable_message.remove_was_called, false,
+            "Removed message, but shouldn't"
+        );
+        let result =
+            block_on(state_manager.get_status(removable_message.get_message().job_id)).unwrap();
+        assert_eq!(result.status, JobStatus::Accepted);
+    }
+
     #[test]
     fn index_with_doc_returns_success() {
         let removable_message = given_we_have_a_delete_message().message;
@@ -512,6 +549,15 @@ mod test {
         anyhow!("Any other error").into()
     }
 
+    fn given_a_search_error(status: u16) -> ProcessMessageError {
+        SearchError::Http {
+            status: reqwest::StatusCode::from_u16(status).unwrap(),
+            body: String::from("No index"),
+            retry_after: None,
+        }
+        .into()
+    }
+
     fn given_a_delete_blob_error() -> ProcessMessageError {
         ProcessMessageError::FailedDeletingBlob("Blob Id".to_string(), "Error message".to_string())
     }
@@ -594,6 +640,22 @@ mod test {
         then_document_not_found_in_index_error_is_raised(result);
     }
 
+    #[test_case(UniqueDocumentIdentifier::ContentId("our_id".to_string()), true; "by content id")]
+    #[test_case(UniqueDocumentIdentifier::MetadataStorageName("storage_name".to_string()), true; "by metadata storage name")]
+    #[test_case(UniqueDocumentIdentifier::ContentId("storage_name".to_string()), false; "by content id with a metadata storage name")]
+    #[test_case(UniqueDocumentIdentifier::MetadataStorageName("our_id".to_string()), false; "by metadata storage name with a content id")]
+    fn get_index_record_matches_the_kind_of_identifier(
+        identifier: UniqueDocumentIdentifier,
+        found: bool,
+    ) {
+        let search_client = given_a_search_client_that_returns_results();
+        let result = block_on(get_index_record_from_unique_identifier(
+            &identifier,
+            &search_client,
+        ));
+        assert_eq!(result.is_ok(), found);
+    }
+
     fn given_a_search_client_that_returns_no_results() -> impl SearchIndex {
         TestAzureSearchClient {
             can_insert_index: true,
@@ -650,8 +712,8 @@ mod test {
     fn when_getting_index_record_from_content_id(
         search_client: impl SearchIndex,
     ) -> Result<IndexResult, ProcessMessageError> {
-        block_on(get_index_record_from_content_id(
-            "non existent content id",
+        block_on(get_index_record_from_unique_identifier(
+            &UniqueDocumentIdentifier::ContentId("non existent content id".to_string()),
             &search_client,
         ))
     }
@@ -678,12 +740,26 @@ mod test {
 
     #[async_trait]
     impl SearchIndex for TestAzureSearchClient {
-        async fn search_index(&self, _search_term: &str) -> Result<IndexResults, reqwest::Error> {
-            Ok(IndexResults {
-                search_results: self.search_results.clone(),
-                context: String::from(""),
-                count: None,
-            })
+        async fn get_index_record(
+            &self,
+            metadata_storage_name: &str,
+        ) -> Result<Option<IndexResult>, SearchError> {
+            Ok(self
+                .search_results
+                .iter()
+                .find(|result| result.metadata_storage_name == metadata_storage_name)
+                .cloned())
+        }
+
+        async fn get_index_record_by_content_id(
+            &self,
+            content_id: &str,
+        ) -> Result<Option<IndexResult>, SearchError> {
+            Ok(self
+                .search_results
+                .iter()
+                .find(|result| result.file_name == content_id)
+                .cloned())
         }
     }
 
@@ -693,9 +769,9 @@ mod test {
             &self,
             key_name: &str,
             _value: &str,
-        ) -> Result<AzureIndexChangedResults, anyhow::Error> {
+        ) -> Result<AzureIndexChangedResults, SearchError> {
             if !&self.can_delete_index {
-                return Err(anyhow!("Index could not be deleted"));
+                return Err(anyhow!("Index could not be deleted").into());
             }
 
             let index_changed_result = AzureIndexChangedResult {
@@ -707,6 +783,18 @@ mod test {
 
             Ok(AzureIndexChangedResults::new(index_changed_result))
         }
+
+        async fn delete_index_entries(
+            &self,
+            key_name: &str,
+            values: &[String],
+        ) -> Result<AzureIndexChangedResults, SearchError> {
+            let mut results = vec![];
+            for value in values {
+                results.extend(self.delete_index_entry(key_name, value).await?.value);
+            }
+            Ok(AzureIndexChangedResults::from_results(results))
+        }
     }
 
     #[async_trait]
@@ -714,9 +802,9 @@ mod test {
         async fn create_index_entry(
             &self,
             _key_values: IndexEntry,
-        ) -> Result<AzureIndexChangedResults, anyhow::Error> {
+        ) -> Result<AzureIndexChangedResults, SearchError> {
             if !&self.can_insert_index {
-                return Err(anyhow!("Index could not be created"));
+                return Err(anyhow!("Index could not be created").into());
             }
 
             let index_changed_result = AzureIndexChangedResult {
@@ -728,5 +816,16 @@ mod test {
 
             Ok(AzureIndexChangedResults::new(index_changed_result))
         }
+
+        async fn create_index_entries(
+            &self,
+            actions: Vec<IndexEntryAction>,
+        ) -> Result<AzureIndexChangedResults, SearchError> {
+            let mut results = vec![];
+            for action in actions {
+                results.extend(self.create_index_entry(action.entry).await?.value);
+            }
+            Ok(AzureIndexChangedResults::from_results(results))
+        }
     }
 }
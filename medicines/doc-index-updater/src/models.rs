@@ -2,8 +2,8 @@ use crate::service_bus_client::ProcessMessageError;
 use async_trait::async_trait;
 use regex::Regex;
 use search_client::{
-    models::{DocumentType, IndexResults, TerritoryType},
-    AzureSearchClient, Search,
+    models::{DocumentType, IndexResult, IndexResults, TerritoryType},
+    Search, SearchClient, SearchError,
 };
 use serde::{Deserialize, Deserializer, Serialize};
 use std::fmt::Debug;
@@ -56,6 +56,19 @@ impl FromStr for JobStatus {
     }
 }
 
+// Job error codes are HTTP status codes, so search failures report the status
+// Azure Search responded with, or 500 if there was no response.
+impl From<&SearchError> for JobStatus {
+    fn from(e: &SearchError) -> Self {
+        JobStatus::Error {
+            message: e.to_string(),
+            code: e
+                .status()
+                .map_or_else(|| "500".to_string(), |status| status.as_u16().to_string()),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JobStatusResponse {
     pub id: Uuid,
@@ -258,13 +271,36 @@ impl Message for DeleteMessage {
 
 #[async_trait]
 pub trait SearchIndex {
-    async fn search_index(&self, search_term: &str) -> Result<IndexResults, reqwest::Error>;
+    async fn get_index_record(
+        &self,
+        metadata_storage_name: &str,
+    ) -> Result<Option<IndexResult>, SearchError>;
+
+    async fn get_index_record_by_content_id(
+        &self,
+        content_id: &str,
+    ) -> Result<Option<IndexResult>, SearchError>;
 }
 
 #[async_trait]
-impl SearchIndex for AzureSearchClient {
-    async fn search_index(&self, search_term: &str) -> Result<IndexResults, reqwest::Error> {
-        self.search::<IndexResults>(search_term).await
+impl SearchIndex for SearchClient {
+    async fn get_index_record(
+        &self,
+        metadata_storage_name: &str,
+    ) -> Result<Option<IndexResult>, SearchError> {
+        self.lookup_document::<IndexResult>(metadata_storage_name)
+            .await
+    }
+
+    async fn get_index_record_by_content_id(
+        &self,
+        content_id: &str,
+    ) -> Result<Option<IndexResult>, SearchError> {
+        let results = self
+            .filter_by_non_collection_field::<IndexResults>("file_name", content_id)
+            .await?;
+
+        Ok(results.search_results.into_iter().next())
     }
 }
 
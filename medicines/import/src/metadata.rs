@@ -1,4 +1,5 @@
 use regex::Regex;
+use search_client::models::ProductLicence;
 use std::str;
 
 pub fn sanitize(s: &str) -> String {
@@ -55,26 +56,11 @@ pub fn create_facets_by_active_substance(active_substances: Vec<String>) -> Vec<
     facets
 }
 
+// BMGF reports often leave out the PL prefix, so numbers without one are taken to be product licences.
 pub fn extract_product_licences(input: &str) -> String {
-    lazy_static! {
-        static ref RE_WHITESPACE: Regex = Regex::new(r"(\s+|/|_|-)").expect("cannot compile regex");
-        static ref RE_PL: Regex = Regex::new(r"(?i:\b|PL)(\s+|/|_|-)*\d{5}(\s+|/|_|-)*\d{4}")
-            .expect("cannot compile regex");
-    }
-    let product_licences: Vec<String> = RE_PL
-        .find_iter(input)
-        .map(|m| {
-            RE_WHITESPACE
-                .replace_all(m.as_str(), "")
-                .to_ascii_uppercase()
-        })
-        .map(|s| {
-            if s.starts_with("PL") {
-                s
-            } else {
-                String::from("PL") + s.as_str()
-            }
-        })
+    let product_licences: Vec<String> = ProductLicence::find_all_assuming_pl(input)
+        .iter()
+        .map(ProductLicence::canonical)
         .collect();
 
     to_json(product_licences)
@@ -0,0 +1,301 @@
+use crate::{
+    models::{AzureIndexChangedResult, AzureIndexChangedResults},
+    RetryPolicy, SearchError,
+};
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::{
+    collections::{HashSet, VecDeque},
+    future::Future,
+    time::Duration,
+};
+
+// Azure Search accepts at most 1000 documents in one index request.
+pub(crate) const MAX_BATCH_SIZE: usize = 1000;
+
+// Azure reports these statuses for documents it couldn't index at the time, but may be able to later:
+// 409 for a version conflict, 422 when the index is temporarily unavailable and 503 when the service is too busy.
+fn is_retryable(result: &AzureIndexChangedResult) -> bool {
+    !result.status && matches!(result.status_code, 409 | 422 | 503)
+}
+
+// Sends the documents in batches of up to `MAX_BATCH_SIZE`, reporting a result for every key.
+// The documents that failed with a retryable status are split in half and sent again, until they succeed or
+// the retry policy runs out. A request rejected as too large is split without counting as a retry.
+// `send` mustn't retry by itself: a batch that failed as a whole is retried here, against the same policy,
+// so that request and document retries don't multiply.
+pub(crate) async fn index_in_batches<F, Fut>(
+    documents: Vec<Value>,
+    key_field: &str,
+    retry_policy: &RetryPolicy,
+    mut send: F,
+) -> Result<AzureIndexChangedResults, SearchError>
+where
+    F: FnMut(Vec<Value>) -> Fut,
+    Fut: Future<Output = Result<AzureIndexChangedResults, SearchError>>,
+{
+    let mut results = Vec::with_capacity(documents.len());
+    // Every batch waits for its delay before it's sent
+    let mut pending: VecDeque<(Vec<Value>, u32, Duration)> = chunk(documents, MAX_BATCH_SIZE)
+        .into_iter()
+        .map(|batch| (batch, 0, Duration::from_millis(0)))
+        .collect();
+
+    while let Some((batch, retries, delay)) = pending.pop_front() {
+        if delay > Duration::from_millis(0) {
+            tokio::time::delay_for(delay).await;
+        }
+
+        let changed = match send(batch.clone()).await {
+            Ok(changed) => changed,
+            Err(e) if e.status() == Some(StatusCode::PAYLOAD_TOO_LARGE) && batch.len() > 1 => {
+                pending.extend(split(batch).into_iter().map(|half| (half, retries, delay)));
+                continue;
+            }
+            Err(e) if e.is_retryable() && retries < retry_policy.max_retries => {
+                let delay = match retry_policy.delay(retries, e.retry_after()) {
+                    Some(delay) => delay,
+                    None => return Err(e),
+                };
+                tracing::warn!(
+                    "Retrying a batch of {} documents in {:?} (retry {} of {}): {}",
+                    batch.len(),
+                    delay,
+                    retries + 1,
+                    retry_policy.max_retries,
+                    e
+                );
+                pending.push_front((batch, retries + 1, delay));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        let (to_retry, done): (Vec<AzureIndexChangedResult>, Vec<AzureIndexChangedResult>) =
+            changed
+                .value
+                .into_iter()
+                .partition(|result| is_retryable(result) && retries < retry_policy.max_retries);
+        results.extend(done);
+        if to_retry.is_empty() {
+            continue;
+        }
+
+        let keys: HashSet<&str> = to_retry.iter().map(|result| result.key.as_str()).collect();
+        let failed: Vec<Value> = batch
+            .into_iter()
+            .filter(|document| matches!(key(document, key_field), Some(k) if keys.contains(k)))
+            .collect();
+        let retried: HashSet<&str> = failed
+            .iter()
+            .filter_map(|document| key(document, key_field))
+            .collect();
+
+        tracing::warn!(
+            "Azure Search couldn't index {} of the documents, retrying them",
+            failed.len()
+        );
+        // Keep the results for any key we can't match to a document, as we can't retry those
+        results.extend(
+            to_retry
+                .iter()
+                .filter(|result| !retried.contains(result.key.as_str()))
+                .cloned(),
+        );
+        let delay = retry_policy
+            .delay(retries, None)
+            .unwrap_or_else(|| Duration::from_millis(0));
+        pending.extend(
+            split(failed)
+                .into_iter()
+                .map(|half| (half, retries + 1, delay)),
+        );
+    }
+
+    Ok(AzureIndexChangedResults::from_results(results))
+}
+
+fn key<'a>(document: &'a Value, key_field: &str) -> Option<&'a str> {
+    document.get(key_field).and_then(Value::as_str)
+}
+
+fn chunk(mut documents: Vec<Value>, size: usize) -> Vec<Vec<Value>> {
+    let mut chunks = Vec::new();
+    while documents.len() > size {
+        let rest = documents.split_off(size);
+        chunks.push(documents);
+        documents = rest;
+    }
+    if !documents.is_empty() {
+        chunks.push(documents);
+    }
+    chunks
+}
+
+fn split(mut documents: Vec<Value>) -> Vec<Vec<Value>> {
+    if documents.len() <= 1 {
+        return vec![documents];
+    }
+    let second_half = documents.split_off(documents.len() / 2);
+    vec![documents, second_half]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::{cell::RefCell, collections::HashMap};
+
+    fn given_documents(count: usize) -> Vec<Value> {
+        (0..count)
+            .map(|i| json!({ "@search.action": "upload", "id": i.to_string() }))
+            .collect()
+    }
+
+    fn given_a_retry_policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            initial_backoff: Duration::from_millis(0),
+            max_backoff: Duration::from_millis(0),
+        }
+    }
+
+    fn a_result(key: &str, status_code: u16) -> AzureIndexChangedResult {
+        AzureIndexChangedResult {
+            key: key.to_string(),
+            status: status_code < 300,
+            error_message: None,
+            status_code,
+        }
+    }
+
+    // Responds to every document with the next of its statuses, or 200 once it has run out
+    fn when_we_index(
+        documents: Vec<Value>,
+        max_retries: u32,
+        statuses: HashMap<&str, Vec<u16>>,
+    ) -> (AzureIndexChangedResults, Vec<usize>) {
+        let statuses = RefCell::new(statuses);
+        let batch_sizes = RefCell::new(vec![]);
+
+        let results = tokio_test::block_on(index_in_batches(
+            documents,
+            "id",
+            &given_a_retry_policy(max_retries),
+            |batch| {
+                batch_sizes.borrow_mut().push(batch.len());
+                let results: Vec<AzureIndexChangedResult> = batch
+                    .iter()
+                    .map(|document| {
+                        let key = document["id"].as_str().unwrap();
+                        let status = match statuses.borrow_mut().get_mut(key) {
+                            Some(statuses) if !statuses.is_empty() => statuses.remove(0),
+                            _ => 200,
+                        };
+                        a_result(key, status)
+                    })
+                    .collect();
+                async move { Ok(AzureIndexChangedResults::from_results(results)) }
+            },
+        ))
+        .unwrap();
+
+        (results, batch_sizes.into_inner())
+    }
+
+    #[test]
+    fn sends_at_most_1000_documents_per_request() {
+        let (results, batch_sizes) = when_we_index(given_documents(2500), 3, HashMap::new());
+
+        assert_eq!(batch_sizes, vec![1000, 1000, 500]);
+        assert_eq!(results.succeeded().count(), 2500);
+    }
+
+    #[test]
+    fn splits_and_retries_documents_that_failed_temporarily() {
+        let mut statuses = HashMap::new();
+        statuses.insert("1", vec![503]);
+        statuses.insert("2", vec![422, 409]);
+        statuses.insert("3", vec![400]);
+
+        let (results, batch_sizes) = when_we_index(given_documents(5), 3, statuses);
+
+        assert_eq!(batch_sizes, vec![5, 1, 1, 1]);
+        assert_eq!(results.value.len(), 5);
+        let failed: Vec<&str> = results.failed().map(|result| result.key.as_str()).collect();
+        assert_eq!(failed, vec!["3"]);
+    }
+
+    #[test]
+    fn reports_documents_that_keep_failing() {
+        let mut statuses = HashMap::new();
+        statuses.insert("0", vec![503, 503, 503]);
+
+        let (results, batch_sizes) = when_we_index(given_documents(2), 2, statuses);
+
+        assert_eq!(batch_sizes, vec![2, 1, 1]);
+        let failed: Vec<&AzureIndexChangedResult> = results.failed().collect();
+        assert_eq!(failed, vec![&a_result("0", 503)]);
+    }
+
+    #[test]
+    fn retries_a_failed_request_within_the_retry_policy() {
+        let batch_sizes = RefCell::new(vec![]);
+
+        let result = tokio_test::block_on(index_in_batches(
+            given_documents(2),
+            "id",
+            &given_a_retry_policy(2),
+            |batch| {
+                batch_sizes.borrow_mut().push(batch.len());
+                let response: Result<AzureIndexChangedResults, SearchError> =
+                    Err(SearchError::Http {
+                        status: StatusCode::SERVICE_UNAVAILABLE,
+                        body: String::default(),
+                        retry_after: None,
+                    });
+                async move { response }
+            },
+        ));
+
+        assert_eq!(
+            result.unwrap_err().status(),
+            Some(StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert_eq!(batch_sizes.into_inner(), vec![2, 2, 2]);
+    }
+
+    #[test]
+    fn splits_requests_that_are_too_large() {
+        let batch_sizes = RefCell::new(vec![]);
+
+        let results = tokio_test::block_on(index_in_batches(
+            given_documents(4),
+            "id",
+            &given_a_retry_policy(0),
+            |batch| {
+                batch_sizes.borrow_mut().push(batch.len());
+                let response = if batch.len() > 2 {
+                    Err(SearchError::Http {
+                        status: StatusCode::PAYLOAD_TOO_LARGE,
+                        body: String::default(),
+                        retry_after: None,
+                    })
+                } else {
+                    Ok(AzureIndexChangedResults::from_results(
+                        batch
+                            .iter()
+                            .map(|document| a_result(document["id"].as_str().unwrap(), 201))
+                            .collect(),
+                    ))
+                };
+                async move { response }
+            },
+        ))
+        .unwrap();
+
+        assert_eq!(batch_sizes.into_inner(), vec![4, 2, 2]);
+        assert_eq!(results.succeeded().count(), 4);
+    }
+}
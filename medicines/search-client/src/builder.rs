@@ -0,0 +1,599 @@
+use crate::{
+    query_normalizer::default_searchable_fields,
+    retry::{DEFAULT_CIRCUIT_BREAKER_OPEN_DURATION, DEFAULT_CIRCUIT_BREAKER_THRESHOLD},
+    spelling::VocabularyCache,
+    AzureConfig, AzureSearchClient, CircuitBreaker, RetryPolicy, Synonyms,
+};
+use std::{
+    fmt::{Display, Formatter},
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+const DEFAULT_SEARCH_FUZZINESS: &str = "1";
+const DEFAULT_SEARCH_EXACTNESS_BOOST: &str = "4";
+const DEFAULT_SCORING_PROFILE: &str = "preferKeywords";
+// Lucene fuzzy searches allow at most 2 edits.
+const MAX_SEARCH_FUZZINESS: u8 = 2;
+
+#[derive(Debug)]
+pub enum BuildError {
+    Missing(&'static str),
+    Invalid { field: &'static str, reason: String },
+    Client(reqwest::Error),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::Missing(field) => {
+                write!(f, "Azure Search client needs a {} to be set", field)
+            }
+            BuildError::Invalid { field, reason } => {
+                write!(
+                    f,
+                    "Azure Search client has an invalid {}: {}",
+                    field, reason
+                )
+            }
+            BuildError::Client(e) => write!(f, "Could not build HTTP client: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BuildError::Client(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// Configures an `AzureSearchClient` without reading the environment, e.g.
+//
+// ```ignore
+// let client = AzureSearchClient::builder()
+//     .search_service("mhraproductsnonprod")
+//     .index("products-index")
+//     .api_version("2017-11-11")
+//     .query_key(query_key)
+//     .build()?;
+// ```
+//
+// The query key is used for searches and the admin key for index updates.
+// Only one of them is needed, and searches fall back to the admin key when there is no query key.
+#[derive(Default)]
+pub struct AzureSearchClientBuilder {
+    base_url: Option<String>,
+    search_index: Option<String>,
+    query_key: Option<String>,
+    admin_key: Option<String>,
+    api_version: Option<String>,
+    timeout: Option<Duration>,
+    client: Option<reqwest::Client>,
+    search_fuzziness: Option<String>,
+    search_exactness_boost: Option<String>,
+    scoring_profile: Option<Option<String>>,
+    searchable_fields: Option<Vec<String>>,
+    synonyms: Option<Synonyms>,
+    synonyms_file: Option<PathBuf>,
+    retry_policy: Option<RetryPolicy>,
+    circuit_breaker_threshold: Option<u32>,
+    circuit_breaker_open_duration: Option<Duration>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl AzureSearchClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Reads the same env variables as `AzureSearchClient::new`, leaving anything unset for `build` to report.
+    // Numbers which don't parse are reported straight away.
+    pub fn from_env() -> Result<Self, BuildError> {
+        let var = |key: &str| std::env::var(key).ok();
+
+        let mut retry_policy = RetryPolicy::default();
+        if let Some(max_retries) = env_number("AZURE_SEARCH_MAX_RETRIES", "max retries")? {
+            retry_policy.max_retries = max_retries;
+        }
+        if let Some(millis) =
+            env_number("AZURE_SEARCH_RETRY_INITIAL_BACKOFF_MS", "initial backoff")?
+        {
+            retry_policy.initial_backoff = Duration::from_millis(millis);
+        }
+        if let Some(millis) = env_number("AZURE_SEARCH_RETRY_MAX_BACKOFF_MS", "maximum backoff")? {
+            retry_policy.max_backoff = Duration::from_millis(millis);
+        }
+
+        let mut builder = Self {
+            search_index: var("AZURE_SEARCH_INDEX"),
+            query_key: var("AZURE_SEARCH_QUERY_KEY"),
+            admin_key: var("AZURE_API_ADMIN_KEY"),
+            api_version: var("AZURE_SEARCH_API_VERSION"),
+            search_fuzziness: var("AZURE_SEARCH_WORD_FUZZINESS"),
+            search_exactness_boost: var("AZURE_SEARCH_EXACTNESS_BOOST"),
+            scoring_profile: var("AZURE_SEARCH_SCORING_PROFILE").map(Some),
+            searchable_fields: var("AZURE_SEARCH_SEARCHABLE_FIELDS").map(|fields| {
+                fields
+                    .split(',')
+                    .map(|field| field.trim().to_string())
+                    .filter(|field| !field.is_empty())
+                    .collect()
+            }),
+            synonyms_file: var("AZURE_SEARCH_SYNONYMS_FILE").map(PathBuf::from),
+            retry_policy: Some(retry_policy),
+            circuit_breaker_threshold: env_number(
+                "AZURE_SEARCH_CIRCUIT_BREAKER_THRESHOLD",
+                "circuit breaker threshold",
+            )?,
+            circuit_breaker_open_duration: env_number(
+                "AZURE_SEARCH_CIRCUIT_BREAKER_OPEN_SECONDS",
+                "circuit breaker open duration",
+            )?
+            .map(Duration::from_secs),
+            timeout: env_number("AZURE_SEARCH_TIMEOUT_SECONDS", "timeout")?
+                .map(Duration::from_secs),
+            ..Self::default()
+        };
+        builder.base_url = match (var("AZURE_SEARCH_BASE_URL"), var("SEARCH_SERVICE")) {
+            (Some(base_url), _) => Some(base_url),
+            (None, Some(search_service)) => Some(service_url(&search_service)),
+            (None, None) => None,
+        };
+        Ok(builder)
+    }
+
+    // Sets the base URL to that of an Azure Search service, i.e. `https://{search_service}.search.windows.net`.
+    pub fn search_service(mut self, search_service: impl AsRef<str>) -> Self {
+        self.base_url = Some(service_url(search_service.as_ref()));
+        self
+    }
+
+    // Sets the base URL explicitly, e.g. to point the client at a local stand-in for Azure Search.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    pub fn index(mut self, search_index: impl Into<String>) -> Self {
+        self.search_index = Some(search_index.into());
+        self
+    }
+
+    pub fn query_key(mut self, query_key: impl Into<String>) -> Self {
+        self.query_key = Some(query_key.into());
+        self
+    }
+
+    pub fn admin_key(mut self, admin_key: impl Into<String>) -> Self {
+        self.admin_key = Some(admin_key.into());
+        self
+    }
+
+    pub fn api_version(mut self, api_version: impl Into<String>) -> Self {
+        self.api_version = Some(api_version.into());
+        self
+    }
+
+    // A timeout for every request. Can't be combined with a custom `client`, which should have its own timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    // The number of edits allowed when fuzzy matching each word of a search term.
+    pub fn search_fuzziness(mut self, search_fuzziness: u8) -> Self {
+        self.search_fuzziness = Some(search_fuzziness.to_string());
+        self
+    }
+
+    // How much more an exact match of a word counts than a fuzzy match.
+    pub fn search_exactness_boost(mut self, search_exactness_boost: u32) -> Self {
+        self.search_exactness_boost = Some(search_exactness_boost.to_string());
+        self
+    }
+
+    // The scoring profile searches use, `preferKeywords` by default. `None` uses the index's default scoring.
+    pub fn scoring_profile(mut self, scoring_profile: Option<&str>) -> Self {
+        self.scoring_profile = Some(scoring_profile.map(String::from));
+        self
+    }
+
+    // The fields `field:` terms in a search can be scoped to, which default to those of the products index,
+    // or of the BMGF index for an index named `bmgf...`. Azure rejects searches scoped to any other field.
+    pub fn searchable_fields(mut self, searchable_fields: &[&str]) -> Self {
+        self.searchable_fields = Some(searchable_fields.iter().map(|f| f.to_string()).collect());
+        self
+    }
+
+    // Terms in a search are expanded with their synonyms, e.g. from `search/definitions/synonyms/default.txt`.
+    pub fn synonyms(mut self, synonyms: Synonyms) -> Self {
+        self.synonyms = Some(synonyms);
+        self
+    }
+
+    pub fn synonyms_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.synonyms_file = Some(path.into());
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    // How many failures in a row open the circuit to the index, 5 by default.
+    pub fn circuit_breaker_threshold(mut self, failure_threshold: u32) -> Self {
+        self.circuit_breaker_threshold = Some(failure_threshold);
+        self
+    }
+
+    // How long the circuit stays open before a trial request is let through, 30 seconds by default.
+    pub fn circuit_breaker_open_duration(mut self, open_duration: Duration) -> Self {
+        self.circuit_breaker_open_duration = Some(open_duration);
+        self
+    }
+
+    // A circuit breaker of the caller's own, e.g. to share it between indexes or to keep it to one client,
+    // rather than the one shared by every client of the index with the same settings.
+    // Can't be combined with the circuit breaker settings, as the circuit breaker already has its own.
+    pub fn circuit_breaker(mut self, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    pub fn build(self) -> Result<AzureSearchClient, BuildError> {
+        let base_url = non_empty(self.base_url, "base URL")?
+            .trim_end_matches('/')
+            .to_string();
+        match reqwest::Url::parse(&base_url) {
+            Ok(url) if url.scheme() == "https" || url.scheme() == "http" => {}
+            Ok(url) => {
+                return Err(BuildError::Invalid {
+                    field: "base URL",
+                    reason: format!("{} is not an http(s) URL", url),
+                })
+            }
+            Err(e) => {
+                return Err(BuildError::Invalid {
+                    field: "base URL",
+                    reason: format!("{} ({})", e, base_url),
+                })
+            }
+        }
+
+        let search_index = non_empty(self.search_index, "index")?;
+        let api_version = non_empty(self.api_version, "API version")?;
+
+        let admin_key = self.admin_key.filter(|key| !key.is_empty());
+        let query_key = self
+            .query_key
+            .filter(|key| !key.is_empty())
+            .or_else(|| admin_key.clone())
+            .ok_or(BuildError::Missing("query key or admin key"))?;
+
+        let search_fuzziness = self
+            .search_fuzziness
+            .unwrap_or_else(|| DEFAULT_SEARCH_FUZZINESS.to_string());
+        match search_fuzziness.parse::<u8>() {
+            Ok(fuzziness) if fuzziness <= MAX_SEARCH_FUZZINESS => {}
+            _ => {
+                return Err(BuildError::Invalid {
+                    field: "search fuzziness",
+                    reason: format!(
+                        "{} is not a number from 0 to {}",
+                        search_fuzziness, MAX_SEARCH_FUZZINESS
+                    ),
+                })
+            }
+        }
+
+        let search_exactness_boost = self
+            .search_exactness_boost
+            .unwrap_or_else(|| DEFAULT_SEARCH_EXACTNESS_BOOST.to_string());
+        match search_exactness_boost.parse::<f64>() {
+            Ok(boost) if boost > 0.0 => {}
+            _ => {
+                return Err(BuildError::Invalid {
+                    field: "search exactness boost",
+                    reason: format!("{} is not a positive number", search_exactness_boost),
+                })
+            }
+        }
+
+        let scoring_profile = self
+            .scoring_profile
+            .unwrap_or_else(|| Some(DEFAULT_SCORING_PROFILE.to_string()))
+            .filter(|profile| !profile.is_empty());
+
+        let client = match (self.client, self.timeout) {
+            (Some(_), Some(_)) => {
+                return Err(BuildError::Invalid {
+                    field: "timeout",
+                    reason: "a timeout can't be set alongside a custom client".to_string(),
+                })
+            }
+            (Some(client), None) => client,
+            (None, Some(timeout)) if timeout == Duration::from_secs(0) => {
+                return Err(BuildError::Invalid {
+                    field: "timeout",
+                    reason: "the timeout must be longer than 0".to_string(),
+                })
+            }
+            (None, Some(timeout)) => reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .map_err(BuildError::Client)?,
+            (None, None) => reqwest::Client::new(),
+        };
+
+        let searchable_fields = self
+            .searchable_fields
+            .unwrap_or_else(|| default_searchable_fields(&search_index));
+
+        let synonyms = match (self.synonyms, self.synonyms_file) {
+            (Some(synonyms), _) => synonyms,
+            (None, Some(path)) => Synonyms::from_file(&path).map_err(|e| BuildError::Invalid {
+                field: "synonyms file",
+                reason: format!("{} ({})", e, path.display()),
+            })?,
+            (None, None) => Synonyms::default(),
+        };
+
+        let retry_policy = self.retry_policy.unwrap_or_default();
+        if retry_policy.initial_backoff > retry_policy.max_backoff {
+            return Err(BuildError::Invalid {
+                field: "retry policy",
+                reason: format!(
+                    "the initial backoff ({:?}) is longer than the maximum backoff ({:?})",
+                    retry_policy.initial_backoff, retry_policy.max_backoff
+                ),
+            });
+        }
+
+        let circuit_breaker = match (
+            self.circuit_breaker,
+            self.circuit_breaker_threshold,
+            self.circuit_breaker_open_duration,
+        ) {
+            (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+                return Err(BuildError::Invalid {
+                    field: "circuit breaker",
+                    reason: "its settings can't be set alongside it".to_string(),
+                })
+            }
+            (Some(circuit_breaker), None, None) => circuit_breaker,
+            (None, Some(0), _) => {
+                return Err(BuildError::Invalid {
+                    field: "circuit breaker threshold",
+                    reason: "the threshold must be at least 1".to_string(),
+                })
+            }
+            (None, threshold, open_duration) => CircuitBreaker::for_index(
+                &base_url,
+                &search_index,
+                threshold.unwrap_or(DEFAULT_CIRCUIT_BREAKER_THRESHOLD),
+                open_duration.unwrap_or(DEFAULT_CIRCUIT_BREAKER_OPEN_DURATION),
+            ),
+        };
+
+        Ok(AzureSearchClient {
+            client,
+            retry_policy,
+            circuit_breaker,
+            vocabulary_cache: VocabularyCache::for_index(&base_url, &search_index),
+            config: AzureConfig {
+                base_url,
+                search_index,
+                query_key,
+                admin_key,
+                api_version,
+                search_fuzziness,
+                search_exactness_boost,
+                scoring_profile,
+                searchable_fields,
+                synonyms,
+            },
+        })
+    }
+}
+
+fn service_url(search_service: &str) -> String {
+    format!("https://{}.search.windows.net", search_service)
+}
+
+fn env_number<T: FromStr>(key: &str, field: &'static str) -> Result<Option<T>, BuildError> {
+    std::env::var(key)
+        .ok()
+        .map(|value| parse_number(key, field, &value))
+        .transpose()
+}
+
+fn parse_number<T: FromStr>(key: &str, field: &'static str, value: &str) -> Result<T, BuildError> {
+    value.trim().parse().map_err(|_| BuildError::Invalid {
+        field,
+        reason: format!("{} must be a whole number, not {}", key, value),
+    })
+}
+
+fn non_empty(value: Option<String>, field: &'static str) -> Result<String, BuildError> {
+    value
+        .filter(|value| !value.trim().is_empty())
+        .ok_or(BuildError::Missing(field))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    fn given_a_complete_builder() -> AzureSearchClientBuilder {
+        AzureSearchClientBuilder::new()
+            .search_service("my_cool_service")
+            .index("my_cool_index")
+            .api_version("2017-11-11")
+            .admin_key("my_cool_admin_key")
+            .retry_policy(RetryPolicy::none())
+    }
+
+    #[test]
+    fn builds_a_client_for_a_search_service() {
+        let client = given_a_complete_builder().build().unwrap();
+
+        assert_eq!(
+            client.config.base_url,
+            "https://my_cool_service.search.windows.net"
+        );
+        assert_eq!(client.config.search_index, "my_cool_index");
+        assert_eq!(client.config.search_fuzziness, "1");
+        assert_eq!(client.config.search_exactness_boost, "4");
+        assert_eq!(
+            client.config.scoring_profile.as_deref(),
+            Some("preferKeywords")
+        );
+    }
+
+    #[test]
+    fn searches_use_the_admin_key_when_there_is_no_query_key() {
+        let client = given_a_complete_builder().build().unwrap();
+
+        assert_eq!(client.config.query_key, "my_cool_admin_key");
+        assert_eq!(
+            client.config.admin_key.as_deref(),
+            Some("my_cool_admin_key")
+        );
+    }
+
+    #[test]
+    fn read_only_clients_need_only_a_query_key() {
+        let client = AzureSearchClientBuilder::new()
+            .base_url("http://localhost:8080/")
+            .index("my_cool_index")
+            .api_version("2017-11-11")
+            .query_key("my_cool_query_key")
+            .scoring_profile(None)
+            .build()
+            .unwrap();
+
+        assert_eq!(client.config.base_url, "http://localhost:8080");
+        assert_eq!(client.config.query_key, "my_cool_query_key");
+        assert_eq!(client.config.admin_key, None);
+        assert_eq!(client.config.scoring_profile, None);
+    }
+
+    #[test_case("products-index", "substance_name")]
+    #[test_case("bmgf-index", "active_substances")]
+    fn defaults_searchable_fields_to_those_of_the_index(index: &str, expected: &str) {
+        let client = given_a_complete_builder().index(index).build().unwrap();
+
+        assert!(client
+            .config
+            .searchable_fields
+            .iter()
+            .any(|f| f == expected));
+    }
+
+    #[test_case(AzureSearchClientBuilder::new().index("i").api_version("v").query_key("k"), "base URL")]
+    #[test_case(AzureSearchClientBuilder::new().search_service("s").api_version("v").query_key("k"), "index")]
+    #[test_case(AzureSearchClientBuilder::new().search_service("s").index("").api_version("v").query_key("k"), "index")]
+    #[test_case(AzureSearchClientBuilder::new().search_service("s").index("i").query_key("k"), "API version")]
+    #[test_case(AzureSearchClientBuilder::new().search_service("s").index("i").api_version("v"), "query key or admin key")]
+    fn reports_missing_settings(builder: AzureSearchClientBuilder, expected: &str) {
+        match builder.build() {
+            Err(BuildError::Missing(field)) => assert_eq!(field, expected),
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("Built a client with a missing {}", expected),
+        }
+    }
+
+    #[test_case(given_a_complete_builder().base_url("not a url"), "base URL")]
+    #[test_case(given_a_complete_builder().base_url("ftp://example.com"), "base URL")]
+    #[test_case(given_a_complete_builder().search_fuzziness(3), "search fuzziness")]
+    #[test_case(given_a_complete_builder().search_exactness_boost(0), "search exactness boost")]
+    #[test_case(given_a_complete_builder().synonyms_file("does/not/exist.txt"), "synonyms file")]
+    #[test_case(given_a_complete_builder().timeout(Duration::from_secs(0)), "timeout")]
+    #[test_case(given_a_complete_builder().client(reqwest::Client::new()).timeout(Duration::from_secs(5)), "timeout")]
+    #[test_case(given_a_complete_builder().circuit_breaker_threshold(0), "circuit breaker threshold")]
+    #[test_case(given_a_complete_builder().circuit_breaker(Arc::new(CircuitBreaker::new(1, Duration::from_secs(1)))).circuit_breaker_threshold(2), "circuit breaker")]
+    #[test_case(given_a_complete_builder().retry_policy(RetryPolicy { max_retries: 1, initial_backoff: Duration::from_secs(2), max_backoff: Duration::from_secs(1) }), "retry policy")]
+    fn reports_invalid_settings(builder: AzureSearchClientBuilder, expected: &str) {
+        match builder.build() {
+            Err(BuildError::Invalid { field, .. }) => assert_eq!(field, expected),
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("Built a client with an invalid {}", expected),
+        }
+    }
+
+    #[test]
+    fn shares_circuit_breakers_between_clients_with_the_same_settings() {
+        let builder = || given_a_complete_builder().index("shared_circuit_breaker_index");
+        let first = builder().build().unwrap();
+        let second = builder().build().unwrap();
+        let other_threshold = builder().circuit_breaker_threshold(2).build().unwrap();
+        let other_open_duration = builder()
+            .circuit_breaker_open_duration(Duration::from_secs(1))
+            .build()
+            .unwrap();
+
+        assert!(Arc::ptr_eq(&first.circuit_breaker, &second.circuit_breaker));
+        assert!(!Arc::ptr_eq(
+            &first.circuit_breaker,
+            &other_threshold.circuit_breaker
+        ));
+        assert!(!Arc::ptr_eq(
+            &first.circuit_breaker,
+            &other_open_duration.circuit_breaker
+        ));
+    }
+
+    #[test]
+    fn uses_a_circuit_breaker_of_its_own() {
+        let circuit_breaker = Arc::new(CircuitBreaker::new(1, Duration::from_secs(1)));
+        let client = given_a_complete_builder()
+            .circuit_breaker(circuit_breaker.clone())
+            .build()
+            .unwrap();
+
+        assert!(Arc::ptr_eq(&client.circuit_breaker, &circuit_breaker));
+    }
+
+    #[test_case("5", Some(5))]
+    #[test_case(" 30 ", Some(30))]
+    #[test_case("soon", None)]
+    #[test_case("-1", None)]
+    fn parses_numbers_from_env_variables(value: &str, expected: Option<u64>) {
+        match parse_number::<u64>("AZURE_SEARCH_TIMEOUT_SECONDS", "timeout", value) {
+            Ok(number) => assert_eq!(Some(number), expected),
+            Err(BuildError::Invalid { field, reason }) => {
+                assert_eq!(expected, None);
+                assert_eq!(field, "timeout");
+                assert!(
+                    reason.starts_with("AZURE_SEARCH_TIMEOUT_SECONDS"),
+                    "{}",
+                    reason
+                );
+            }
+            Err(e) => panic!("Unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn describes_validation_errors() {
+        let error = AzureSearchClientBuilder::new().build().err().unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "Azure Search client needs a base URL to be set"
+        );
+    }
+}
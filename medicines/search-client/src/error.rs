@@ -0,0 +1,232 @@
+use reqwest::StatusCode;
+use serde_derive::Deserialize;
+use serde_json::json;
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
+
+// The body Azure Search sends with an unsuccessful response, e.g.
+// `{ "error": { "code": "", "message": "Invalid expression: ..." } }`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct AzureError {
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+struct AzureErrorResponse {
+    error: AzureError,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchErrorKind {
+    BadRequest,
+    Unauthorized,
+    NotFound,
+    Throttled,
+    Unavailable,
+    InvalidResponse,
+    Other,
+}
+
+#[derive(Debug)]
+pub enum SearchError {
+    Http {
+        status: StatusCode,
+        body: String,
+        retry_after: Option<Duration>,
+    },
+    Request(reqwest::Error),
+    CircuitOpen,
+    Deserialization(serde_json::Error),
+    Other(anyhow::Error),
+}
+
+impl SearchError {
+    pub(crate) fn bad_request(message: impl Into<String>) -> Self {
+        SearchError::Http {
+            status: StatusCode::BAD_REQUEST,
+            body: json!({ "error": { "code": "", "message": message.into() } }).to_string(),
+            retry_after: None,
+        }
+    }
+
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            SearchError::Http { status, .. } => Some(*status),
+            SearchError::Request(e) => e.status(),
+            _ => None,
+        }
+    }
+
+    pub fn azure_error(&self) -> Option<AzureError> {
+        match self {
+            SearchError::Http { body, .. } => serde_json::from_str::<AzureErrorResponse>(body)
+                .map(|response| response.error)
+                .ok(),
+            _ => None,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            SearchError::Http { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> SearchErrorKind {
+        match self {
+            SearchError::Http { status, .. } => match *status {
+                StatusCode::BAD_REQUEST => SearchErrorKind::BadRequest,
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => SearchErrorKind::Unauthorized,
+                StatusCode::NOT_FOUND => SearchErrorKind::NotFound,
+                // Azure Search answers 503 when the service is too busy to take the request
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+                    SearchErrorKind::Throttled
+                }
+                status if status.is_server_error() => SearchErrorKind::Unavailable,
+                _ => SearchErrorKind::Other,
+            },
+            SearchError::Request(e) if e.is_builder() => SearchErrorKind::Other,
+            SearchError::Request(_) | SearchError::CircuitOpen => SearchErrorKind::Unavailable,
+            SearchError::Deserialization(_) => SearchErrorKind::InvalidResponse,
+            SearchError::Other(_) => SearchErrorKind::Other,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        match self.kind() {
+            SearchErrorKind::Throttled | SearchErrorKind::Unavailable => true,
+            _ => self.status() == Some(StatusCode::REQUEST_TIMEOUT),
+        }
+    }
+}
+
+impl Display for SearchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchError::Http { status, body, .. } => match self.azure_error() {
+                Some(error) => write!(
+                    f,
+                    "Azure Search responded with {}: {}",
+                    status, error.message
+                ),
+                None => write!(f, "Azure Search responded with {}: {}", status, body),
+            },
+            SearchError::Request(e) => write!(f, "Could not send request to Azure Search: {}", e),
+            SearchError::CircuitOpen => write!(
+                f,
+                "Not calling Azure Search as it has been failing, try again later"
+            ),
+            SearchError::Deserialization(e) => {
+                write!(f, "Could not deserialize Azure Search response: {}", e)
+            }
+            SearchError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SearchError::Request(e) => Some(e),
+            SearchError::Deserialization(e) => Some(e),
+            SearchError::Other(e) => Some(e.as_ref()),
+            SearchError::Http { .. } | SearchError::CircuitOpen => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for SearchError {
+    fn from(e: reqwest::Error) -> Self {
+        SearchError::Request(e)
+    }
+}
+
+impl From<serde_json::Error> for SearchError {
+    fn from(e: serde_json::Error) -> Self {
+        SearchError::Deserialization(e)
+    }
+}
+
+impl From<anyhow::Error> for SearchError {
+    fn from(e: anyhow::Error) -> Self {
+        SearchError::Other(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    fn given_an_http_error(status: u16, body: &str) -> SearchError {
+        SearchError::Http {
+            status: StatusCode::from_u16(status).unwrap(),
+            body: body.to_string(),
+            retry_after: None,
+        }
+    }
+
+    #[test_case(400, SearchErrorKind::BadRequest, false)]
+    #[test_case(401, SearchErrorKind::Unauthorized, false)]
+    #[test_case(403, SearchErrorKind::Unauthorized, false)]
+    #[test_case(404, SearchErrorKind::NotFound, false)]
+    #[test_case(408, SearchErrorKind::Other, true)]
+    #[test_case(409, SearchErrorKind::Other, false)]
+    #[test_case(429, SearchErrorKind::Throttled, true)]
+    #[test_case(500, SearchErrorKind::Unavailable, true)]
+    #[test_case(502, SearchErrorKind::Unavailable, true)]
+    #[test_case(503, SearchErrorKind::Throttled, true)]
+    #[test_case(504, SearchErrorKind::Unavailable, true)]
+    fn classifies_http_errors(
+        status: u16,
+        expected_kind: SearchErrorKind,
+        expected_retryable: bool,
+    ) {
+        let error = given_an_http_error(status, "");
+        assert_eq!(error.kind(), expected_kind);
+        assert_eq!(error.is_retryable(), expected_retryable);
+    }
+
+    #[test]
+    fn reads_the_azure_error_body() {
+        let error = given_an_http_error(
+            400,
+            r#"{"error":{"code":"","message":"Invalid expression: Syntax error at position 9."}}"#,
+        );
+
+        assert_eq!(
+            error.azure_error(),
+            Some(AzureError {
+                code: "".to_string(),
+                message: "Invalid expression: Syntax error at position 9.".to_string(),
+            })
+        );
+        assert_eq!(
+            error.to_string(),
+            "Azure Search responded with 400 Bad Request: Invalid expression: Syntax error at position 9."
+        );
+    }
+
+    #[test]
+    fn keeps_bodies_that_are_not_azure_errors() {
+        let error = given_an_http_error(502, "Bad Gateway");
+
+        assert_eq!(error.azure_error(), None);
+        assert_eq!(
+            error.to_string(),
+            "Azure Search responded with 502 Bad Gateway: Bad Gateway"
+        );
+    }
+
+    #[test]
+    fn deserialization_errors_are_not_retryable() {
+        let error: SearchError = serde_json::from_str::<AzureError>("{}").unwrap_err().into();
+
+        assert_eq!(error.kind(), SearchErrorKind::InvalidResponse);
+        assert!(!error.is_retryable());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::sort::SortDirection;
+use std::fmt::{Display, Formatter};
+
+// Azure Search returns the 10 most common values of a facet when no count is given.
+pub const DEFAULT_FACET_COUNT: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FacetSort {
+    // Descending puts the most common values first, which is what Azure does by default.
+    Count(SortDirection),
+    Value(SortDirection),
+}
+
+impl Display for FacetSort {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FacetSort::Count(SortDirection::Desc) => write!(f, "count"),
+            FacetSort::Count(SortDirection::Asc) => write!(f, "-count"),
+            FacetSort::Value(SortDirection::Asc) => write!(f, "value"),
+            FacetSort::Value(SortDirection::Desc) => write!(f, "-value"),
+        }
+    }
+}
+
+// Buckets numbers by a width, or dates by a unit of time, with each bucket's value being where it starts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FacetInterval {
+    Number(i64),
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl Display for FacetInterval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FacetInterval::Number(width) => write!(f, "{}", width),
+            FacetInterval::Day => write!(f, "day"),
+            FacetInterval::Week => write!(f, "week"),
+            FacetInterval::Month => write!(f, "month"),
+            FacetInterval::Quarter => write!(f, "quarter"),
+            FacetInterval::Year => write!(f, "year"),
+        }
+    }
+}
+
+// A `facet` parameter for Azure Search, rendered with `to_string()`, e.g. `doc_type,count:5,sort:value`.
+// The field has to be `facetable` in the index definition.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FacetRequest {
+    pub field: String,
+    pub count: Option<usize>,
+    pub sort: Option<FacetSort>,
+    pub interval: Option<FacetInterval>,
+}
+
+impl FacetRequest {
+    pub fn new(field: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            count: None,
+            sort: None,
+            interval: None,
+        }
+    }
+
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    pub fn sort(mut self, sort: FacetSort) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    pub fn interval(mut self, interval: FacetInterval) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+}
+
+impl Display for FacetRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.field)?;
+        if let Some(count) = self.count {
+            write!(f, ",count:{}", count)?;
+        }
+        if let Some(sort) = self.sort {
+            write!(f, ",sort:{}", sort)?;
+        }
+        if let Some(interval) = self.interval {
+            write!(f, ",interval:{}", interval)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(FacetRequest::new("doc_type"), "doc_type")]
+    #[test_case(
+        FacetRequest::new("facets").count(50000).sort(FacetSort::Value(SortDirection::Asc)),
+        "facets,count:50000,sort:value"
+    )]
+    #[test_case(
+        FacetRequest::new("territory").sort(FacetSort::Count(SortDirection::Asc)),
+        "territory,sort:-count"
+    )]
+    #[test_case(
+        FacetRequest::new("metadata_storage_size").interval(FacetInterval::Number(1000)),
+        "metadata_storage_size,interval:1000"
+    )]
+    #[test_case(
+        FacetRequest::new("created").interval(FacetInterval::Month),
+        "created,interval:month"
+    )]
+    fn renders_facet_requests(facet: FacetRequest, expected: &str) {
+        assert_eq!(facet.to_string(), expected);
+    }
+}
@@ -0,0 +1,337 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operator::Eq => write!(f, "eq"),
+            Operator::Ne => write!(f, "ne"),
+            Operator::Gt => write!(f, "gt"),
+            Operator::Ge => write!(f, "ge"),
+            Operator::Lt => write!(f, "lt"),
+            Operator::Le => write!(f, "le"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Null,
+    Bool(bool),
+    Int(i64),
+    String(String),
+    // Only comparable with `Edm.DateTimeOffset` fields.
+    DateTime(DateTime<Utc>),
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Null => write!(f, "null"),
+            Literal::Bool(value) => write!(f, "{}", value),
+            Literal::Int(value) => write!(f, "{}", value),
+            // OData escapes a single quote inside a string literal by doubling it
+            Literal::String(value) => write!(f, "'{}'", value.replace('\'', "''")),
+            Literal::DateTime(value) => {
+                write!(f, "{}", value.to_rfc3339_opts(SecondsFormat::Secs, true))
+            }
+        }
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(value: &str) -> Self {
+        Literal::String(value.to_string())
+    }
+}
+
+impl From<String> for Literal {
+    fn from(value: String) -> Self {
+        Literal::String(value)
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(value: bool) -> Self {
+        Literal::Bool(value)
+    }
+}
+
+impl From<i32> for Literal {
+    fn from(value: i32) -> Self {
+        Literal::Int(value.into())
+    }
+}
+
+impl From<i64> for Literal {
+    fn from(value: i64) -> Self {
+        Literal::Int(value)
+    }
+}
+
+impl From<DateTime<Utc>> for Literal {
+    fn from(value: DateTime<Utc>) -> Self {
+        Literal::DateTime(value)
+    }
+}
+
+impl<T> From<Option<T>> for Literal
+where
+    T: Into<Literal>,
+{
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Literal::Null, Into::into)
+    }
+}
+
+// A `$filter` expression for Azure Search, rendered to OData with `to_string()`.
+// Collection fields are matched with `Any`/`All`, whose lambda compares each element of the collection.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    Compare {
+        field: String,
+        operator: Operator,
+        value: Literal,
+    },
+    Any {
+        field: String,
+        operator: Operator,
+        value: Literal,
+    },
+    All {
+        field: String,
+        operator: Operator,
+        value: Literal,
+    },
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn eq(field: &str, value: impl Into<Literal>) -> Self {
+        Filter::Compare {
+            field: field.to_string(),
+            operator: Operator::Eq,
+            value: value.into(),
+        }
+    }
+
+    pub fn ne(field: &str, value: impl Into<Literal>) -> Self {
+        Filter::Compare {
+            field: field.to_string(),
+            operator: Operator::Ne,
+            value: value.into(),
+        }
+    }
+
+    pub fn gt(field: &str, value: impl Into<Literal>) -> Self {
+        Filter::Compare {
+            field: field.to_string(),
+            operator: Operator::Gt,
+            value: value.into(),
+        }
+    }
+
+    pub fn ge(field: &str, value: impl Into<Literal>) -> Self {
+        Filter::Compare {
+            field: field.to_string(),
+            operator: Operator::Ge,
+            value: value.into(),
+        }
+    }
+
+    pub fn lt(field: &str, value: impl Into<Literal>) -> Self {
+        Filter::Compare {
+            field: field.to_string(),
+            operator: Operator::Lt,
+            value: value.into(),
+        }
+    }
+
+    pub fn le(field: &str, value: impl Into<Literal>) -> Self {
+        Filter::Compare {
+            field: field.to_string(),
+            operator: Operator::Le,
+            value: value.into(),
+        }
+    }
+
+    pub fn any(field: &str, operator: Operator, value: impl Into<Literal>) -> Self {
+        Filter::Any {
+            field: field.to_string(),
+            operator,
+            value: value.into(),
+        }
+    }
+
+    pub fn all(field: &str, operator: Operator, value: impl Into<Literal>) -> Self {
+        Filter::All {
+            field: field.to_string(),
+            operator,
+            value: value.into(),
+        }
+    }
+
+    pub fn and(filters: impl IntoIterator<Item = Filter>) -> Self {
+        Filter::And(filters.into_iter().collect())
+    }
+
+    pub fn or(filters: impl IntoIterator<Item = Filter>) -> Self {
+        Filter::Or(filters.into_iter().collect())
+    }
+
+    fn is_parenthesized(&self) -> bool {
+        match self {
+            Filter::And(filters) | Filter::Or(filters) => filters.len() > 1,
+            _ => false,
+        }
+    }
+}
+
+impl std::ops::Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Self::Output {
+        Filter::Not(Box::new(self))
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Filter::Compare {
+                field,
+                operator,
+                value,
+            } => write!(f, "{} {} {}", field, operator, value),
+            Filter::Any {
+                field,
+                operator,
+                value,
+            } => write!(f, "{}/any(f: f {} {})", field, operator, value),
+            Filter::All {
+                field,
+                operator,
+                value,
+            } => write!(f, "{}/all(f: f {} {})", field, operator, value),
+            Filter::And(filters) => write_group(f, filters, "and", "true"),
+            Filter::Or(filters) => write_group(f, filters, "or", "false"),
+            Filter::Not(filter) if filter.is_parenthesized() => write!(f, "not {}", filter),
+            Filter::Not(filter) => write!(f, "not ({})", filter),
+        }
+    }
+}
+
+fn write_group(
+    f: &mut Formatter<'_>,
+    filters: &[Filter],
+    separator: &str,
+    identity: &str,
+) -> std::fmt::Result {
+    match filters {
+        [] => write!(f, "{}", identity),
+        [filter] => write!(f, "{}", filter),
+        _ => write!(
+            f,
+            "({})",
+            filters
+                .iter()
+                .map(Filter::to_string)
+                .collect::<Vec<_>>()
+                .join(&format!(" {} ", separator))
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    fn datetime(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().into()
+    }
+
+    #[test_case(Filter::eq("doc_type", "Spc"), "doc_type eq 'Spc'")]
+    #[test_case(Filter::ne("doc_type", "Spc"), "doc_type ne 'Spc'")]
+    #[test_case(
+        Filter::gt("metadata_storage_name", "abc"),
+        "metadata_storage_name gt 'abc'"
+    )]
+    #[test_case(
+        Filter::ge("created", datetime("2021-01-01T00:00:00Z")),
+        "created ge 2021-01-01T00:00:00Z"
+    )]
+    #[test_case(
+        Filter::lt("created", datetime("2021-06-30T13:30:05+01:00")),
+        "created lt 2021-06-30T12:30:05Z"
+    )]
+    #[test_case(
+        Filter::le("metadata_storage_size", 300),
+        "metadata_storage_size le 300"
+    )]
+    #[test_case(Filter::eq("territory", Literal::Null), "territory eq null")]
+    #[test_case(Filter::eq("territory", None::<&str>), "territory eq null")]
+    #[test_case(
+        Filter::eq("metadata_storage_size", 300),
+        "metadata_storage_size eq 300"
+    )]
+    #[test_case(Filter::eq("release_state", true), "release_state eq true")]
+    #[test_case(
+        Filter::any("substance_name", Operator::Eq, "IBUPROFEN"),
+        "substance_name/any(f: f eq 'IBUPROFEN')"
+    )]
+    #[test_case(Filter::all("facets", Operator::Ne, "I"), "facets/all(f: f ne 'I')")]
+    fn renders_simple_filters(filter: Filter, expected: &str) {
+        assert_eq!(filter.to_string(), expected);
+    }
+
+    #[test_case("St John's Wort", "product_name eq 'St John''s Wort'")]
+    #[test_case("' or true or '", "product_name eq ''' or true or '''")]
+    #[test_case("''", "product_name eq ''''''")]
+    fn escapes_quotes_in_string_literals(value: &str, expected: &str) {
+        assert_eq!(Filter::eq("product_name", value).to_string(), expected);
+    }
+
+    #[test]
+    fn renders_nested_groups() {
+        let filter = Filter::and(vec![
+            Filter::eq("product_name", "IBUPROFEN 100MG CAPLETS"),
+            Filter::or(vec![
+                Filter::eq("territory", "GB"),
+                Filter::eq("territory", Literal::Null),
+            ]),
+        ]);
+        assert_eq!(
+            filter.to_string(),
+            "(product_name eq 'IBUPROFEN 100MG CAPLETS' and (territory eq 'GB' or territory eq null))"
+        );
+    }
+
+    #[test_case(Filter::and(vec![]), "true")]
+    #[test_case(Filter::or(vec![]), "false")]
+    #[test_case(Filter::and(vec![Filter::eq("doc_type", "Par")]), "doc_type eq 'Par'")]
+    fn collapses_small_groups(filter: Filter, expected: &str) {
+        assert_eq!(filter.to_string(), expected);
+    }
+
+    #[test_case(!Filter::eq("doc_type", "Par"), "not (doc_type eq 'Par')")]
+    #[test_case(
+        !Filter::or(vec![Filter::eq("doc_type", "Par"), Filter::eq("doc_type", "Pil")]),
+        "not (doc_type eq 'Par' or doc_type eq 'Pil')"
+    )]
+    fn renders_negations(filter: Filter, expected: &str) {
+        assert_eq!(filter.to_string(), expected);
+    }
+}
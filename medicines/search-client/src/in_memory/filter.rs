@@ -0,0 +1,122 @@
+use crate::filter::{Filter, Literal, Operator};
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+// Evaluates a `$filter` against a document as Azure Search would, straight from the `Filter` that the
+// Azure client renders to OData, so that the two can't disagree about what was asked for.
+pub fn matches(filter: &Filter, document: &Map<String, Value>) -> bool {
+    match filter {
+        Filter::Compare {
+            field: name,
+            operator,
+            value,
+        } => compare(field(document, name), *operator, value),
+        Filter::Any {
+            field: name,
+            operator,
+            value,
+        } => match field(document, name) {
+            Value::Array(items) => items.iter().any(|item| compare(item, *operator, value)),
+            _ => false,
+        },
+        Filter::All {
+            field: name,
+            operator,
+            value,
+        } => match field(document, name) {
+            Value::Array(items) => items.iter().all(|item| compare(item, *operator, value)),
+            _ => true,
+        },
+        Filter::And(filters) => filters.iter().all(|filter| matches(filter, document)),
+        Filter::Or(filters) => filters.iter().any(|filter| matches(filter, document)),
+        Filter::Not(filter) => !matches(filter, document),
+    }
+}
+
+fn field<'a>(document: &'a Map<String, Value>, name: &str) -> &'a Value {
+    document.get(name).unwrap_or(&Value::Null)
+}
+
+fn compare(actual: &Value, operator: Operator, expected: &Literal) -> bool {
+    let ordering = match (actual, expected) {
+        (Value::Null, Literal::Null) => Some(Ordering::Equal),
+        (Value::Null, _) | (_, Literal::Null) => None,
+        (Value::Bool(a), Literal::Bool(b)) => Some(a.cmp(b)),
+        (Value::Number(a), Literal::Int(b)) => match a.as_i64() {
+            Some(a) => Some(a.cmp(b)),
+            None => a.as_f64().and_then(|a| a.partial_cmp(&(*b as f64))),
+        },
+        (Value::String(a), Literal::String(b)) => Some(a.as_str().cmp(b.as_str())),
+        (Value::String(a), Literal::DateTime(b)) => DateTime::parse_from_rfc3339(a)
+            .ok()
+            .map(|a| a.with_timezone(&Utc).cmp(b)),
+        _ => None,
+    };
+
+    match (operator, ordering) {
+        (Operator::Eq, ordering) => ordering == Some(Ordering::Equal),
+        (Operator::Ne, ordering) => ordering != Some(Ordering::Equal),
+        (Operator::Gt, Some(ordering)) => ordering == Ordering::Greater,
+        (Operator::Ge, Some(ordering)) => ordering != Ordering::Less,
+        (Operator::Lt, Some(ordering)) => ordering == Ordering::Less,
+        (Operator::Le, Some(ordering)) => ordering != Ordering::Greater,
+        (_, None) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use test_case::test_case;
+
+    fn given_a_document() -> Map<String, Value> {
+        match json!({
+            "doc_type": "Spc",
+            "territory": null,
+            "product_name": "NURSE SANDIE'S IBUPROFEN",
+            "substance_name": ["IBUPROFEN", "CAFFEINE"],
+            "created": "2020-06-01T10:00:00+00:00",
+            "metadata_storage_size": 300
+        }) {
+            Value::Object(document) => document,
+            _ => unreachable!(),
+        }
+    }
+
+    fn datetime(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().into()
+    }
+
+    #[test_case(Filter::eq("doc_type", "Spc"), true)]
+    #[test_case(Filter::ne("doc_type", "Spc"), false)]
+    #[test_case(
+        Filter::or(vec![Filter::eq("doc_type", "Pil"), Filter::eq("doc_type", "Spc")]),
+        true
+    )]
+    #[test_case(
+        Filter::and(vec![Filter::eq("doc_type", "Spc"), Filter::eq("territory", "GB")]),
+        false
+    )]
+    #[test_case(
+        Filter::or(vec![Filter::eq("territory", "UK"), Filter::eq("territory", Literal::Null)]),
+        true
+    )]
+    #[test_case(!Filter::eq("territory", Literal::Null), false)]
+    #[test_case(Filter::and(vec![]), true)]
+    #[test_case(Filter::or(vec![]), false)]
+    #[test_case(Filter::eq("product_name", "NURSE SANDIE'S IBUPROFEN"), true)]
+    #[test_case(Filter::any("substance_name", Operator::Eq, "CAFFEINE"), true)]
+    #[test_case(Filter::any("substance_name", Operator::Eq, "PARACETAMOL"), false)]
+    #[test_case(Filter::all("substance_name", Operator::Ne, "PARACETAMOL"), true)]
+    #[test_case(Filter::any("keywords", Operator::Eq, "PAIN"), false)]
+    #[test_case(Filter::gt("metadata_storage_size", 200), true)]
+    #[test_case(Filter::le("metadata_storage_size", 200), false)]
+    #[test_case(Filter::ge("created", datetime("2020-01-01T00:00:00Z")), true)]
+    #[test_case(Filter::lt("created", datetime("2020-06-01T10:30:00+01:00")), false)]
+    #[test_case(Filter::gt("doc_type", Literal::Null), false)]
+    fn evaluates_filters(filter: Filter, expected: bool) {
+        assert_eq!(matches(&filter, &given_a_document()), expected);
+    }
+}
@@ -0,0 +1,1385 @@
+mod filter;
+mod query;
+mod suggest;
+
+use crate::{
+    build_search_term,
+    facet::{FacetRequest, DEFAULT_FACET_COUNT},
+    facet_field_search,
+    filter::{Filter, Operator},
+    get_env_or_default,
+    models::{
+        AutocompleteResults, AzureIndexChangedResult, AzureIndexChangedResults, FacetCounts,
+        FacetResults, IndexEntry, IndexEntryAction, IndexResult, IndexResults, SuggestResults,
+    },
+    query_normalizer::default_searchable_fields,
+    sort::{OrderBy, SortDirection, RELEVANCE},
+    spelling::{VocabularyFacetResults, VOCABULARY_FACETS},
+    stream::{keyset_filter, stream_documents, KeysetPages, KEY_FIELD, PAGE_SIZE},
+    AutocompleteMode, AzurePagination, CreateIndexEntry, DeleteIndexEntry, Search, SearchError,
+    SuggestOptions, Synonyms, Vocabulary, CONTENT_ID_FIELD,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration};
+use futures::stream::BoxStream;
+use query::{Query, Searchable};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, RwLock},
+};
+
+const DEFAULT_KEY_FIELD: &str = "metadata_storage_name";
+
+lazy_static! {
+    static ref INDEXES: Mutex<HashMap<String, Arc<RwLock<InMemoryIndex>>>> =
+        Mutex::new(HashMap::new());
+}
+
+struct InMemoryIndex {
+    name: String,
+    key_field: String,
+    documents: BTreeMap<String, Map<String, Value>>,
+}
+
+impl InMemoryIndex {
+    fn new(name: String, key_field: String) -> Self {
+        Self {
+            name,
+            key_field,
+            documents: BTreeMap::new(),
+        }
+    }
+
+    fn upsert(&mut self, document: Map<String, Value>) -> Result<String, SearchError> {
+        let key = match document.get(&self.key_field) {
+            Some(Value::String(key)) => key.clone(),
+            _ => {
+                return Err(SearchError::bad_request(format!(
+                    "Document is missing key field {}",
+                    self.key_field
+                )))
+            }
+        };
+        self.documents.insert(key.clone(), document);
+        Ok(key)
+    }
+
+    // Applies a document from a batch update, reporting failures per document like Azure does.
+    fn apply(&mut self, mut document: Map<String, Value>) -> AzureIndexChangedResult {
+        let action = match document.remove("@search.action") {
+            Some(Value::String(action)) => action,
+            _ => "upload".to_string(),
+        };
+        let key = match document.get(&self.key_field) {
+            Some(Value::String(key)) => key.clone(),
+            _ => {
+                return index_changed_result(
+                    "",
+                    400,
+                    Some(format!("Document is missing key field {}", self.key_field)),
+                )
+            }
+        };
+
+        match (action.as_str(), self.documents.get_mut(&key)) {
+            ("upload", _) | ("mergeOrUpload", None) => {
+                self.documents.insert(key.clone(), document);
+            }
+            ("merge", Some(existing)) | ("mergeOrUpload", Some(existing)) => {
+                existing.extend(document);
+            }
+            ("merge", None) => {
+                return index_changed_result(&key, 404, Some("Document not found.".to_string()))
+            }
+            ("delete", _) => {
+                self.documents.remove(&key);
+            }
+            (action, _) => {
+                return index_changed_result(
+                    &key,
+                    400,
+                    Some(format!("{} is not a valid index action", action)),
+                )
+            }
+        }
+        index_changed_result(&key, 200, None)
+    }
+}
+
+fn index_changed_result(
+    key: &str,
+    status_code: u16,
+    error_message: Option<String>,
+) -> AzureIndexChangedResult {
+    AzureIndexChangedResult {
+        key: key.to_string(),
+        status: error_message.is_none(),
+        error_message,
+        status_code,
+    }
+}
+
+// The seed file for an index has the same shape as an Azure `docs` response,
+// optionally naming the key field: `{ "key": "id", "value": [ { ... } ] }`.
+fn load_index(name: &str) -> InMemoryIndex {
+    let mut index = InMemoryIndex::new(name.to_string(), DEFAULT_KEY_FIELD.to_string());
+
+    let data_dir = match std::env::var("IN_MEMORY_SEARCH_DATA_DIR") {
+        Ok(data_dir) => data_dir,
+        Err(_) => return index,
+    };
+    let path = std::path::Path::new(&data_dir).join(format!("{}.json", name));
+
+    let seed = std::fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| serde_json::from_str::<Value>(&contents).map_err(Into::into));
+
+    match seed {
+        Ok(seed) => {
+            if let Some(key_field) = seed.get("key").and_then(Value::as_str) {
+                index.key_field = key_field.to_string();
+            }
+            let documents = seed
+                .get("value")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            for document in documents {
+                if let Value::Object(document) = document {
+                    if let Err(e) = index.upsert(document) {
+                        tracing::warn!("Skipping seed document for {}: {:?}", name, e);
+                    }
+                }
+            }
+            tracing::info!(
+                "Loaded {} documents into in-memory index {} from {:?}",
+                index.documents.len(),
+                name,
+                path
+            );
+        }
+        Err(e) => tracing::warn!("No seed data loaded for in-memory index {}: {:?}", name, e),
+    }
+
+    index
+}
+
+fn load_synonyms() -> Synonyms {
+    let path = match std::env::var("AZURE_SEARCH_SYNONYMS_FILE") {
+        Ok(path) => path,
+        Err(_) => return Synonyms::default(),
+    };
+    Synonyms::from_file(&path).unwrap_or_else(|e| {
+        tracing::warn!("No synonyms loaded from {}: {:?}", path, e);
+        Synonyms::default()
+    })
+}
+
+#[derive(Clone)]
+pub struct InMemorySearchClient {
+    index: Arc<RwLock<InMemoryIndex>>,
+    searchable_fields: Option<Vec<String>>,
+    synonyms: Synonyms,
+    search_fuzziness: String,
+    search_exactness_boost: String,
+}
+
+impl Default for InMemorySearchClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemorySearchClient {
+    pub fn new() -> Self {
+        let default_index = get_env_or_default("AZURE_SEARCH_INDEX", "products-index");
+        InMemorySearchClient::new_with_index(default_index)
+    }
+
+    // Clients for the same index name share their documents for the lifetime of the process,
+    // so that index entries written by one client can be searched for by another.
+    pub fn new_with_index(index: String) -> Self {
+        let shared_index = INDEXES
+            .lock()
+            .expect("In-memory index registry lock was poisoned")
+            .entry(index.clone())
+            .or_insert_with(|| Arc::new(RwLock::new(load_index(&index))))
+            .clone();
+
+        InMemorySearchClient::with_index(shared_index, Some(default_searchable_fields(&index)))
+    }
+
+    pub fn with_documents(key_field: &str, documents: Vec<Value>) -> Self {
+        let mut index = InMemoryIndex::new("in-memory".to_string(), key_field.to_string());
+        for document in documents {
+            if let Value::Object(document) = document {
+                index
+                    .upsert(document)
+                    .expect("Document is missing its key field");
+            }
+        }
+
+        InMemorySearchClient::with_index(Arc::new(RwLock::new(index)), None)
+    }
+
+    // Without a list of searchable fields, searches can be scoped to any field of the documents.
+    fn with_index(
+        index: Arc<RwLock<InMemoryIndex>>,
+        searchable_fields: Option<Vec<String>>,
+    ) -> Self {
+        InMemorySearchClient {
+            index,
+            searchable_fields,
+            synonyms: load_synonyms(),
+            search_fuzziness: get_env_or_default("AZURE_SEARCH_WORD_FUZZINESS", "1"),
+            search_exactness_boost: get_env_or_default("AZURE_SEARCH_EXACTNESS_BOOST", "4"),
+        }
+    }
+
+    fn execute(&self, request: &InMemoryRequest) -> Result<Value, SearchError> {
+        let index = self
+            .index
+            .read()
+            .map_err(|_| anyhow::anyhow!("In-memory index lock was poisoned"))?;
+
+        let query = request
+            .search
+            .as_deref()
+            .map(query::parse)
+            .unwrap_or(Query::All);
+        let mut matches: Vec<(f32, &Map<String, Value>)> = index
+            .documents
+            .values()
+            .filter(|document| match &request.filter {
+                Some(filter) => filter::matches(filter, document),
+                None => true,
+            })
+            .filter_map(|document| {
+                query
+                    .score(&AnalyzedDocument::new(document))
+                    .map(|score| (score, document))
+            })
+            .collect();
+
+        matches.sort_by(|a, b| compare_matches(&request.order_by, a, b));
+
+        let mut response = Map::new();
+        response.insert(
+            "@odata.context".to_string(),
+            json!(format!(
+                "in-memory://indexes('{}')/$metadata#docs(*)",
+                index.name
+            )),
+        );
+        if request.include_count {
+            response.insert("@odata.count".to_string(), json!(matches.len()));
+        }
+        if !request.facets.is_empty() {
+            let facets = request
+                .facets
+                .iter()
+                .map(|facet| facet_counts(facet, &matches))
+                .collect::<Map<String, Value>>();
+            response.insert("@search.facets".to_string(), Value::Object(facets));
+        }
+
+        let highlight_tokens = query.highlight_tokens();
+        let value = matches
+            .iter()
+            .skip(request.skip)
+            .take(request.top)
+            .map(|(score, document)| {
+                let mut result = (*document).clone();
+                result.insert("@search.score".to_string(), json!(score));
+                let highlights = request
+                    .highlight
+                    .iter()
+                    .filter_map(|field| {
+                        let fragments = highlight(document.get(field), &highlight_tokens);
+                        if fragments.is_empty() {
+                            None
+                        } else {
+                            Some((field.clone(), json!(fragments)))
+                        }
+                    })
+                    .collect::<Map<String, Value>>();
+                if !highlights.is_empty() {
+                    result.insert("@search.highlights".to_string(), Value::Object(highlights));
+                }
+                Value::Object(result)
+            })
+            .collect::<Vec<Value>>();
+        response.insert("value".to_string(), Value::Array(value));
+
+        Ok(Value::Object(response))
+    }
+
+    fn execute_as<T>(&self, request: &InMemoryRequest) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.execute(request)?;
+        serde_json::from_value::<T>(response).map_err(Into::into)
+    }
+
+    fn find_document<T>(
+        &self,
+        find: impl FnOnce(&InMemoryIndex) -> Option<Map<String, Value>>,
+    ) -> Result<Option<T>, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        let index = self
+            .index
+            .read()
+            .map_err(|_| anyhow::anyhow!("In-memory index lock was poisoned"))?;
+
+        find(&index)
+            .map(|document| serde_json::from_value(Value::Object(document)))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn search_request(
+        &self,
+        search_term: &str,
+        pagination: Option<AzurePagination>,
+        include_count: bool,
+        filter: Option<&Filter>,
+    ) -> InMemoryRequest {
+        let (top, skip) = match pagination {
+            Some(pagination) => (
+                pagination.result_count.max(0) as usize,
+                pagination.offset.max(0) as usize,
+            ),
+            None => (DEFAULT_TOP, 0),
+        };
+
+        InMemoryRequest {
+            search: Some(build_search_term(
+                search_term,
+                self.searchable_fields.as_deref(),
+                &self.synonyms,
+                &self.search_fuzziness,
+                &self.search_exactness_boost,
+            )),
+            filter: filter.cloned(),
+            facets: vec![],
+            highlight: vec!["content".to_string()],
+            include_count,
+            order_by: vec![],
+            top,
+            skip,
+        }
+    }
+
+    fn update_index(&self, document: Value) -> Result<AzureIndexChangedResults, SearchError> {
+        let mut index = self
+            .index
+            .write()
+            .map_err(|_| anyhow::anyhow!("In-memory index lock was poisoned"))?;
+
+        let key = match document {
+            Value::Object(document) => index.upsert(document)?,
+            _ => {
+                return Err(SearchError::bad_request(
+                    "Index entry must be a JSON object",
+                ))
+            }
+        };
+
+        Ok(AzureIndexChangedResults::new(index_changed_result(
+            &key, 200, None,
+        )))
+    }
+}
+
+// Azure Search returns 50 results when `$top` isn't specified.
+const DEFAULT_TOP: usize = 50;
+
+struct InMemoryRequest {
+    search: Option<String>,
+    filter: Option<Filter>,
+    facets: Vec<String>,
+    highlight: Vec<String>,
+    include_count: bool,
+    order_by: Vec<OrderBy>,
+    top: usize,
+    skip: usize,
+}
+
+impl InMemoryRequest {
+    fn filter_only(filter: Filter) -> Self {
+        InMemoryRequest {
+            search: None,
+            filter: Some(filter),
+            facets: vec![],
+            highlight: vec![],
+            include_count: false,
+            order_by: vec![],
+            top: DEFAULT_TOP,
+            skip: 0,
+        }
+    }
+}
+
+// Mirrors the standard Lucene analyser closely enough for local development:
+// lowercase and split on anything which isn't a letter or a digit.
+pub(crate) fn analyze(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+struct AnalyzedDocument {
+    fields: Vec<(String, Vec<Vec<String>>)>,
+}
+
+impl AnalyzedDocument {
+    fn new(document: &Map<String, Value>) -> Self {
+        let fields = document
+            .iter()
+            .map(|(name, value)| {
+                let values = match value {
+                    Value::String(text) => vec![analyze(text)],
+                    Value::Array(items) => items
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(analyze)
+                        .collect(),
+                    _ => vec![],
+                };
+                (name.clone(), values)
+            })
+            .collect();
+
+        AnalyzedDocument { fields }
+    }
+}
+
+impl Searchable for AnalyzedDocument {
+    fn field_values(&self, field: Option<&str>) -> Vec<&[String]> {
+        self.fields
+            .iter()
+            .filter(|(name, _)| match field {
+                Some(field) => name == field,
+                None => true,
+            })
+            .flat_map(|(_, values)| values.iter().map(Vec::as_slice))
+            .collect()
+    }
+}
+
+// Like Azure, ties between the keys are ranked by relevance and missing values sort before any other value.
+fn compare_matches(
+    order_by: &[OrderBy],
+    (score_a, a): &(f32, &Map<String, Value>),
+    (score_b, b): &(f32, &Map<String, Value>),
+) -> Ordering {
+    let by_relevance = score_b.partial_cmp(score_a).unwrap_or(Ordering::Equal);
+
+    order_by
+        .iter()
+        .map(|key| {
+            let ordering = if key.field == RELEVANCE {
+                by_relevance.reverse()
+            } else {
+                compare_values(a.get(&key.field), b.get(&key.field))
+            };
+            match key.direction {
+                SortDirection::Asc => ordering,
+                SortDirection::Desc => ordering.reverse(),
+            }
+        })
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(by_relevance)
+}
+
+fn compare_values(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    match (a.filter(|a| !a.is_null()), b.filter(|b| !b.is_null())) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (Some(Value::String(a)), Some(Value::String(b))) => a.cmp(b),
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Some(Value::Bool(a)), Some(Value::Bool(b))) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+// Supports the `facet` parameter syntax used by Azure, e.g. `facets,count:50000,sort:value` or `created,interval:month`.
+fn facet_counts(facet: &str, matches: &[(f32, &Map<String, Value>)]) -> (String, Value) {
+    let mut parameters = facet.split(',');
+    let field = parameters.next().unwrap_or_default().trim().to_string();
+    let mut count = DEFAULT_FACET_COUNT;
+    let mut sort = "count";
+    let mut interval = None;
+    for parameter in parameters {
+        let mut parts = parameter.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("count"), Some(value)) => count = value.parse().unwrap_or(count),
+            (Some("sort"), Some(value)) => sort = value,
+            (Some("interval"), Some(value)) => interval = Some(value),
+            _ => {}
+        }
+    }
+
+    let mut values: Vec<Value> = vec![];
+    for (_, document) in matches {
+        let field_values = match document.get(&field) {
+            Some(Value::Array(items)) => items.iter().collect(),
+            Some(value) => vec![value],
+            None => vec![],
+        };
+        for value in field_values.into_iter().filter(|value| !value.is_null()) {
+            match interval {
+                Some(interval) => values.extend(interval_start(value, interval)),
+                None => values.push(value.clone()),
+            }
+        }
+    }
+    values.sort_by(|a, b| compare_values(Some(a), Some(b)));
+
+    let mut buckets: Vec<(Value, i32)> = vec![];
+    for value in values {
+        match buckets.last_mut() {
+            Some((last, count)) if *last == value => *count += 1,
+            _ => buckets.push((value, 1)),
+        }
+    }
+    match sort {
+        "value" => {}
+        "-value" => buckets.reverse(),
+        "-count" => buckets.sort_by_key(|(_, count)| *count),
+        _ => buckets.sort_by_key(|(_, count)| std::cmp::Reverse(*count)),
+    }
+
+    // There's a bucket for every interval, as the count only limits the values of other facets.
+    if interval.is_none() {
+        buckets.truncate(count);
+    }
+    let buckets = buckets
+        .into_iter()
+        .map(|(value, count)| json!({ "value": value, "count": count }))
+        .collect::<Vec<Value>>();
+
+    (field, Value::Array(buckets))
+}
+
+// The start of the interval a number or date falls in, which is the value of its facet bucket.
+fn interval_start(value: &Value, interval: &str) -> Option<Value> {
+    if let Ok(width) = interval.parse::<i64>() {
+        if width <= 0 {
+            return None;
+        }
+        return match value.as_i64() {
+            Some(value) => Some(json!(value.div_euclid(width) * width)),
+            None => value
+                .as_f64()
+                .map(|value| json!((value / width as f64).floor() * width as f64)),
+        };
+    }
+
+    let date = DateTime::parse_from_rfc3339(value.as_str()?)
+        .ok()?
+        .naive_utc()
+        .date();
+    let start = match interval {
+        "day" => date,
+        "week" => date - Duration::days(date.weekday().num_days_from_monday().into()),
+        "month" => date.with_day(1)?,
+        "quarter" => date.with_day(1)?.with_month(date.month0() / 3 * 3 + 1)?,
+        "year" => date.with_day(1)?.with_month(1)?,
+        _ => return None,
+    };
+
+    Some(json!(start.format("%Y-%m-%dT00:00:00Z").to_string()))
+}
+
+fn highlight(value: Option<&Value>, tokens: &[&str]) -> Vec<String> {
+    const MAX_FRAGMENTS: usize = 5;
+    const WORDS_OF_CONTEXT: usize = 8;
+
+    let text = match value {
+        Some(Value::String(text)) if !tokens.is_empty() => text,
+        _ => return vec![],
+    };
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let is_match = |word: &str| analyze(word).iter().any(|t| tokens.contains(&t.as_str()));
+
+    words
+        .iter()
+        .enumerate()
+        .filter(|(_, word)| is_match(word))
+        .take(MAX_FRAGMENTS)
+        .map(|(i, _)| {
+            let start = i.saturating_sub(WORDS_OF_CONTEXT);
+            let end = (i + WORDS_OF_CONTEXT + 1).min(words.len());
+            words[start..end]
+                .iter()
+                .map(|word| {
+                    if is_match(word) {
+                        format!("<em>{}</em>", word)
+                    } else {
+                        word.to_string()
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Search for InMemorySearchClient {
+    async fn search<T>(&self, search_term: &str) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        self.execute_as(&self.search_request(search_term, None, false, None))
+    }
+
+    async fn search_with_pagination<T>(
+        &self,
+        search_term: &str,
+        pagination: AzurePagination,
+        include_count: bool,
+    ) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        self.execute_as(&self.search_request(search_term, Some(pagination), include_count, None))
+    }
+
+    async fn search_with_pagination_and_filter<T>(
+        &self,
+        search_term: &str,
+        pagination: AzurePagination,
+        include_count: bool,
+        filter: Option<&Filter>,
+        order_by: &[OrderBy],
+    ) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut request = self.search_request(search_term, Some(pagination), include_count, filter);
+        request.order_by = order_by.to_vec();
+
+        self.execute_as(&request)
+    }
+
+    async fn search_by_facet_field(
+        &self,
+        field_name: &str,
+        field_value: &str,
+    ) -> Result<FacetResults, SearchError> {
+        let (filter, facet) = facet_field_search(field_name, field_value);
+        let mut request = InMemoryRequest::filter_only(filter);
+        request.facets = vec![facet.to_string()];
+        request.top = 0;
+
+        self.execute_as(&request)
+    }
+
+    async fn search_facets(
+        &self,
+        search_term: &str,
+        filter: Option<&Filter>,
+        facets: &[FacetRequest],
+    ) -> Result<FacetCounts, SearchError> {
+        let mut request = self.search_request(search_term, None, true, filter);
+        request.facets = facets.iter().map(FacetRequest::to_string).collect();
+        request.highlight = vec![];
+        request.top = 0;
+
+        self.execute_as(&request)
+    }
+
+    async fn filter_by_collection_field<T>(
+        &self,
+        field_name: &str,
+        field_value: &str,
+    ) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        self.execute_as(&InMemoryRequest::filter_only(Filter::any(
+            field_name,
+            Operator::Eq,
+            field_value,
+        )))
+    }
+
+    async fn filter_by_non_collection_field<T>(
+        &self,
+        field_name: &str,
+        field_value: &str,
+    ) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        self.execute_as(&InMemoryRequest::filter_only(Filter::eq(
+            field_name,
+            field_value,
+        )))
+    }
+
+    async fn lookup_document<T>(&self, key: &str) -> Result<Option<T>, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        self.find_document(|index| index.documents.get(key).cloned())
+    }
+
+    async fn get_document<T>(&self, key: &str) -> Result<Option<T>, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        self.find_document(|index| {
+            index
+                .documents
+                .get(key)
+                .or_else(|| {
+                    index
+                        .documents
+                        .values()
+                        .find(|document| document.get(CONTENT_ID_FIELD) == Some(&json!(key)))
+                })
+                .cloned()
+        })
+    }
+
+    async fn suggest(
+        &self,
+        search_term: &str,
+        options: &SuggestOptions,
+    ) -> Result<SuggestResults, SearchError> {
+        let index = self
+            .index
+            .read()
+            .map_err(|_| anyhow::anyhow!("In-memory index lock was poisoned"))?;
+
+        let response = suggest::suggest(
+            index.documents.values(),
+            &index.key_field,
+            search_term,
+            options,
+        );
+        serde_json::from_value(response).map_err(Into::into)
+    }
+
+    async fn autocomplete(
+        &self,
+        search_term: &str,
+        mode: AutocompleteMode,
+        options: &SuggestOptions,
+    ) -> Result<AutocompleteResults, SearchError> {
+        let index = self
+            .index
+            .read()
+            .map_err(|_| anyhow::anyhow!("In-memory index lock was poisoned"))?;
+
+        let response = suggest::autocomplete(index.documents.values(), search_term, mode, options);
+        serde_json::from_value(response).map_err(Into::into)
+    }
+
+    async fn did_you_mean(
+        &self,
+        search_term: &str,
+        top: usize,
+    ) -> Result<Vec<String>, SearchError> {
+        let request = InMemoryRequest {
+            search: None,
+            filter: None,
+            facets: VOCABULARY_FACETS
+                .iter()
+                .map(|field| format!("{},count:50000", field))
+                .collect(),
+            highlight: vec![],
+            include_count: false,
+            order_by: vec![],
+            top: 0,
+            skip: 0,
+        };
+        let results: VocabularyFacetResults = self.execute_as(&request)?;
+        let vocabulary = Vocabulary::from_facets(results.facet_results.values().flatten());
+
+        Ok(vocabulary.did_you_mean(search_term, top))
+    }
+
+    fn stream_documents(
+        &self,
+        filter: Option<Filter>,
+    ) -> BoxStream<'_, Result<IndexResult, SearchError>> {
+        stream_documents(self, filter, PAGE_SIZE)
+    }
+}
+
+#[async_trait]
+impl KeysetPages for InMemorySearchClient {
+    async fn page_after(
+        &self,
+        after: Option<&str>,
+        filter: Option<&Filter>,
+        top: usize,
+    ) -> Result<Vec<IndexResult>, SearchError> {
+        let request = InMemoryRequest {
+            search: None,
+            filter: keyset_filter(after, filter),
+            facets: vec![],
+            highlight: vec![],
+            include_count: false,
+            order_by: vec![OrderBy::asc(KEY_FIELD)],
+            top,
+            skip: 0,
+        };
+        let results: IndexResults = self.execute_as(&request)?;
+
+        Ok(results.search_results)
+    }
+}
+
+#[async_trait]
+impl DeleteIndexEntry for InMemorySearchClient {
+    async fn delete_index_entry(
+        &self,
+        key_name: &str,
+        value: &str,
+    ) -> Result<AzureIndexChangedResults, SearchError> {
+        let mut index = self
+            .index
+            .write()
+            .map_err(|_| anyhow::anyhow!("In-memory index lock was poisoned"))?;
+
+        if key_name != index.key_field {
+            return Err(SearchError::bad_request(format!(
+                "{} is not the key field of index {}",
+                key_name, index.name
+            )));
+        }
+        index.documents.remove(value);
+
+        Ok(AzureIndexChangedResults::new(index_changed_result(
+            value, 200, None,
+        )))
+    }
+
+    async fn delete_index_entries(
+        &self,
+        key_name: &str,
+        values: &[String],
+    ) -> Result<AzureIndexChangedResults, SearchError> {
+        let mut index = self
+            .index
+            .write()
+            .map_err(|_| anyhow::anyhow!("In-memory index lock was poisoned"))?;
+
+        if key_name != index.key_field {
+            return Err(SearchError::bad_request(format!(
+                "{} is not the key field of index {}",
+                key_name, index.name
+            )));
+        }
+
+        let results = values
+            .iter()
+            .map(|value| {
+                index.documents.remove(value);
+                index_changed_result(value, 200, None)
+            })
+            .collect();
+        Ok(AzureIndexChangedResults::from_results(results))
+    }
+}
+
+#[async_trait]
+impl CreateIndexEntry for InMemorySearchClient {
+    async fn create_index_entry(
+        &self,
+        key_values: IndexEntry,
+    ) -> Result<AzureIndexChangedResults, SearchError> {
+        self.update_index(serde_json::to_value(key_values)?)
+    }
+
+    async fn create_index_entries(
+        &self,
+        actions: Vec<IndexEntryAction>,
+    ) -> Result<AzureIndexChangedResults, SearchError> {
+        let documents = actions
+            .into_iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<Value>, serde_json::Error>>()?;
+
+        let mut index = self
+            .index
+            .write()
+            .map_err(|_| anyhow::anyhow!("In-memory index lock was poisoned"))?;
+
+        let results = documents
+            .into_iter()
+            .map(|document| match document {
+                Value::Object(document) => index.apply(document),
+                _ => index_changed_result(
+                    "",
+                    400,
+                    Some("Index entry must be a JSON object".to_string()),
+                ),
+            })
+            .collect();
+        Ok(AzureIndexChangedResults::from_results(results))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::facet::{FacetInterval, FacetSort};
+    use futures::stream::TryStreamExt;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    fn given_an_index_entry(
+        name: &str,
+        product_name: &str,
+        substance: &str,
+        doc_type: &str,
+    ) -> Value {
+        json!({
+            "metadata_storage_name": name,
+            "metadata_storage_path": format!("https://example.com/{}", name),
+            "metadata_storage_size": 300,
+            "file_name": format!("CON{}", name),
+            "doc_type": doc_type,
+            "territory": "UK",
+            "title": format!("{} {}", product_name, doc_type),
+            "product_name": product_name,
+            "substance_name": [substance],
+            "facets": [
+                substance[..1].to_string(),
+                format!("{}, {}", &substance[..1], substance),
+                format!("{}, {}, {}", &substance[..1], substance, product_name)
+            ],
+            "suggestions": [],
+            "content": format!("{} is used to treat pain", product_name),
+            "created": "2020-06-01T10:00:00+00:00"
+        })
+    }
+
+    fn given_an_action(action: &str, mut document: Value) -> Value {
+        document["@search.action"] = json!(action);
+        document
+    }
+
+    fn given_a_client() -> InMemorySearchClient {
+        InMemorySearchClient::with_documents(
+            DEFAULT_KEY_FIELD,
+            vec![
+                given_an_index_entry("1", "IBUPROFEN 200MG TABLETS", "IBUPROFEN", "Spc"),
+                given_an_index_entry("2", "IBUPROFEN 400MG TABLETS", "IBUPROFEN", "Pil"),
+                given_an_index_entry("3", "PARACETAMOL 500MG CAPLETS", "PARACETAMOL", "Spc"),
+            ],
+        )
+    }
+
+    fn names(results: &IndexResults) -> Vec<String> {
+        let mut names: Vec<String> = results
+            .search_results
+            .iter()
+            .map(|result| result.metadata_storage_name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn searches_with_the_same_query_as_azure() {
+        let client = given_a_client();
+        let request = client.search_request("ibuprofin tablets", None, true, None);
+        let results: IndexResults = client.execute_as(&request).unwrap();
+
+        assert_eq!(names(&results), vec!["1", "2"]);
+        assert_eq!(results.count, Some(2));
+    }
+
+    #[test_case(r#""200mg tablets""#, vec!["1"])]
+    #[test_case("ibuprofen -400mg", vec!["1"])]
+    #[test_case("substance:paracetamol", vec!["3"])]
+    #[test_case("title:pil", vec!["2"])]
+    fn searches_with_phrases_exclusions_and_fields(search_term: &str, expected: Vec<&str>) {
+        let client = given_a_client();
+        let request = client.search_request(search_term, None, false, None);
+        let results: IndexResults = client.execute_as(&request).unwrap();
+
+        assert_eq!(names(&results), expected);
+    }
+
+    #[test]
+    fn searches_for_synonyms() {
+        let client = InMemorySearchClient {
+            synonyms: Synonyms::parse("acetaminophen, paracetamol"),
+            ..given_a_client()
+        };
+        let request = client.search_request("acetaminophen", None, false, None);
+        let results: IndexResults = client.execute_as(&request).unwrap();
+
+        assert_eq!(names(&results), vec!["3"]);
+    }
+
+    #[test]
+    fn filters_and_paginates_results() {
+        let client = given_a_client();
+        let request = client.search_request(
+            " ",
+            Some(AzurePagination {
+                result_count: 1,
+                offset: 0,
+            }),
+            true,
+            Some(&Filter::eq("doc_type", "Spc")),
+        );
+        let results: IndexResults = client.execute_as(&request).unwrap();
+
+        assert_eq!(results.search_results.len(), 1);
+        assert_eq!(results.count, Some(2));
+    }
+
+    #[test]
+    fn filters_on_values_containing_quotes() {
+        let client = given_a_client();
+        client
+            .update_index(given_an_index_entry(
+                "4",
+                "ST JOHN'S WORT",
+                "HYPERICUM",
+                "Spc",
+            ))
+            .unwrap();
+
+        let results: IndexResults = tokio_test::block_on(
+            client.filter_by_non_collection_field("product_name", "ST JOHN'S WORT"),
+        )
+        .unwrap();
+        assert_eq!(names(&results), vec!["4"]);
+    }
+
+    #[test]
+    fn sorts_by_fields_and_then_by_relevance() {
+        let client = given_a_client();
+        let mut request = client.search_request("ibuprofen 200mg", None, false, None);
+        request.order_by = vec![OrderBy::asc("product_name")];
+        let results: IndexResults = client.execute_as(&request).unwrap();
+        let names_in_order: Vec<&str> = results
+            .search_results
+            .iter()
+            .map(|result| result.metadata_storage_name.as_str())
+            .collect();
+        assert_eq!(names_in_order, vec!["1", "2"]);
+
+        let mut request = client.search_request(" ", None, false, None);
+        request.order_by = vec![OrderBy::desc("doc_type"), OrderBy::desc("product_name")];
+        let results: IndexResults = client.execute_as(&request).unwrap();
+        let names_in_order: Vec<&str> = results
+            .search_results
+            .iter()
+            .map(|result| result.metadata_storage_name.as_str())
+            .collect();
+        assert_eq!(names_in_order, vec!["3", "1", "2"]);
+    }
+
+    #[test]
+    fn highlights_matching_content() {
+        let client = given_a_client();
+        let request = client.search_request("paracetamol", None, false, None);
+        let results: IndexResults = client.execute_as(&request).unwrap();
+
+        assert_eq!(
+            results.search_results[0]
+                .highlights
+                .clone()
+                .unwrap()
+                .content,
+            vec!["<em>PARACETAMOL</em> 500MG CAPLETS is used to treat pain"]
+        );
+    }
+
+    #[test]
+    fn counts_facets() {
+        let client = given_a_client();
+        let mut request = InMemoryRequest::filter_only(Filter::any("facets", Operator::Eq, "I"));
+        request.facets = vec!["facets,count:50000,sort:value".to_string()];
+        request.top = 0;
+        let results: FacetResults = client.execute_as(&request).unwrap();
+
+        let facets: Vec<(String, i32)> = results
+            .facet_results
+            .facets
+            .into_iter()
+            .map(|facet| (facet.value, facet.count))
+            .collect();
+        assert_eq!(
+            facets,
+            vec![
+                ("I".to_string(), 2),
+                ("I, IBUPROFEN".to_string(), 2),
+                ("I, IBUPROFEN, IBUPROFEN 200MG TABLETS".to_string(), 1),
+                ("I, IBUPROFEN, IBUPROFEN 400MG TABLETS".to_string(), 1),
+            ]
+        );
+        assert!(results.search_results.is_empty());
+    }
+
+    #[test]
+    fn searches_by_a_facet_field_counting_the_facets_like_azure() {
+        let client = given_a_client();
+        // Azure counts the `facets` of the documents found, not the values of the field they were found by
+        let results =
+            tokio_test::block_on(client.search_by_facet_field("substance_name", "IBUPROFEN"))
+                .unwrap();
+
+        let facets: Vec<(String, i32)> = results
+            .facet_results
+            .facets
+            .into_iter()
+            .map(|facet| (facet.value, facet.count))
+            .collect();
+        assert_eq!(
+            facets,
+            vec![
+                ("I".to_string(), 2),
+                ("I, IBUPROFEN".to_string(), 2),
+                ("I, IBUPROFEN, IBUPROFEN 200MG TABLETS".to_string(), 1),
+                ("I, IBUPROFEN, IBUPROFEN 400MG TABLETS".to_string(), 1),
+            ]
+        );
+        assert!(results.search_results.is_empty());
+    }
+
+    #[test]
+    fn counts_facets_of_a_search() {
+        let client = given_a_client();
+        let facets = vec![
+            FacetRequest::new("doc_type"),
+            FacetRequest::new("substance_name").sort(FacetSort::Value(SortDirection::Desc)),
+            FacetRequest::new("metadata_storage_size").interval(FacetInterval::Number(200)),
+            FacetRequest::new("created").interval(FacetInterval::Quarter),
+        ];
+
+        let results = tokio_test::block_on(client.search_facets(
+            " ",
+            Some(&Filter::eq("territory", "UK")),
+            &facets,
+        ))
+        .unwrap();
+
+        let buckets = |field: &str| -> Vec<(String, i32)> {
+            results
+                .buckets(field)
+                .iter()
+                .map(|bucket| (bucket.value.to_string(), bucket.count))
+                .collect()
+        };
+        assert_eq!(results.count, Some(3));
+        assert_eq!(
+            buckets("doc_type"),
+            vec![("Spc".to_string(), 2), ("Pil".to_string(), 1)]
+        );
+        assert_eq!(
+            buckets("substance_name"),
+            vec![("PARACETAMOL".to_string(), 1), ("IBUPROFEN".to_string(), 2)]
+        );
+        assert_eq!(
+            buckets("metadata_storage_size"),
+            vec![("200".to_string(), 3)]
+        );
+        assert_eq!(
+            buckets("created"),
+            vec![("2020-04-01T00:00:00Z".to_string(), 3)]
+        );
+    }
+
+    #[test_case("2", Some("2"); "by key")]
+    #[test_case("CON3", Some("3"); "by content id")]
+    #[test_case("4", None; "when it isn't there")]
+    fn gets_a_document(key: &str, expected: Option<&str>) {
+        let client = given_a_client();
+
+        let document: Option<IndexResult> = tokio_test::block_on(client.get_document(key)).unwrap();
+        assert_eq!(
+            document.map(|document| document.metadata_storage_name),
+            expected.map(String::from)
+        );
+    }
+
+    #[test_case("2", Some("2"); "by key")]
+    #[test_case("CON3", None; "by content id")]
+    fn looks_a_document_up_by_key_only(key: &str, expected: Option<&str>) {
+        let client = given_a_client();
+
+        let document: Option<IndexResult> =
+            tokio_test::block_on(client.lookup_document(key)).unwrap();
+        assert_eq!(
+            document.map(|document| document.metadata_storage_name),
+            expected.map(String::from)
+        );
+    }
+
+    #[test]
+    fn streams_documents_matching_a_filter_in_order_of_their_keys() {
+        let client = given_a_client();
+
+        let documents: Vec<IndexResult> = tokio_test::block_on(
+            stream_documents(&client, Some(Filter::eq("doc_type", "Spc")), 1).try_collect(),
+        )
+        .unwrap();
+        assert_eq!(
+            documents
+                .into_iter()
+                .map(|document| document.metadata_storage_name)
+                .collect::<Vec<_>>(),
+            vec!["1", "3"]
+        );
+    }
+
+    #[test]
+    fn suggests_spellings_from_facets_and_product_names() {
+        let client = given_a_client();
+
+        let suggestions =
+            tokio_test::block_on(client.did_you_mean("paracetmol caplest", 5)).unwrap();
+        assert_eq!(suggestions, vec!["paracetamol caplets"]);
+    }
+
+    #[test]
+    fn suggests_documents_matching_a_partial_term() {
+        let client = given_a_client();
+        let options = SuggestOptions {
+            fuzzy: true,
+            highlight_pre_tag: Some("<em>".to_string()),
+            highlight_post_tag: Some("</em>".to_string()),
+            top: 5,
+        };
+        let results = tokio_test::block_on(client.suggest("ibuprofn 4", &options)).unwrap();
+
+        let texts: Vec<String> = results
+            .suggest_results
+            .into_iter()
+            .map(|result| result.text)
+            .collect();
+        assert_eq!(texts, vec!["<em>IBUPROFEN</em> <em>400MG</em> TABLETS Pil"]);
+    }
+
+    #[test]
+    fn autocompletes_the_last_term() {
+        let client = given_a_client();
+        let options = SuggestOptions::default();
+
+        let one_term = tokio_test::block_on(client.autocomplete(
+            "pain para",
+            AutocompleteMode::OneTerm,
+            &options,
+        ))
+        .unwrap();
+        let completions: Vec<(String, String)> = one_term
+            .autocomplete_results
+            .into_iter()
+            .map(|result| (result.text, result.query_plus_text))
+            .collect();
+        assert_eq!(
+            completions,
+            vec![("paracetamol".to_string(), "pain paracetamol".to_string())]
+        );
+
+        let two_terms =
+            tokio_test::block_on(client.autocomplete("ibu", AutocompleteMode::TwoTerms, &options))
+                .unwrap();
+        let completions: Vec<String> = two_terms
+            .autocomplete_results
+            .into_iter()
+            .map(|result| result.text)
+            .collect();
+        assert_eq!(
+            completions,
+            vec!["ibuprofen", "ibuprofen 200mg", "ibuprofen 400mg"]
+        );
+    }
+
+    #[test]
+    fn updates_and_deletes_index_entries() {
+        let client = given_a_client();
+        client
+            .update_index(given_an_index_entry("4", "NUROFEN", "IBUPROFEN", "Par"))
+            .unwrap();
+
+        let request = client.search_request("nurofen", None, false, None);
+        let results: IndexResults = client.execute_as(&request).unwrap();
+        assert_eq!(names(&results), vec!["4"]);
+
+        tokio_test::block_on(client.delete_index_entry("metadata_storage_name", "4")).unwrap();
+        let results: IndexResults = client.execute_as(&request).unwrap();
+        assert!(results.search_results.is_empty());
+    }
+
+    #[test]
+    fn applies_batches_of_index_actions() {
+        let client = given_a_client();
+        let results: Vec<(String, u16)> = {
+            let mut index = client.index.write().unwrap();
+            vec![
+                json!({ "@search.action": "merge", "metadata_storage_name": "1", "product_name": "NUROFEN" }),
+                json!({ "@search.action": "merge", "metadata_storage_name": "5", "product_name": "NUROFEN" }),
+                given_an_action("mergeOrUpload", given_an_index_entry("5", "NUROFEN", "IBUPROFEN", "Par")),
+                json!({ "@search.action": "delete", "metadata_storage_name": "3" }),
+                json!({ "@search.action": "upload", "product_name": "NUROFEN" }),
+            ]
+            .into_iter()
+            .map(|document| {
+                let result = index.apply(document.as_object().unwrap().clone());
+                (result.key, result.status_code)
+            })
+            .collect()
+        };
+
+        assert_eq!(
+            results,
+            vec![
+                ("1".to_string(), 200),
+                ("5".to_string(), 404),
+                ("5".to_string(), 200),
+                ("3".to_string(), 200),
+                ("".to_string(), 400),
+            ]
+        );
+        let results: IndexResults =
+            tokio_test::block_on(client.filter_by_non_collection_field("product_name", "NUROFEN"))
+                .unwrap();
+        assert_eq!(names(&results), vec!["1", "5"]);
+
+        let deleted = tokio_test::block_on(
+            client
+                .delete_index_entries("metadata_storage_name", &["1".to_string(), "2".to_string()]),
+        )
+        .unwrap();
+        assert_eq!(deleted.succeeded().count(), 2);
+        let results: IndexResults = client
+            .execute_as(&client.search_request("", None, false, None))
+            .unwrap();
+        assert_eq!(names(&results), vec!["5"]);
+    }
+
+    #[test]
+    fn rejects_malformed_filters() {
+        let client = given_a_client();
+        let mut request = client.search_request("", None, false, None);
+        request.filter = Some("doc_type eq 'Spc".to_string());
+        let error = client.execute_as::<IndexResults>(&request).unwrap_err();
+        assert_eq!(error.kind(), crate::SearchErrorKind::BadRequest);
+    }
+}
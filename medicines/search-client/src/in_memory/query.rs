@@ -0,0 +1,591 @@
+use super::analyze;
+
+// Evaluates the subset of the Lucene query syntax that `build_search` produces
+// (terms, fuzzy `~` and boost `^` modifiers, `||`/`&&`, groups and escapes),
+// plus phrases, field prefixes, prefix wildcards and `-`/`NOT` exclusions.
+// Adjacent clauses are combined as `searchMode=all` does, i.e. with AND.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Query {
+    Term {
+        field: Option<String>,
+        token: String,
+        fuzziness: usize,
+        prefix: bool,
+        boost: f32,
+    },
+    Phrase {
+        field: Option<String>,
+        tokens: Vec<String>,
+        boost: f32,
+    },
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    All,
+    Empty,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String, bool),
+    Phrase(String),
+    Field(String),
+    Fuzzy(Option<f32>),
+    Boost(f32),
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    Required,
+}
+
+pub fn parse(search_term: &str) -> Query {
+    let tokens = tokenize(search_term);
+    if tokens.is_empty() {
+        return Query::All;
+    }
+
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+    };
+    let mut query = parser.parse_or(None);
+    while parser.peek().is_some() {
+        // Unbalanced closing parentheses are skipped rather than failing the whole search.
+        parser.next();
+        query = Query::And(vec![query, parser.parse_or(None)]);
+    }
+
+    match simplify(query) {
+        Query::Empty => Query::All,
+        query => query,
+    }
+}
+
+fn tokenize(search_term: &str) -> Vec<Token> {
+    let chars: Vec<char> = search_term.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LeftParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RightParen);
+                i += 1;
+            }
+            '|' if chars.get(i + 1) == Some(&'|') => {
+                tokens.push(Token::Or);
+                i += 2;
+            }
+            '&' if chars.get(i + 1) == Some(&'&') => {
+                tokens.push(Token::And);
+                i += 2;
+            }
+            '-' | '!' => {
+                tokens.push(Token::Not);
+                i += 1;
+            }
+            '+' => {
+                tokens.push(Token::Required);
+                i += 1;
+            }
+            '"' => {
+                let mut phrase = String::new();
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        i += 1;
+                    }
+                    phrase.push(chars[i]);
+                    i += 1;
+                }
+                i += 1;
+                tokens.push(Token::Phrase(phrase));
+            }
+            '~' => {
+                let (number, next) = read_number(&chars, i + 1);
+                tokens.push(Token::Fuzzy(number));
+                i = next;
+            }
+            '^' => {
+                let (number, next) = read_number(&chars, i + 1);
+                tokens.push(Token::Boost(number.unwrap_or(1.0)));
+                i = next;
+            }
+            _ => {
+                let mut word = String::new();
+                let mut prefix = false;
+                while i < chars.len() {
+                    match chars[i] {
+                        '\\' if i + 1 < chars.len() => {
+                            word.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        '*' => {
+                            prefix = true;
+                            i += 1;
+                        }
+                        c if c.is_whitespace() || "()\"~^:".contains(c) => break,
+                        c => {
+                            word.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+
+                if chars.get(i) == Some(&':') && !word.is_empty() {
+                    tokens.push(Token::Field(word));
+                    i += 1;
+                    continue;
+                }
+
+                tokens.push(match word.as_str() {
+                    "AND" if !prefix => Token::And,
+                    "OR" if !prefix => Token::Or,
+                    "NOT" if !prefix => Token::Not,
+                    _ => Token::Word(word, prefix),
+                });
+            }
+        }
+    }
+
+    tokens
+}
+
+fn read_number(chars: &[char], start: usize) -> (Option<f32>, usize) {
+    let end = chars[start..]
+        .iter()
+        .position(|c| !(c.is_ascii_digit() || *c == '.'))
+        .map(|offset| start + offset)
+        .unwrap_or_else(|| chars.len());
+    let number = chars[start..end].iter().collect::<String>().parse().ok();
+    (number, end)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self, field: Option<&str>) -> Query {
+        let mut clauses = vec![self.parse_and(field)];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            clauses.push(self.parse_and(field));
+        }
+        match clauses.len() {
+            1 => clauses.remove(0),
+            _ => Query::Or(clauses),
+        }
+    }
+
+    fn parse_and(&mut self, field: Option<&str>) -> Query {
+        let mut clauses = vec![];
+        loop {
+            match self.peek() {
+                None | Some(Token::RightParen) | Some(Token::Or) => break,
+                Some(Token::And) => {
+                    self.next();
+                }
+                _ => clauses.push(self.parse_clause(field)),
+            }
+        }
+        match clauses.len() {
+            0 => Query::Empty,
+            1 => clauses.remove(0),
+            _ => Query::And(clauses),
+        }
+    }
+
+    fn parse_clause(&mut self, field: Option<&str>) -> Query {
+        let token = match self.next() {
+            Some(token) => token.clone(),
+            None => return Query::Empty,
+        };
+
+        match token {
+            Token::Not => Query::Not(Box::new(self.parse_clause(field))),
+            Token::Required => self.parse_clause(field),
+            Token::Field(name) => self.parse_clause(Some(&name)),
+            Token::LeftParen => {
+                let query = self.parse_or(field);
+                if self.peek() == Some(&Token::RightParen) {
+                    self.next();
+                }
+                let (_, boost) = self.parse_modifiers();
+                with_boost(query, boost)
+            }
+            Token::Word(word, prefix) => {
+                let (fuzziness, boost) = self.parse_modifiers();
+                term_query(field, &word, prefix, fuzziness, boost)
+            }
+            Token::Phrase(phrase) => {
+                let (_, boost) = self.parse_modifiers();
+                let tokens = analyze(&phrase);
+                match tokens.len() {
+                    0 => Query::Empty,
+                    _ => Query::Phrase {
+                        field: field.map(String::from),
+                        tokens,
+                        boost,
+                    },
+                }
+            }
+            _ => Query::Empty,
+        }
+    }
+
+    fn parse_modifiers(&mut self) -> (usize, f32) {
+        let mut fuzziness = 0;
+        let mut boost = 1.0;
+        loop {
+            match self.peek() {
+                Some(Token::Fuzzy(distance)) => {
+                    fuzziness = distance.map(|d| d.round() as usize).unwrap_or(2).min(2);
+                    self.next();
+                }
+                Some(Token::Boost(value)) => {
+                    boost = *value;
+                    self.next();
+                }
+                _ => return (fuzziness, boost),
+            }
+        }
+    }
+}
+
+fn term_query(
+    field: Option<&str>,
+    word: &str,
+    prefix: bool,
+    fuzziness: usize,
+    boost: f32,
+) -> Query {
+    let mut tokens = analyze(word);
+    match tokens.len() {
+        0 => Query::Empty,
+        1 => Query::Term {
+            field: field.map(String::from),
+            token: tokens.remove(0),
+            fuzziness,
+            prefix,
+            boost,
+        },
+        _ => Query::Phrase {
+            field: field.map(String::from),
+            tokens,
+            boost,
+        },
+    }
+}
+
+fn with_boost(query: Query, factor: f32) -> Query {
+    if (factor - 1.0).abs() < f32::EPSILON {
+        return query;
+    }
+    match query {
+        Query::Term {
+            field,
+            token,
+            fuzziness,
+            prefix,
+            boost,
+        } => Query::Term {
+            field,
+            token,
+            fuzziness,
+            prefix,
+            boost: boost * factor,
+        },
+        Query::Phrase {
+            field,
+            tokens,
+            boost,
+        } => Query::Phrase {
+            field,
+            tokens,
+            boost: boost * factor,
+        },
+        Query::And(clauses) => {
+            Query::And(clauses.into_iter().map(|q| with_boost(q, factor)).collect())
+        }
+        Query::Or(clauses) => {
+            Query::Or(clauses.into_iter().map(|q| with_boost(q, factor)).collect())
+        }
+        query => query,
+    }
+}
+
+// Terms which analyse to nothing (e.g. a lone escaped symbol) are dropped,
+// the same way the Lucene analyser drops them.
+fn simplify(query: Query) -> Query {
+    match query {
+        Query::And(clauses) => simplify_clauses(clauses, Query::And),
+        Query::Or(clauses) => simplify_clauses(clauses, Query::Or),
+        Query::Not(inner) => match simplify(*inner) {
+            Query::Empty => Query::Empty,
+            inner => Query::Not(Box::new(inner)),
+        },
+        query => query,
+    }
+}
+
+fn simplify_clauses(clauses: Vec<Query>, combine: fn(Vec<Query>) -> Query) -> Query {
+    let mut clauses: Vec<Query> = clauses
+        .into_iter()
+        .map(simplify)
+        .filter(|clause| *clause != Query::Empty)
+        .collect();
+    match clauses.len() {
+        0 => Query::Empty,
+        1 => clauses.remove(0),
+        _ => combine(clauses),
+    }
+}
+
+pub trait Searchable {
+    fn field_values(&self, field: Option<&str>) -> Vec<&[String]>;
+}
+
+impl Query {
+    // Returns the relevance score of a matching document, or `None` if it doesn't match.
+    pub fn score(&self, document: &impl Searchable) -> Option<f32> {
+        match self {
+            Query::All | Query::Empty => Some(1.0),
+            Query::Term {
+                field,
+                token,
+                fuzziness,
+                prefix,
+                boost,
+            } => {
+                let score: f32 = document
+                    .field_values(field.as_deref())
+                    .iter()
+                    .flat_map(|tokens| tokens.iter())
+                    .map(|candidate| term_weight(token, candidate, *fuzziness, *prefix))
+                    .sum();
+                positive(score * boost)
+            }
+            Query::Phrase {
+                field,
+                tokens,
+                boost,
+            } => {
+                let occurrences = document
+                    .field_values(field.as_deref())
+                    .iter()
+                    .map(|candidates| {
+                        candidates
+                            .windows(tokens.len())
+                            .filter(|window| *window == tokens.as_slice())
+                            .count()
+                    })
+                    .sum::<usize>();
+                positive(occurrences as f32 * tokens.len() as f32 * boost)
+            }
+            Query::And(clauses) => clauses.iter().try_fold(0.0, |total, clause| {
+                clause.score(document).map(|score| total + score)
+            }),
+            Query::Or(clauses) => positive(
+                clauses
+                    .iter()
+                    .filter_map(|clause| clause.score(document))
+                    .sum(),
+            ),
+            Query::Not(inner) => match inner.score(document) {
+                Some(_) => None,
+                None => Some(0.0),
+            },
+        }
+    }
+
+    // The tokens a highlighter should emphasise.
+    pub fn highlight_tokens(&self) -> Vec<&str> {
+        match self {
+            Query::Term { token, .. } => vec![token],
+            Query::Phrase { tokens, .. } => tokens.iter().map(String::as_str).collect(),
+            Query::And(clauses) | Query::Or(clauses) => {
+                clauses.iter().flat_map(Query::highlight_tokens).collect()
+            }
+            _ => vec![],
+        }
+    }
+}
+
+fn positive(score: f32) -> Option<f32> {
+    if score > 0.0 {
+        Some(score)
+    } else {
+        None
+    }
+}
+
+fn term_weight(token: &str, candidate: &str, fuzziness: usize, prefix: bool) -> f32 {
+    if token == candidate {
+        return 1.0;
+    }
+    if prefix && candidate.starts_with(token) {
+        return 0.5;
+    }
+    if fuzziness > 0 {
+        let distance = levenshtein(token, candidate);
+        if distance <= fuzziness {
+            return 1.0 / (1.0 + distance as f32);
+        }
+    }
+    0.0
+}
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    struct Document(HashMap<&'static str, Vec<Vec<String>>>);
+
+    impl Searchable for Document {
+        fn field_values(&self, field: Option<&str>) -> Vec<&[String]> {
+            self.0
+                .iter()
+                .filter(|(name, _)| field.map(|field| field == **name).unwrap_or(true))
+                .flat_map(|(_, values)| values.iter().map(Vec::as_slice))
+                .collect()
+        }
+    }
+
+    fn given_a_document() -> Document {
+        let mut fields = HashMap::new();
+        fields.insert(
+            "title",
+            vec![analyze("Ibuprofen 200mg film-coated tablets")],
+        );
+        fields.insert("pl_number", vec![analyze("PL123451234")]);
+        Document(fields)
+    }
+
+    fn term(token: &str, fuzziness: usize, boost: f32) -> Query {
+        Query::Term {
+            field: None,
+            token: token.to_string(),
+            fuzziness,
+            prefix: false,
+            boost,
+        }
+    }
+
+    #[test]
+    fn parses_the_query_produced_by_build_search() {
+        let query = parse("(cool~1 || cool^4) (beans~1 || beans^4)");
+        assert_eq!(
+            query,
+            Query::And(vec![
+                Query::Or(vec![term("cool", 1, 1.0), term("cool", 0, 4.0)]),
+                Query::Or(vec![term("beans", 1, 1.0), term("beans", 0, 4.0)]),
+            ])
+        );
+    }
+
+    #[test]
+    fn drops_terms_which_analyse_to_nothing() {
+        let query = parse(r"(\*\!~1 || \*\!^4) (beans~1 || beans^4)");
+        assert_eq!(
+            query,
+            Query::Or(vec![term("beans", 1, 1.0), term("beans", 0, 4.0)])
+        );
+    }
+
+    #[test]
+    fn empty_search_matches_everything() {
+        assert_eq!(parse(""), Query::All);
+        assert_eq!(parse("   "), Query::All);
+    }
+
+    #[test]
+    fn fuzzy_terms_match_misspellings() {
+        let document = given_a_document();
+        assert!(parse("ibuprofin~1").score(&document).is_some());
+        assert!(parse("ibuprofin").score(&document).is_none());
+    }
+
+    #[test]
+    fn exact_matches_score_higher_than_fuzzy_matches() {
+        let document = given_a_document();
+        let exact = parse("(tablets~1 || tablets^4)").score(&document).unwrap();
+        let fuzzy = parse("(tablet~1 || tablet^4)").score(&document).unwrap();
+        assert!(exact > fuzzy);
+    }
+
+    #[test]
+    fn all_clauses_must_match() {
+        let document = given_a_document();
+        assert!(parse("ibuprofen tablets").score(&document).is_some());
+        assert!(parse("ibuprofen capsules").score(&document).is_none());
+    }
+
+    #[test]
+    fn phrases_and_fields_are_respected() {
+        let document = given_a_document();
+        assert!(parse(r#""film-coated tablets""#).score(&document).is_some());
+        assert!(parse(r#""tablets film-coated""#).score(&document).is_none());
+        assert!(parse("pl_number:PL123451234").score(&document).is_some());
+        assert!(parse("title:PL123451234").score(&document).is_none());
+    }
+
+    #[test]
+    fn exclusions_remove_matching_documents() {
+        let document = given_a_document();
+        assert!(parse("ibuprofen -capsules").score(&document).is_some());
+        assert!(parse("ibuprofen -tablets").score(&document).is_none());
+        assert!(parse("ibuprofen NOT tablets").score(&document).is_none());
+    }
+
+    #[test]
+    fn prefix_wildcards_match_the_start_of_words() {
+        let document = given_a_document();
+        assert!(parse("ibupro*").score(&document).is_some());
+        assert!(parse(r"ibupro\*").score(&document).is_none());
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("ibuprofen", "ibuprofen"), 0);
+        assert_eq!(levenshtein("ibuprofin", "ibuprofen"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+    }
+}
@@ -0,0 +1,215 @@
+use super::{analyze, query::levenshtein};
+use crate::{AutocompleteMode, SuggestOptions};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+// The fields covered by the `azure-suggester` of the products index.
+const SUGGESTER_FIELDS: [&str; 4] = ["suggestions", "title", "substance_name", "product_name"];
+
+// Like Azure, every word of the search term has to match and the last one may be partial.
+// Fuzzy matching allows one edit, but only for words long enough for that to be meaningful.
+const MIN_FUZZY_LENGTH: usize = 3;
+
+struct SuggestTerm {
+    words: Vec<String>,
+    prefix: String,
+    fuzzy: bool,
+}
+
+impl SuggestTerm {
+    fn parse(search_term: &str, fuzzy: bool) -> Option<Self> {
+        let mut words = analyze(search_term);
+        let prefix = words.pop()?;
+        Some(Self {
+            words,
+            prefix,
+            fuzzy,
+        })
+    }
+
+    fn matches_word(&self, token: &str, word: &str) -> bool {
+        token == word
+            || (self.fuzzy
+                && word.chars().count() >= MIN_FUZZY_LENGTH
+                && levenshtein(token, word) <= 1)
+    }
+
+    fn matches_prefix(&self, token: &str) -> bool {
+        if token.starts_with(&self.prefix) {
+            return true;
+        }
+        let prefix_length = self.prefix.chars().count();
+        self.fuzzy
+            && prefix_length >= MIN_FUZZY_LENGTH
+            && token.chars().count() >= prefix_length
+            && levenshtein(
+                &token.chars().take(prefix_length).collect::<String>(),
+                &self.prefix,
+            ) <= 1
+    }
+
+    fn matches_any(&self, token: &str) -> bool {
+        self.matches_prefix(token) || self.words.iter().any(|word| self.matches_word(token, word))
+    }
+
+    fn matches(&self, tokens: &[String]) -> bool {
+        self.words
+            .iter()
+            .all(|word| tokens.iter().any(|token| self.matches_word(token, word)))
+            && tokens.iter().any(|token| self.matches_prefix(token))
+    }
+}
+
+pub(crate) fn suggest<'a>(
+    documents: impl Iterator<Item = &'a Map<String, Value>>,
+    key_field: &str,
+    search_term: &str,
+    options: &SuggestOptions,
+) -> Value {
+    let term = match SuggestTerm::parse(search_term, options.fuzzy) {
+        Some(term) => term,
+        None => return json!({ "value": [] }),
+    };
+
+    let value = documents
+        .filter_map(|document| {
+            let text = suggester_texts(document)
+                .into_iter()
+                .find(|text| term.matches(&analyze(text)))?;
+
+            let mut result = Map::new();
+            result.insert(
+                "@search.text".to_string(),
+                json!(highlight(text, &term, options)),
+            );
+            if let Some(key) = document.get(key_field) {
+                result.insert(key_field.to_string(), key.clone());
+            }
+            Some(Value::Object(result))
+        })
+        .take(options.top.max(0) as usize)
+        .collect::<Vec<Value>>();
+
+    json!({ "value": value })
+}
+
+pub(crate) fn autocomplete<'a>(
+    documents: impl Iterator<Item = &'a Map<String, Value>>,
+    search_term: &str,
+    mode: AutocompleteMode,
+    options: &SuggestOptions,
+) -> Value {
+    let term = match SuggestTerm::parse(search_term, options.fuzzy) {
+        Some(term) => term,
+        None => return json!({ "value": [] }),
+    };
+
+    let mut completions: BTreeMap<String, usize> = BTreeMap::new();
+    for document in documents {
+        for text in suggester_texts(document) {
+            let tokens = analyze(text);
+            for i in 0..tokens.len() {
+                if let Some(completion) = complete(&term, &tokens, i, mode) {
+                    *completions.entry(completion).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    let mut completions: Vec<(String, usize)> = completions.into_iter().collect();
+    completions.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    let value = completions
+        .into_iter()
+        .take(options.top.max(0) as usize)
+        .map(|(completion, _)| {
+            let query_plus_text = match mode {
+                AutocompleteMode::OneTermWithContext => completion.clone(),
+                _ => term
+                    .words
+                    .iter()
+                    .chain(std::iter::once(&completion))
+                    .cloned()
+                    .collect::<Vec<String>>()
+                    .join(" "),
+            };
+            json!({
+                "text": wrap(&completion, options),
+                "queryPlusText": query_plus_text,
+            })
+        })
+        .collect::<Vec<Value>>();
+
+    json!({ "value": value })
+}
+
+fn complete(
+    term: &SuggestTerm,
+    tokens: &[String],
+    i: usize,
+    mode: AutocompleteMode,
+) -> Option<String> {
+    let token = &tokens[i];
+    if !term.matches_prefix(token) {
+        return None;
+    }
+
+    match mode {
+        AutocompleteMode::OneTerm => Some(token.clone()),
+        AutocompleteMode::TwoTerms => Some(match tokens.get(i + 1) {
+            Some(next) => format!("{} {}", token, next),
+            None => token.clone(),
+        }),
+        AutocompleteMode::OneTermWithContext => {
+            let start = i.checked_sub(term.words.len())?;
+            let context = &tokens[start..i];
+            if context
+                .iter()
+                .zip(&term.words)
+                .all(|(token, word)| term.matches_word(token, word))
+            {
+                Some(tokens[start..=i].join(" "))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+fn suggester_texts(document: &Map<String, Value>) -> Vec<&str> {
+    SUGGESTER_FIELDS
+        .iter()
+        .filter_map(|field| document.get(*field))
+        .flat_map(|value| match value {
+            Value::String(text) => vec![text.as_str()],
+            Value::Array(items) => items.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        })
+        .collect()
+}
+
+fn highlight(text: &str, term: &SuggestTerm, options: &SuggestOptions) -> String {
+    if options.highlight_pre_tag.is_none() && options.highlight_post_tag.is_none() {
+        return text.to_string();
+    }
+
+    text.split_whitespace()
+        .map(|word| {
+            if analyze(word).iter().any(|token| term.matches_any(token)) {
+                wrap(word, options)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn wrap(text: &str, options: &SuggestOptions) -> String {
+    format!(
+        "{}{}{}",
+        options.highlight_pre_tag.as_deref().unwrap_or_default(),
+        text,
+        options.highlight_post_tag.as_deref().unwrap_or_default()
+    )
+}
@@ -1,35 +1,83 @@
+mod batch;
+mod builder;
 mod document_type;
+mod error;
+pub mod facet;
+pub mod filter;
+mod in_memory;
 pub mod models;
-mod query_normalizer;
+mod product_licence;
+pub mod query_normalizer;
+mod retry;
+pub mod sort;
+mod spelling;
+mod stream;
+mod synonyms;
 mod territory_type;
 
 #[macro_use]
 extern crate lazy_static;
 
-use crate::models::{AzureIndexChangedResults, FacetResults, IndexEntry};
-use crate::query_normalizer::{
-    escape_special_characters, escape_special_words, normalize_product_licences,
-    prefer_exact_match_but_support_fuzzy_match,
+use crate::batch::index_in_batches;
+pub use crate::builder::{AzureSearchClientBuilder, BuildError};
+pub use crate::error::{AzureError, SearchError, SearchErrorKind};
+use crate::facet::{FacetRequest, FacetSort};
+use crate::filter::{Filter, Operator};
+pub use crate::in_memory::InMemorySearchClient;
+use crate::models::{
+    AutocompleteResults, AzureIndexChangedResults, FacetCounts, FacetResults, IndexEntry,
+    IndexEntryAction, IndexResult, IndexResults, SuggestResults,
 };
+use crate::query_normalizer::{normalize_product_licences, parse_query, to_lucene_query};
+use crate::retry::{parse_retry_after, with_retries};
+pub use crate::retry::{CircuitBreaker, RetryPolicy};
+use crate::sort::{to_order_by_expression, OrderBy, SortDirection};
+pub use crate::spelling::Vocabulary;
+use crate::spelling::{VocabularyCache, VocabularyFacetResults, VOCABULARY_FACETS};
+pub use crate::stream::PAGE_SIZE as STREAM_PAGE_SIZE;
+use crate::stream::{keyset_filter, stream_documents, KeysetPages, KEY_FIELD, PAGE_SIZE};
+pub use crate::synonyms::Synonyms;
 use async_trait::async_trait;
 use core::fmt::Debug;
+use futures::stream::BoxStream;
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
+use serde_derive::Deserialize;
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 
 #[derive(Clone)]
 struct AzureConfig {
-    search_service: String,
+    base_url: String,
     search_index: String,
-    api_key: String,
+    query_key: String,
+    admin_key: Option<String>,
     api_version: String,
     search_fuzziness: String,
     search_exactness_boost: String,
+    scoring_profile: Option<String>,
+    searchable_fields: Vec<String>,
+    synonyms: Synonyms,
+}
+
+impl AzureConfig {
+    fn docs_url(&self, path: &str) -> String {
+        format!(
+            "{base_url}/indexes/{search_index}/docs{path}",
+            base_url = self.base_url,
+            search_index = self.search_index,
+            path = path
+        )
+    }
 }
 
 pub struct AzureSearchClient {
     client: reqwest::Client,
     config: AzureConfig,
+    retry_policy: RetryPolicy,
+    circuit_breaker: Arc<CircuitBreaker>,
+    vocabulary_cache: Arc<VocabularyCache>,
 }
 
 pub struct AzurePagination {
@@ -37,6 +85,47 @@ pub struct AzurePagination {
     pub offset: i32,
 }
 
+// The suggester declared in the [index definition](../../search/definitions/indexes/default.json).
+const SUGGESTER_NAME: &str = "azure-suggester";
+
+// Documents are also looked up by their content id, which isn't the key of the index.
+pub(crate) const CONTENT_ID_FIELD: &str = "file_name";
+
+pub struct SuggestOptions {
+    pub fuzzy: bool,
+    pub highlight_pre_tag: Option<String>,
+    pub highlight_post_tag: Option<String>,
+    pub top: i32,
+}
+
+impl Default for SuggestOptions {
+    fn default() -> Self {
+        Self {
+            fuzzy: false,
+            highlight_pre_tag: None,
+            highlight_post_tag: None,
+            top: 5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutocompleteMode {
+    OneTerm,
+    TwoTerms,
+    OneTermWithContext,
+}
+
+impl Display for AutocompleteMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AutocompleteMode::OneTerm => write!(f, "oneTerm"),
+            AutocompleteMode::TwoTerms => write!(f, "twoTerms"),
+            AutocompleteMode::OneTermWithContext => write!(f, "oneTermWithContext"),
+        }
+    }
+}
+
 impl Default for AzureSearchClient {
     fn default() -> Self {
         Self::new()
@@ -44,35 +133,20 @@ impl Default for AzureSearchClient {
 }
 
 impl AzureSearchClient {
-    // Unfortunately this method is required by the API project.
-    // We can't rely on the `factory` below which returns `impl Search + ...` because we need a concrete type for the [juniper context](../../api/src/schema.rs:11).
-    //
-    // There might be a way around this but I think it would probably take a fair bit of effort so in the interests of time we're just having `AzureContext` depend on `AzureSearchClient` directly in api/src/azure_search.rs.
     pub fn new() -> Self {
         let default_index = get_env("AZURE_SEARCH_INDEX");
         AzureSearchClient::new_with_index(default_index)
     }
 
+    // Panics if the env variables don't configure a valid client, use `builder` to handle that instead.
     pub fn new_with_index(index: String) -> Self {
-        let api_key = get_env("AZURE_API_ADMIN_KEY");
-        let search_index = index;
-        let search_service = get_env("SEARCH_SERVICE");
-        let api_version = get_env("AZURE_SEARCH_API_VERSION");
-
-        let search_fuzziness = get_env_or_default("AZURE_SEARCH_WORD_FUZZINESS", "1");
-        let search_exactness_boost = get_env_or_default("AZURE_SEARCH_EXACTNESS_BOOST", "4");
-
-        AzureSearchClient {
-            client: reqwest::Client::new(),
-            config: AzureConfig {
-                api_key,
-                search_index,
-                search_service,
-                api_version,
-                search_fuzziness,
-                search_exactness_boost,
-            },
-        }
+        AzureSearchClientBuilder::from_env()
+            .and_then(|builder| builder.index(index).build())
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn builder() -> AzureSearchClientBuilder {
+        AzureSearchClientBuilder::new()
     }
 }
 
@@ -88,12 +162,47 @@ pub fn get_env_or_default(key: &str, default: &str) -> String {
 }
 
 pub fn factory() -> impl Search + DeleteIndexEntry + CreateIndexEntry {
-    AzureSearchClient::new()
+    SearchClient::new()
+}
+
+// Unfortunately this type is required by the API project.
+// We can't rely on the `factory` above which returns `impl Search + ...` because we need a concrete type for the [graphql context](../../api/src/azure_context.rs).
+//
+// The backend is chosen with the `SEARCH_BACKEND` env variable: `azure` (the default) or `in-memory`,
+// which serves the index from memory (optionally seeded from `IN_MEMORY_SEARCH_DATA_DIR/{index}.json`)
+// so that the API and doc-index-updater can be run without an Azure Search service.
+pub enum SearchClient {
+    Azure(AzureSearchClient),
+    InMemory(InMemorySearchClient),
+}
+
+impl Default for SearchClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SearchClient {
+    pub fn new() -> Self {
+        let default_index = get_env("AZURE_SEARCH_INDEX");
+        SearchClient::new_with_index(default_index)
+    }
+
+    pub fn new_with_index(index: String) -> Self {
+        match get_env_or_default("SEARCH_BACKEND", "azure").as_str() {
+            "azure" => SearchClient::Azure(AzureSearchClient::new_with_index(index)),
+            "in-memory" => SearchClient::InMemory(InMemorySearchClient::new_with_index(index)),
+            backend => panic!(
+                "Unknown SEARCH_BACKEND {}, expected azure or in-memory",
+                backend
+            ),
+        }
+    }
 }
 
 #[async_trait]
 pub trait Search {
-    async fn search<T>(&self, search_term: &str) -> Result<T, reqwest::Error>
+    async fn search<T>(&self, search_term: &str) -> Result<T, SearchError>
     where
         T: DeserializeOwned;
 
@@ -102,7 +211,7 @@ pub trait Search {
         search_term: &str,
         pagination: AzurePagination,
         include_count: bool,
-    ) -> Result<T, reqwest::Error>
+    ) -> Result<T, SearchError>
     where
         T: DeserializeOwned;
 
@@ -111,8 +220,9 @@ pub trait Search {
         search_term: &str,
         pagination: AzurePagination,
         include_count: bool,
-        filter: Option<&str>,
-    ) -> Result<T, reqwest::Error>
+        filter: Option<&Filter>,
+        order_by: &[OrderBy],
+    ) -> Result<T, SearchError>
     where
         T: DeserializeOwned;
 
@@ -120,13 +230,21 @@ pub trait Search {
         &self,
         field_name: &str,
         field_value: &str,
-    ) -> Result<FacetResults, reqwest::Error>;
+    ) -> Result<FacetResults, SearchError>;
+
+    // Counts the documents matching the search and filter by the values of each of the facets.
+    async fn search_facets(
+        &self,
+        search_term: &str,
+        filter: Option<&Filter>,
+        facets: &[FacetRequest],
+    ) -> Result<FacetCounts, SearchError>;
 
     async fn filter_by_collection_field<T>(
         &self,
         field_name: &str,
         field_value: &str,
-    ) -> Result<T, reqwest::Error>
+    ) -> Result<T, SearchError>
     where
         T: DeserializeOwned;
 
@@ -134,18 +252,50 @@ pub trait Search {
         &self,
         field_name: &str,
         field_value: &str,
-    ) -> Result<T, reqwest::Error>
+    ) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned;
+
+    // Looks the document up by its key only.
+    async fn lookup_document<T>(&self, key: &str) -> Result<Option<T>, SearchError>
     where
         T: DeserializeOwned;
+
+    // Looks the document up by its key, or failing that by its content id.
+    async fn get_document<T>(&self, key: &str) -> Result<Option<T>, SearchError>
+    where
+        T: DeserializeOwned;
+
+    async fn suggest(
+        &self,
+        search_term: &str,
+        options: &SuggestOptions,
+    ) -> Result<SuggestResults, SearchError>;
+
+    async fn autocomplete(
+        &self,
+        search_term: &str,
+        mode: AutocompleteMode,
+        options: &SuggestOptions,
+    ) -> Result<AutocompleteResults, SearchError>;
+
+    async fn did_you_mean(&self, search_term: &str, top: usize)
+        -> Result<Vec<String>, SearchError>;
+
+    // Walks every document of the index matching the filter, e.g. for exports and reconciliation.
+    fn stream_documents(
+        &self,
+        filter: Option<Filter>,
+    ) -> BoxStream<'_, Result<IndexResult, SearchError>>;
 }
 
 #[async_trait]
 impl Search for AzureSearchClient {
-    async fn search<T>(&self, search_term: &str) -> Result<T, reqwest::Error>
+    async fn search<T>(&self, search_term: &str) -> Result<T, SearchError>
     where
         T: DeserializeOwned,
     {
-        search::<T>(search_term, None, None, None, &self.client, &self.config).await
+        search::<T>(search_term, None, None, None, &[], self).await
     }
 
     async fn search_with_pagination<T>(
@@ -153,7 +303,7 @@ impl Search for AzureSearchClient {
         search_term: &str,
         pagination: AzurePagination,
         include_count: bool,
-    ) -> Result<T, reqwest::Error>
+    ) -> Result<T, SearchError>
     where
         T: DeserializeOwned,
     {
@@ -162,8 +312,8 @@ impl Search for AzureSearchClient {
             Some(pagination),
             Some(include_count),
             None,
-            &self.client,
-            &self.config,
+            &[],
+            self,
         )
         .await
     }
@@ -173,8 +323,9 @@ impl Search for AzureSearchClient {
         search_term: &str,
         pagination: AzurePagination,
         include_count: bool,
-        filter: Option<&str>,
-    ) -> Result<T, reqwest::Error>
+        filter: Option<&Filter>,
+        order_by: &[OrderBy],
+    ) -> Result<T, SearchError>
     where
         T: DeserializeOwned,
     {
@@ -183,8 +334,8 @@ impl Search for AzureSearchClient {
             Some(pagination),
             Some(include_count),
             filter,
-            &self.client,
-            &self.config,
+            order_by,
+            self,
         )
         .await
     }
@@ -193,113 +344,164 @@ impl Search for AzureSearchClient {
         &self,
         field_name: &str,
         field_value: &str,
-    ) -> Result<FacetResults, reqwest::Error> {
+    ) -> Result<FacetResults, SearchError> {
+        let (filter, facet) = facet_field_search(field_name, field_value);
+        let request = build_facet_search(&filter, &facet, &self.client, &self.config)?;
+
+        self.execute(request).await
+    }
+
+    async fn search_facets(
+        &self,
+        search_term: &str,
+        filter: Option<&Filter>,
+        facets: &[FacetRequest],
+    ) -> Result<FacetCounts, SearchError> {
         let request =
-            build_facet_search(field_name, field_value, "eq", &self.client, &self.config)?;
+            build_facets_request(search_term, filter, facets, &self.client, &self.config)?;
 
-        tracing::debug!("Requesting from URL: {}", &request.url());
-        self.client
-            .execute(request)
-            .await?
-            .error_for_status()?
-            .json::<FacetResults>()
-            .await
+        self.execute(request).await
     }
 
     async fn filter_by_collection_field<T>(
         &self,
         field_name: &str,
         field_value: &str,
-    ) -> Result<T, reqwest::Error>
+    ) -> Result<T, SearchError>
     where
         T: DeserializeOwned,
     {
-        let request = build_filter_by_collection_request(
-            field_name,
-            field_value,
-            "eq",
-            &self.client,
-            &self.config,
-        )?;
+        let filter = Filter::any(field_name, Operator::Eq, field_value);
+        let request = build_filter_request(&filter, &self.client, &self.config)?;
 
-        tracing::debug!("Requesting from URL: {}", &request.url());
-        self.client
-            .execute(request)
-            .await?
-            .error_for_status()?
-            .json::<T>()
-            .await
+        self.execute(request).await
     }
 
     async fn filter_by_non_collection_field<T>(
         &self,
         field_name: &str,
         field_value: &str,
-    ) -> Result<T, reqwest::Error>
+    ) -> Result<T, SearchError>
     where
         T: DeserializeOwned,
     {
-        let request = build_filter_by_field_request(
-            field_name,
-            field_value,
-            "eq",
-            &self.client,
-            &self.config,
-        )?;
-
-        self.client
-            .execute(request)
-            .await?
-            .error_for_status()?
-            .json::<T>()
-            .await
+        let filter = Filter::eq(field_name, field_value);
+        let request = build_filter_request(&filter, &self.client, &self.config)?;
+
+        self.execute(request).await
+    }
+
+    async fn lookup_document<T>(&self, key: &str) -> Result<Option<T>, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        let request = build_lookup_request(key, &self.client, &self.config)?;
+        match self.execute(request).await {
+            Ok(document) => Ok(Some(document)),
+            Err(e) if e.kind() == SearchErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn get_document<T>(&self, key: &str) -> Result<Option<T>, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        if let Some(document) = self.lookup_document(key).await? {
+            return Ok(Some(document));
+        }
+
+        let filter = Filter::eq(CONTENT_ID_FIELD, key);
+        let request = build_filter_request(&filter, &self.client, &self.config)?;
+        let results: DocumentResults<T> = self.execute(request).await?;
+
+        Ok(results.value.into_iter().next())
+    }
+
+    async fn suggest(
+        &self,
+        search_term: &str,
+        options: &SuggestOptions,
+    ) -> Result<SuggestResults, SearchError> {
+        let request = build_suggest_request(search_term, options, &self.client, &self.config)?;
+
+        self.execute(request).await
+    }
+
+    async fn autocomplete(
+        &self,
+        search_term: &str,
+        mode: AutocompleteMode,
+        options: &SuggestOptions,
+    ) -> Result<AutocompleteResults, SearchError> {
+        let request =
+            build_autocomplete_request(search_term, mode, options, &self.client, &self.config)?;
+
+        self.execute(request).await
+    }
+
+    async fn did_you_mean(
+        &self,
+        search_term: &str,
+        top: usize,
+    ) -> Result<Vec<String>, SearchError> {
+        let vocabulary = self.get_vocabulary().await?;
+
+        Ok(vocabulary.did_you_mean(search_term, top))
+    }
+
+    fn stream_documents(
+        &self,
+        filter: Option<Filter>,
+    ) -> BoxStream<'_, Result<IndexResult, SearchError>> {
+        stream_documents(self, filter, PAGE_SIZE)
     }
 }
 
-fn clean_up_search_term(search_term: &str) -> String {
-    let search_term = normalize_product_licences(&search_term);
-    let search_term = escape_special_characters(&search_term);
-    escape_special_words(&search_term)
+#[async_trait]
+impl KeysetPages for AzureSearchClient {
+    async fn page_after(
+        &self,
+        after: Option<&str>,
+        filter: Option<&Filter>,
+        top: usize,
+    ) -> Result<Vec<IndexResult>, SearchError> {
+        let request = build_keyset_request(after, filter, top, &self.client, &self.config)?;
+        let results: IndexResults = self.execute(request).await?;
+
+        Ok(results.search_results)
+    }
 }
 
-fn add_fuzzy_search(
+pub(crate) fn build_search_term(
     search_term: &str,
+    searchable_fields: Option<&[String]>,
+    synonyms: &Synonyms,
     search_fuzziness: &str,
     search_exactness_boost: &str,
 ) -> String {
-    search_term
-        .split(' ')
-        .filter(|word| !word.is_empty())
-        .map(|word| {
-            prefer_exact_match_but_support_fuzzy_match(
-                word,
-                search_fuzziness,
-                search_exactness_boost,
-            )
-        })
-        .collect::<Vec<String>>()
-        .join(" ")
+    let search_term = normalize_product_licences(search_term);
+    let clauses = parse_query(&search_term, searchable_fields);
+    to_lucene_query(&clauses, synonyms, search_fuzziness, search_exactness_boost)
 }
 
 fn build_search(
     search_term: &str,
     pagination: Option<AzurePagination>,
     include_count: Option<bool>,
-    filter: Option<&str>,
+    filter: Option<&Filter>,
+    order_by: &[OrderBy],
     client: &reqwest::Client,
     config: &AzureConfig,
 ) -> Result<reqwest::Request, reqwest::Error> {
-    let base_url = format!(
-        "https://{search_service}.search.windows.net/indexes/{search_index}/docs",
-        search_service = config.search_service,
-        search_index = config.search_index
-    );
+    let base_url = config.docs_url("");
 
     let include_count = include_count.unwrap_or(false).to_string();
 
-    let search_term = clean_up_search_term(search_term);
-    let search_term = add_fuzzy_search(
-        &search_term,
+    let search_term = build_search_term(
+        search_term,
+        Some(&config.searchable_fields),
+        &config.synonyms,
         &config.search_fuzziness,
         &config.search_exactness_boost,
     );
@@ -311,14 +513,20 @@ fn build_search(
             ("highlight", "content"),
             ("queryType", "full"),
             ("search", &search_term),
-            ("scoringProfile", "preferKeywords"),
-            ("searchMode", "all"),
-            ("$count", &include_count),
         ])
-        .header("api-key", &config.api_key);
+        .header("api-key", &config.query_key);
+
+    if let Some(scoring_profile) = &config.scoring_profile {
+        request_builder = request_builder.query(&[("scoringProfile", scoring_profile)]);
+    }
+    request_builder = request_builder.query(&[("searchMode", "all"), ("$count", &include_count)]);
 
     if let Some(filter) = filter {
-        request_builder = request_builder.query(&[("$filter", filter)]);
+        request_builder = request_builder.query(&[("$filter", filter.to_string())]);
+    }
+
+    if let Some(order_by) = to_order_by_expression(order_by) {
+        request_builder = request_builder.query(&[("$orderby", order_by)]);
     }
 
     match pagination {
@@ -332,90 +540,210 @@ fn build_search(
     }
 }
 
-fn build_filter_by_collection_request(
-    field_name: &str,
-    value: &str,
-    operator: &str,
+#[derive(Deserialize)]
+struct DocumentResults<T> {
+    value: Vec<T>,
+}
+
+fn build_lookup_request(
+    key: &str,
     client: &reqwest::Client,
     config: &AzureConfig,
-) -> Result<reqwest::Request, reqwest::Error> {
-    let base_url = format!(
-        "https://{search_service}.search.windows.net/indexes/{search_index}/docs",
-        search_service = config.search_service,
-        search_index = config.search_index
-    );
+) -> Result<reqwest::Request, SearchError> {
+    let mut url = reqwest::Url::parse(&config.docs_url("")).map_err(anyhow::Error::from)?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow::anyhow!("The index URL can't have a document key added to it"))?
+        .push(key);
 
-    let filter = format!(
-        "{field_name}/any(f: f {operator} '{value}')",
-        field_name = field_name,
-        value = value,
-        operator = operator,
-    );
+    client
+        .get(url)
+        .query(&[("api-version", &config.api_version)])
+        .header("api-key", &config.query_key)
+        .build()
+        .map_err(Into::into)
+}
+
+fn build_filter_request(
+    filter: &Filter,
+    client: &reqwest::Client,
+    config: &AzureConfig,
+) -> Result<reqwest::Request, reqwest::Error> {
+    let base_url = config.docs_url("");
 
     client
         .get(&base_url)
-        .query(&[("api-version", &config.api_version), ("$filter", &filter)])
-        .header("api-key", &config.api_key)
+        .query(&[
+            ("api-version", &config.api_version),
+            ("$filter", &filter.to_string()),
+        ])
+        .header("api-key", &config.query_key)
         .build()
 }
 
-fn build_facet_search(
-    field_name: &str,
-    value: &str,
-    operator: &str,
+fn build_keyset_request(
+    after: Option<&str>,
+    filter: Option<&Filter>,
+    top: usize,
     client: &reqwest::Client,
     config: &AzureConfig,
 ) -> Result<reqwest::Request, reqwest::Error> {
-    let base_url = format!(
-        "https://{search_service}.search.windows.net/indexes/{search_index}/docs",
-        search_service = config.search_service,
-        search_index = config.search_index
-    );
+    let base_url = config.docs_url("");
 
-    let filter = format!(
-        "{field_name}/any(f: f {operator} '{value}')",
-        field_name = field_name,
-        value = value,
-        operator = operator,
-    );
+    let mut request_builder = client
+        .get(&base_url)
+        .query(&[
+            ("api-version", config.api_version.clone()),
+            ("search", "*".to_string()),
+            ("$orderby", OrderBy::asc(KEY_FIELD).to_string()),
+            ("$top", top.to_string()),
+        ])
+        .header("api-key", &config.query_key);
+
+    if let Some(filter) = keyset_filter(after, filter) {
+        request_builder = request_builder.query(&[("$filter", filter.to_string())]);
+    }
+
+    request_builder.build()
+}
+
+// What `search_by_facet_field` asks for: the documents with the value in the collection field,
+// and the values of their `facets`, whichever field they were found by.
+// Shared with the in-memory client, so that the two ask for the same thing.
+fn facet_field_search(field_name: &str, field_value: &str) -> (Filter, FacetRequest) {
+    (
+        Filter::any(field_name, Operator::Eq, field_value),
+        FacetRequest::new("facets")
+            .count(50000)
+            .sort(FacetSort::Value(SortDirection::Asc)),
+    )
+}
+
+fn build_facet_search(
+    filter: &Filter,
+    facet: &FacetRequest,
+    client: &reqwest::Client,
+    config: &AzureConfig,
+) -> Result<reqwest::Request, reqwest::Error> {
+    let base_url = config.docs_url("");
 
     client
         .get(&base_url)
         .query(&[
             ("api-version", &config.api_version),
-            ("$filter", &filter),
-            ("facet", &String::from("facets,count:50000,sort:value")),
+            ("$filter", &filter.to_string()),
+            ("facet", &facet.to_string()),
             ("$top", &String::from("0")),
         ])
-        .header("api-key", &config.api_key)
+        .header("api-key", &config.query_key)
         .build()
 }
 
-fn build_filter_by_field_request(
-    field_name: &str,
-    value: &str,
-    operator: &str,
+// Searches without returning any documents, counting all of the matches and their facets.
+fn build_facets_request(
+    search_term: &str,
+    filter: Option<&Filter>,
+    facets: &[FacetRequest],
     client: &reqwest::Client,
     config: &AzureConfig,
 ) -> Result<reqwest::Request, reqwest::Error> {
-    let base_url = format!(
-        "https://{search_service}.search.windows.net/indexes/{search_index}/docs",
-        search_service = config.search_service,
-        search_index = config.search_index
-    );
+    let pagination = AzurePagination {
+        result_count: 0,
+        offset: 0,
+    };
+    let mut request = build_search(
+        search_term,
+        Some(pagination),
+        Some(true),
+        filter,
+        &[],
+        client,
+        config,
+    )?;
 
-    let filter = format!(
-        "{field_name} {operator} '{value}'",
-        field_name = field_name,
-        value = value,
-        operator = operator,
-    );
+    request
+        .url_mut()
+        .query_pairs_mut()
+        .extend_pairs(facets.iter().map(|facet| ("facet", facet.to_string())));
 
-    client
+    Ok(request)
+}
+
+fn build_suggest_request(
+    search_term: &str,
+    options: &SuggestOptions,
+    client: &reqwest::Client,
+    config: &AzureConfig,
+) -> Result<reqwest::Request, reqwest::Error> {
+    let base_url = config.docs_url("/suggest");
+
+    let request_builder = client
         .get(&base_url)
-        .query(&[("api-version", &config.api_version), ("$filter", &filter)])
-        .header("api-key", &config.api_key)
-        .build()
+        .query(&[
+            ("api-version", config.api_version.as_str()),
+            ("search", search_term),
+            ("suggesterName", SUGGESTER_NAME),
+        ])
+        .header("api-key", &config.query_key);
+
+    add_suggest_options(request_builder, options).build()
+}
+
+fn build_autocomplete_request(
+    search_term: &str,
+    mode: AutocompleteMode,
+    options: &SuggestOptions,
+    client: &reqwest::Client,
+    config: &AzureConfig,
+) -> Result<reqwest::Request, reqwest::Error> {
+    let base_url = config.docs_url("/autocomplete");
+
+    let request_builder = client
+        .get(&base_url)
+        .query(&[
+            ("api-version", config.api_version.as_str()),
+            ("search", search_term),
+            ("suggesterName", SUGGESTER_NAME),
+            ("autocompleteMode", &mode.to_string()),
+        ])
+        .header("api-key", &config.query_key);
+
+    add_suggest_options(request_builder, options).build()
+}
+
+fn build_vocabulary_request(
+    client: &reqwest::Client,
+    config: &AzureConfig,
+) -> Result<reqwest::Request, reqwest::Error> {
+    let base_url = config.docs_url("");
+
+    let mut request_builder = client
+        .get(&base_url)
+        .query(&[("api-version", config.api_version.as_str()), ("$top", "0")])
+        .header("api-key", &config.query_key);
+    for field in VOCABULARY_FACETS {
+        request_builder = request_builder.query(&[("facet", format!("{},count:50000", field))]);
+    }
+
+    request_builder.build()
+}
+
+fn add_suggest_options(
+    mut request_builder: reqwest::RequestBuilder,
+    options: &SuggestOptions,
+) -> reqwest::RequestBuilder {
+    request_builder = request_builder.query(&[
+        ("fuzzy", options.fuzzy.to_string()),
+        ("$top", options.top.to_string()),
+    ]);
+
+    if let Some(highlight_pre_tag) = &options.highlight_pre_tag {
+        request_builder = request_builder.query(&[("highlightPreTag", highlight_pre_tag)]);
+    }
+    if let Some(highlight_post_tag) = &options.highlight_post_tag {
+        request_builder = request_builder.query(&[("highlightPostTag", highlight_post_tag)]);
+    }
+
+    request_builder
 }
 
 #[async_trait]
@@ -424,7 +752,14 @@ pub trait DeleteIndexEntry {
         &self,
         key_name: &str,
         value: &str,
-    ) -> Result<AzureIndexChangedResults, anyhow::Error>;
+    ) -> Result<AzureIndexChangedResults, SearchError>;
+
+    // Deletes the entries with the given keys, in as few requests as possible.
+    async fn delete_index_entries(
+        &self,
+        key_name: &str,
+        values: &[String],
+    ) -> Result<AzureIndexChangedResults, SearchError>;
 }
 
 #[async_trait]
@@ -433,12 +768,30 @@ impl DeleteIndexEntry for AzureSearchClient {
         &self,
         key_name: &str,
         value: &str,
-    ) -> Result<AzureIndexChangedResults, anyhow::Error> {
+    ) -> Result<AzureIndexChangedResults, SearchError> {
         let mut key_values = HashMap::new();
         key_values.insert(key_name, value);
         key_values.insert("@search.action", "delete");
 
-        update_index(key_values, &self.client, &self.config).await
+        update_index(key_values, self).await
+    }
+
+    async fn delete_index_entries(
+        &self,
+        key_name: &str,
+        values: &[String],
+    ) -> Result<AzureIndexChangedResults, SearchError> {
+        let documents = values
+            .iter()
+            .map(|value| {
+                let mut key_values = serde_json::Map::new();
+                key_values.insert(key_name.to_string(), value.clone().into());
+                key_values.insert("@search.action".to_string(), "delete".into());
+                serde_json::Value::Object(key_values)
+            })
+            .collect();
+
+        self.update_index_in_batches(documents, key_name).await
     }
 }
 
@@ -447,7 +800,14 @@ pub trait CreateIndexEntry {
     async fn create_index_entry(
         &self,
         key_values: IndexEntry,
-    ) -> Result<AzureIndexChangedResults, anyhow::Error>;
+    ) -> Result<AzureIndexChangedResults, SearchError>;
+
+    // Applies the actions in as few requests as possible, reporting the result for every entry.
+    // Entries that Azure couldn't index temporarily are retried.
+    async fn create_index_entries(
+        &self,
+        actions: Vec<IndexEntryAction>,
+    ) -> Result<AzureIndexChangedResults, SearchError>;
 }
 
 #[async_trait]
@@ -455,8 +815,279 @@ impl CreateIndexEntry for AzureSearchClient {
     async fn create_index_entry(
         &self,
         key_values: IndexEntry,
-    ) -> Result<AzureIndexChangedResults, anyhow::Error> {
-        update_index(key_values, &self.client, &self.config).await
+    ) -> Result<AzureIndexChangedResults, SearchError> {
+        update_index(key_values, self).await
+    }
+
+    async fn create_index_entries(
+        &self,
+        actions: Vec<IndexEntryAction>,
+    ) -> Result<AzureIndexChangedResults, SearchError> {
+        let documents = actions
+            .into_iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<serde_json::Value>, serde_json::Error>>()?;
+
+        self.update_index_in_batches(documents, "metadata_storage_name")
+            .await
+    }
+}
+
+#[async_trait]
+impl Search for SearchClient {
+    async fn search<T>(&self, search_term: &str) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            SearchClient::Azure(client) => client.search(search_term).await,
+            SearchClient::InMemory(client) => client.search(search_term).await,
+        }
+    }
+
+    async fn search_with_pagination<T>(
+        &self,
+        search_term: &str,
+        pagination: AzurePagination,
+        include_count: bool,
+    ) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            SearchClient::Azure(client) => {
+                client
+                    .search_with_pagination(search_term, pagination, include_count)
+                    .await
+            }
+            SearchClient::InMemory(client) => {
+                client
+                    .search_with_pagination(search_term, pagination, include_count)
+                    .await
+            }
+        }
+    }
+
+    async fn search_with_pagination_and_filter<T>(
+        &self,
+        search_term: &str,
+        pagination: AzurePagination,
+        include_count: bool,
+        filter: Option<&Filter>,
+        order_by: &[OrderBy],
+    ) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            SearchClient::Azure(client) => {
+                client
+                    .search_with_pagination_and_filter(
+                        search_term,
+                        pagination,
+                        include_count,
+                        filter,
+                        order_by,
+                    )
+                    .await
+            }
+            SearchClient::InMemory(client) => {
+                client
+                    .search_with_pagination_and_filter(
+                        search_term,
+                        pagination,
+                        include_count,
+                        filter,
+                        order_by,
+                    )
+                    .await
+            }
+        }
+    }
+
+    async fn search_by_facet_field(
+        &self,
+        field_name: &str,
+        field_value: &str,
+    ) -> Result<FacetResults, SearchError> {
+        match self {
+            SearchClient::Azure(client) => {
+                client.search_by_facet_field(field_name, field_value).await
+            }
+            SearchClient::InMemory(client) => {
+                client.search_by_facet_field(field_name, field_value).await
+            }
+        }
+    }
+
+    async fn search_facets(
+        &self,
+        search_term: &str,
+        filter: Option<&Filter>,
+        facets: &[FacetRequest],
+    ) -> Result<FacetCounts, SearchError> {
+        match self {
+            SearchClient::Azure(client) => client.search_facets(search_term, filter, facets).await,
+            SearchClient::InMemory(client) => {
+                client.search_facets(search_term, filter, facets).await
+            }
+        }
+    }
+
+    async fn filter_by_collection_field<T>(
+        &self,
+        field_name: &str,
+        field_value: &str,
+    ) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            SearchClient::Azure(client) => {
+                client
+                    .filter_by_collection_field(field_name, field_value)
+                    .await
+            }
+            SearchClient::InMemory(client) => {
+                client
+                    .filter_by_collection_field(field_name, field_value)
+                    .await
+            }
+        }
+    }
+
+    async fn filter_by_non_collection_field<T>(
+        &self,
+        field_name: &str,
+        field_value: &str,
+    ) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            SearchClient::Azure(client) => {
+                client
+                    .filter_by_non_collection_field(field_name, field_value)
+                    .await
+            }
+            SearchClient::InMemory(client) => {
+                client
+                    .filter_by_non_collection_field(field_name, field_value)
+                    .await
+            }
+        }
+    }
+
+    async fn lookup_document<T>(&self, key: &str) -> Result<Option<T>, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            SearchClient::Azure(client) => client.lookup_document(key).await,
+            SearchClient::InMemory(client) => client.lookup_document(key).await,
+        }
+    }
+
+    async fn get_document<T>(&self, key: &str) -> Result<Option<T>, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            SearchClient::Azure(client) => client.get_document(key).await,
+            SearchClient::InMemory(client) => client.get_document(key).await,
+        }
+    }
+
+    async fn suggest(
+        &self,
+        search_term: &str,
+        options: &SuggestOptions,
+    ) -> Result<SuggestResults, SearchError> {
+        match self {
+            SearchClient::Azure(client) => client.suggest(search_term, options).await,
+            SearchClient::InMemory(client) => client.suggest(search_term, options).await,
+        }
+    }
+
+    async fn autocomplete(
+        &self,
+        search_term: &str,
+        mode: AutocompleteMode,
+        options: &SuggestOptions,
+    ) -> Result<AutocompleteResults, SearchError> {
+        match self {
+            SearchClient::Azure(client) => client.autocomplete(search_term, mode, options).await,
+            SearchClient::InMemory(client) => client.autocomplete(search_term, mode, options).await,
+        }
+    }
+
+    async fn did_you_mean(
+        &self,
+        search_term: &str,
+        top: usize,
+    ) -> Result<Vec<String>, SearchError> {
+        match self {
+            SearchClient::Azure(client) => client.did_you_mean(search_term, top).await,
+            SearchClient::InMemory(client) => client.did_you_mean(search_term, top).await,
+        }
+    }
+
+    fn stream_documents(
+        &self,
+        filter: Option<Filter>,
+    ) -> BoxStream<'_, Result<IndexResult, SearchError>> {
+        match self {
+            SearchClient::Azure(client) => client.stream_documents(filter),
+            SearchClient::InMemory(client) => client.stream_documents(filter),
+        }
+    }
+}
+
+#[async_trait]
+impl DeleteIndexEntry for SearchClient {
+    async fn delete_index_entry(
+        &self,
+        key_name: &str,
+        value: &str,
+    ) -> Result<AzureIndexChangedResults, SearchError> {
+        match self {
+            SearchClient::Azure(client) => client.delete_index_entry(key_name, value).await,
+            SearchClient::InMemory(client) => client.delete_index_entry(key_name, value).await,
+        }
+    }
+
+    async fn delete_index_entries(
+        &self,
+        key_name: &str,
+        values: &[String],
+    ) -> Result<AzureIndexChangedResults, SearchError> {
+        match self {
+            SearchClient::Azure(client) => client.delete_index_entries(key_name, values).await,
+            SearchClient::InMemory(client) => client.delete_index_entries(key_name, values).await,
+        }
+    }
+}
+
+#[async_trait]
+impl CreateIndexEntry for SearchClient {
+    async fn create_index_entry(
+        &self,
+        key_values: IndexEntry,
+    ) -> Result<AzureIndexChangedResults, SearchError> {
+        match self {
+            SearchClient::Azure(client) => client.create_index_entry(key_values).await,
+            SearchClient::InMemory(client) => client.create_index_entry(key_values).await,
+        }
+    }
+
+    async fn create_index_entries(
+        &self,
+        actions: Vec<IndexEntryAction>,
+    ) -> Result<AzureIndexChangedResults, SearchError> {
+        match self {
+            SearchClient::Azure(client) => client.create_index_entries(actions).await,
+            SearchClient::InMemory(client) => client.create_index_entries(actions).await,
+        }
     }
 }
 
@@ -464,10 +1095,10 @@ async fn search<T>(
     search_term: &str,
     pagination: Option<AzurePagination>,
     include_count: Option<bool>,
-    filter: Option<&str>,
-    client: &reqwest::Client,
-    config: &AzureConfig,
-) -> Result<T, reqwest::Error>
+    filter: Option<&Filter>,
+    order_by: &[OrderBy],
+    search_client: &AzureSearchClient,
+) -> Result<T, SearchError>
 where
     T: DeserializeOwned,
 {
@@ -476,40 +1107,138 @@ where
         pagination,
         include_count,
         filter,
-        &client,
-        &config,
+        order_by,
+        &search_client.client,
+        &search_client.config,
     )?;
 
-    tracing::debug!("Requesting from URL: {}", &req.url());
-    client
-        .execute(req)
-        .await?
-        .error_for_status()?
-        .json::<T>()
-        .await
+    search_client.execute(req).await
+}
+
+impl AzureSearchClient {
+    async fn execute<T>(&self, request: reqwest::Request) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        self.execute_expecting(request, &self.retry_policy, |status| status.is_success())
+            .await
+    }
+
+    // Sends the request, retrying it as `retry_policy` allows when Azure is throttling or unavailable,
+    // and treats any response for which `is_expected(status)` is false as an error.
+    async fn execute_expecting<T>(
+        &self,
+        request: reqwest::Request,
+        retry_policy: &RetryPolicy,
+        is_expected: fn(reqwest::StatusCode) -> bool,
+    ) -> Result<T, SearchError>
+    where
+        T: DeserializeOwned,
+    {
+        tracing::debug!("Requesting from URL: {}", &request.url());
+        let request = &request;
+        let client = &self.client;
+
+        let body = with_retries(retry_policy, &self.circuit_breaker, move || async move {
+            let request = request
+                .try_clone()
+                .ok_or_else(|| anyhow::anyhow!("Request to {} can't be retried", request.url()))?;
+            let response = client.execute(request).await?;
+            let status = response.status();
+            let retry_after = parse_retry_after(response.headers());
+            let body = response.text().await?;
+
+            if !is_expected(status) {
+                return Err(SearchError::Http {
+                    status,
+                    body,
+                    retry_after,
+                });
+            }
+            Ok(body)
+        })
+        .await?;
+
+        serde_json::from_str(&body).map_err(Into::into)
+    }
+
+    async fn get_vocabulary(&self) -> Result<Arc<Vocabulary>, SearchError> {
+        if let Some(vocabulary) = self.vocabulary_cache.get() {
+            return Ok(vocabulary);
+        }
+
+        let request = build_vocabulary_request(&self.client, &self.config)?;
+        let results: VocabularyFacetResults = self.execute(request).await?;
+        let vocabulary = Vocabulary::from_facets(results.facet_results.values().flatten());
+
+        Ok(self.vocabulary_cache.set(vocabulary))
+    }
 }
 
 async fn update_index<T>(
     key_values: T,
+    search_client: &AzureSearchClient,
+) -> Result<AzureIndexChangedResults, SearchError>
+where
+    T: Serialize + Sized + Debug,
+{
+    let req = build_index_request(&[key_values], &search_client.client, &search_client.config)?;
+
+    // Azure responds with 207 when only some of the documents could be indexed
+    search_client
+        .execute_expecting(req, &search_client.retry_policy, |status| {
+            status == reqwest::StatusCode::OK
+        })
+        .await
+}
+
+impl AzureSearchClient {
+    async fn update_index_in_batches(
+        &self,
+        documents: Vec<serde_json::Value>,
+        key_field: &str,
+    ) -> Result<AzureIndexChangedResults, SearchError> {
+        index_in_batches(
+            documents,
+            key_field,
+            &self.retry_policy,
+            |batch| async move {
+                let req = build_index_request(&batch, &self.client, &self.config)?;
+
+                // The batches retry by themselves
+                self.execute_expecting(req, &RetryPolicy::none(), |status| {
+                    status == reqwest::StatusCode::OK || status == reqwest::StatusCode::MULTI_STATUS
+                })
+                .await
+            },
+        )
+        .await
+    }
+}
+
+fn build_index_request<T>(
+    documents: &[T],
     client: &reqwest::Client,
     config: &AzureConfig,
-) -> Result<AzureIndexChangedResults, anyhow::Error>
+) -> Result<reqwest::Request, SearchError>
 where
-    T: Serialize + Sized + Debug,
+    T: Serialize + Debug,
 {
-    let base_url = format!(
-        "https://{search_service}.search.windows.net/indexes/{search_index}/docs/index",
-        search_service = config.search_service,
-        search_index = config.search_index
-    );
+    let base_url = config.docs_url("/index");
+    let admin_key = config.admin_key.as_ref().ok_or_else(|| {
+        anyhow::anyhow!(
+            "An admin key is needed to update the {} index",
+            config.search_index
+        )
+    })?;
 
     let mut body = HashMap::new();
-    body.insert("value", [key_values]);
+    body.insert("value", documents);
 
     let req = client
         .post(&base_url)
         .query(&[("api-version", &config.api_version)])
-        .header("api-key", &config.api_key)
+        .header("api-key", admin_key)
         .header("Content-Type", "application/json")
         .json(&body)
         .build()?;
@@ -518,21 +1247,13 @@ where
     tracing::debug!("\nRequest: {:?}", &req);
     tracing::debug!("\nRequesting from URL: {}", &req.url());
 
-    let h = client.execute(req).await?;
-
-    if h.status() == reqwest::StatusCode::OK {
-        h.json::<AzureIndexChangedResults>()
-            .await
-            .map_err(|e| anyhow::anyhow!(e))
-    } else {
-        let error_message = h.text().await?;
-        Err(anyhow::anyhow!(error_message))
-    }
+    Ok(req)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::query_normalizer::default_searchable_fields;
     use pretty_assertions::assert_eq;
 
     fn given_we_have_a_search_client() -> reqwest::Client {
@@ -554,12 +1275,16 @@ mod test {
 
     fn given_we_have_a_config() -> AzureConfig {
         AzureConfig {
-            api_key: "api_key".to_string(),
+            query_key: "api_key".to_string(),
+            admin_key: None,
             search_index: "search_index".to_string(),
-            search_service: "search_service".to_string(),
+            base_url: "https://search_service.search.windows.net".to_string(),
             api_version: "api_version".to_string(),
             search_exactness_boost: "4".to_string(),
             search_fuzziness: "1".to_string(),
+            scoring_profile: Some("preferKeywords".to_string()),
+            searchable_fields: default_searchable_fields("products-index"),
+            synonyms: Synonyms::default(),
         }
     }
 
@@ -568,7 +1293,7 @@ mod test {
         search_term: String,
         config: AzureConfig,
     ) -> Result<reqwest::Request, reqwest::Error> {
-        build_search(&search_term, None, None, None, &client, &config)
+        build_search(&search_term, None, None, None, &[], &client, &config)
     }
 
     fn then_search_url_without_pagination_is_as_expected(
@@ -631,6 +1356,7 @@ mod test {
             }),
             Some(true),
             None,
+            &[],
             &client,
             &config,
         )
@@ -648,7 +1374,11 @@ mod test {
                 offset: 50,
             }),
             Some(true),
-            Some("(my_cool_field eq 'my cool value' xor my_cool_field ne 'my uncool value')"),
+            Some(&Filter::or(vec![
+                Filter::eq("my_cool_field", "my cool value"),
+                Filter::ne("my_cool_field", "my uncool value"),
+            ])),
+            &[],
             &client,
             &config,
         )
@@ -658,13 +1388,8 @@ mod test {
         client: reqwest::Client,
         config: AzureConfig,
     ) -> Result<reqwest::Request, reqwest::Error> {
-        build_facet_search(
-            &String::from("field"),
-            &String::from("I, IBUPROFEN"),
-            &String::from("eq"),
-            &client,
-            &config,
-        )
+        let (filter, facet) = facet_field_search("field", "I, IBUPROFEN");
+        build_facet_search(&filter, &facet, &client, &config)
     }
 
     fn then_search_url_with_pagination_is_as_expected(
@@ -686,7 +1411,7 @@ mod test {
     ) {
         if let Ok(actual) = actual_result {
             let actual = actual.url().to_string();
-            let expected = "https://search_service.search.windows.net/indexes/search_index/docs?api-version=api_version&highlight=content&queryType=full&search=&scoringProfile=preferKeywords&searchMode=all&%24count=true&%24filter=%28my_cool_field+eq+%27my+cool+value%27+xor+my_cool_field+ne+%27my+uncool+value%27%29&%24top=10&%24skip=50"
+            let expected = "https://search_service.search.windows.net/indexes/search_index/docs?api-version=api_version&highlight=content&queryType=full&search=&scoringProfile=preferKeywords&searchMode=all&%24count=true&%24filter=%28my_cool_field+eq+%27my+cool+value%27+or+my_cool_field+ne+%27my+uncool+value%27%29&%24top=10&%24skip=50"
                 .to_string();
 
             assert_eq!(actual, expected);
@@ -700,7 +1425,7 @@ mod test {
     ) {
         if let Ok(actual) = actual_result {
             let actual = actual.url().to_string();
-            let expected = "https://search_service.search.windows.net/indexes/search_index/docs?api-version=api_version&highlight=content&queryType=full&search=%28cool%7E1+%7C%7C+cool%5E4%29+%28beans%7E1+%7C%7C+beans%5E4%29&scoringProfile=preferKeywords&searchMode=all&%24count=true&%24filter=%28my_cool_field+eq+%27my+cool+value%27+xor+my_cool_field+ne+%27my+uncool+value%27%29&%24top=10&%24skip=50"
+            let expected = "https://search_service.search.windows.net/indexes/search_index/docs?api-version=api_version&highlight=content&queryType=full&search=%28cool%7E1+%7C%7C+cool%5E4%29+%28beans%7E1+%7C%7C+beans%5E4%29&scoringProfile=preferKeywords&searchMode=all&%24count=true&%24filter=%28my_cool_field+eq+%27my+cool+value%27+or+my_cool_field+ne+%27my+uncool+value%27%29&%24top=10&%24skip=50"
                 .to_string();
 
             assert_eq!(actual, expected);
@@ -761,28 +1486,119 @@ mod test {
     }
 
     #[test]
-    fn test_build_filter_by_collection_request() {
+    fn test_build_search_against_a_local_endpoint_without_scoring_profile() {
+        let client = given_we_have_a_search_client();
+        let config = AzureConfig {
+            base_url: "http://localhost:8080".to_string(),
+            scoring_profile: None,
+            ..given_we_have_a_config()
+        };
+
+        let req = build_search("", None, None, None, &[], &client, &config).unwrap();
+
+        assert_eq!(
+            req.url().to_string(),
+            "http://localhost:8080/indexes/search_index/docs?api-version=api_version&highlight=content&queryType=full&search=&searchMode=all&%24count=false"
+        );
+    }
+
+    #[test]
+    fn test_build_search_with_order_by() {
+        let client = given_we_have_a_search_client();
+        let config = given_we_have_a_config();
+
+        let req = build_search(
+            "",
+            None,
+            None,
+            None,
+            &[OrderBy::desc("created"), OrderBy::asc("title")],
+            &client,
+            &config,
+        )
+        .unwrap();
+
+        let order_by = req
+            .url()
+            .query_pairs()
+            .find(|(key, _)| key == "$orderby")
+            .map(|(_, value)| value.to_string());
+        assert_eq!(order_by, Some("created desc,title asc".to_string()));
+    }
+
+    #[test]
+    fn test_build_search_with_a_phrase_an_exclusion_and_a_field() {
+        let client = given_we_have_a_search_client();
+        let config = given_we_have_a_config();
+
+        let req = build_search(
+            r#""film-coated tablets" -capsule pl:pl 12345/0001"#,
+            None,
+            None,
+            None,
+            &[],
+            &client,
+            &config,
+        )
+        .unwrap();
+
+        let search = req
+            .url()
+            .query_pairs()
+            .find(|(key, _)| key == "search")
+            .map(|(_, value)| value.to_string());
+        assert_eq!(
+            search,
+            Some(r#""film\-coated tablets" -capsule pl_number:PL123450001"#.to_string())
+        );
+    }
+
+    #[test]
+    fn test_build_search_expands_synonyms() {
+        let client = given_we_have_a_search_client();
+        let config = AzureConfig {
+            synonyms: Synonyms::parse("adrenaline, epinephrine"),
+            ..given_we_have_a_config()
+        };
+
+        let req = build_search("adrenaline", None, None, None, &[], &client, &config).unwrap();
+
+        let search = req
+            .url()
+            .query_pairs()
+            .find(|(key, _)| key == "search")
+            .map(|(_, value)| value.to_string());
+        assert_eq!(
+            search,
+            Some("(adrenaline~1 || adrenaline^4 || epinephrine^4)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_build_filter_request() {
         let client = reqwest::Client::new();
         let config = AzureConfig {
-            search_service: "my_cool_service".to_string(),
+            base_url: "https://my_cool_service.search.windows.net".to_string(),
             search_index: "my_cool_search_index".to_string(),
-            api_key: "my_cool_api_key".to_string(),
+            query_key: "my_cool_query_key".to_string(),
+            admin_key: Some("my_cool_admin_key".to_string()),
             api_version: "2017-11-11".to_string(),
             search_fuzziness: "1".to_string(),
             search_exactness_boost: "4".to_string(),
+            scoring_profile: None,
+            searchable_fields: vec![],
+            synonyms: Synonyms::default(),
         };
 
-        let req = build_filter_by_collection_request(
-            &"my_cool_field".to_string(),
-            &"my cool value".to_string(),
-            &"cooler_than".to_string(),
+        let req = build_filter_request(
+            &Filter::any("my_cool_field", Operator::Ne, "my cool value's"),
             &client,
             &config,
         )
         .unwrap();
 
         let api_key = req.headers().get("api-key").unwrap().to_str().unwrap();
-        assert_eq!(api_key, config.api_key);
+        assert_eq!(api_key, config.query_key);
 
         let url = req.url();
         assert_eq!(url.scheme(), "https");
@@ -802,7 +1618,121 @@ mod test {
                 .find(|query_pair| query_pair.0 == "$filter")
                 .unwrap()
                 .1,
-            "my_cool_field/any(f: f cooler_than 'my cool value')"
+            "my_cool_field/any(f: f ne 'my cool value''s')"
         );
     }
+
+    #[test]
+    fn test_build_suggest_request() {
+        let client = given_we_have_a_search_client();
+        let config = given_we_have_a_config();
+        let options = SuggestOptions {
+            fuzzy: true,
+            highlight_pre_tag: Some("<em>".to_string()),
+            highlight_post_tag: Some("</em>".to_string()),
+            top: 10,
+        };
+
+        let actual = build_suggest_request("ibupro", &options, &client, &config)
+            .unwrap()
+            .url()
+            .to_string();
+
+        let expected = "https://search_service.search.windows.net/indexes/search_index/docs/suggest?api-version=api_version&search=ibupro&suggesterName=azure-suggester&fuzzy=true&%24top=10&highlightPreTag=%3Cem%3E&highlightPostTag=%3C%2Fem%3E";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_build_autocomplete_request() {
+        let client = given_we_have_a_search_client();
+        let config = given_we_have_a_config();
+
+        let actual = build_autocomplete_request(
+            "ibuprofen tab",
+            AutocompleteMode::OneTermWithContext,
+            &SuggestOptions::default(),
+            &client,
+            &config,
+        )
+        .unwrap()
+        .url()
+        .to_string();
+
+        let expected = "https://search_service.search.windows.net/indexes/search_index/docs/autocomplete?api-version=api_version&search=ibuprofen+tab&suggesterName=azure-suggester&autocompleteMode=oneTermWithContext&fuzzy=false&%24top=5";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_build_lookup_request() {
+        let client = given_we_have_a_search_client();
+        let config = given_we_have_a_config();
+
+        let actual = build_lookup_request("a key/with?symbols", &client, &config)
+            .unwrap()
+            .url()
+            .to_string();
+
+        let expected = "https://search_service.search.windows.net/indexes/search_index/docs/a%20key%2Fwith%3Fsymbols?api-version=api_version";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_build_keyset_request() {
+        let client = given_we_have_a_search_client();
+        let config = given_we_have_a_config();
+        let filter = Filter::eq("doc_type", "Spc");
+
+        let actual = build_keyset_request(Some("abc"), Some(&filter), 1000, &client, &config)
+            .unwrap()
+            .url()
+            .to_string();
+
+        let expected = "https://search_service.search.windows.net/indexes/search_index/docs?api-version=api_version&search=*&%24orderby=metadata_storage_name+asc&%24top=1000&%24filter=%28doc_type+eq+%27Spc%27+and+metadata_storage_name+gt+%27abc%27%29";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_build_facets_request() {
+        let client = given_we_have_a_search_client();
+        let config = given_we_have_a_config();
+        let filter = Filter::eq("territory", "UK");
+        let facets = vec![
+            FacetRequest::new("doc_type"),
+            FacetRequest::new("substance_name")
+                .count(5)
+                .sort(FacetSort::Value(SortDirection::Asc)),
+        ];
+
+        let actual = build_facets_request("ibuprofen", Some(&filter), &facets, &client, &config)
+            .unwrap()
+            .url()
+            .to_string();
+
+        let expected = "https://search_service.search.windows.net/indexes/search_index/docs?api-version=api_version&highlight=content&queryType=full&search=%28ibuprofen%7E1+%7C%7C+ibuprofen%5E4%29&scoringProfile=preferKeywords&searchMode=all&%24count=true&%24filter=territory+eq+%27UK%27&%24top=0&%24skip=0&facet=doc_type&facet=substance_name%2Ccount%3A5%2Csort%3Avalue";
+        assert_eq!(actual, expected);
+    }
+
+    const PRODUCTS_INDEX_DEFINITION: &str =
+        include_str!("../../search/definitions/indexes/default.json");
+    const BMGF_INDEX_DEFINITION: &str = include_str!("../../search/definitions/indexes/bmgf.json");
+
+    fn given_a_field_of_the_index(index_definition: &str, field_name: &str) -> serde_json::Value {
+        let index_definition: serde_json::Value = serde_json::from_str(index_definition).unwrap();
+        index_definition["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|field| field["name"] == field_name)
+            .cloned()
+            .unwrap_or_else(|| panic!("{} is not a field of the index", field_name))
+    }
+
+    #[test]
+    fn test_content_id_field_is_filterable_in_every_index() {
+        for index_definition in &[PRODUCTS_INDEX_DEFINITION, BMGF_INDEX_DEFINITION] {
+            let field = given_a_field_of_the_index(index_definition, CONTENT_ID_FIELD);
+
+            assert_eq!(field["filterable"], true);
+        }
+    }
 }
@@ -1,9 +1,11 @@
 pub use crate::document_type::{DocTypeParseError, DocumentType};
+pub use crate::product_licence::{LicenceClass, ProductLicence, ProductLicenceParseError};
 pub use crate::territory_type::{TerritoryType, TerritoryTypeParseError};
 use chrono::{SecondsFormat, Utc};
 use core::fmt::Debug;
 use serde_derive::{Deserialize, Serialize};
 use std::clone::Clone;
+use std::collections::HashMap;
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct AzureHighlight {
@@ -27,7 +29,8 @@ pub struct IndexResult {
     pub release_state: Option<String>,
     pub rev_label: Option<String>,
     pub suggestions: Vec<String>,
-    #[serde(rename = "@search.score")]
+    // Documents looked up by key don't have a score
+    #[serde(rename = "@search.score", default)]
     pub score: f32,
     #[serde(rename = "@search.highlights")]
     pub highlights: Option<AzureHighlight>,
@@ -45,10 +48,11 @@ pub struct IndexResults {
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ReportResult {
+    pub id: Option<String>,
     pub active_substances: Option<Vec<String>>,
     #[serde(rename = "@search.highlights")]
     pub highlights: Option<AzureHighlight>,
-    #[serde(rename = "@search.score")]
+    #[serde(rename = "@search.score", default)]
     pub score: f32,
     pub file_name: String,
     pub metadata_storage_path: String,
@@ -94,6 +98,76 @@ pub struct FacetResults {
     pub context: String,
 }
 
+// The value of a facet bucket, which has the type of the field, or for dates, is a string.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum FacetValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl std::fmt::Display for FacetValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FacetValue::Bool(value) => write!(f, "{}", value),
+            FacetValue::Int(value) => write!(f, "{}", value),
+            FacetValue::Float(value) => write!(f, "{}", value),
+            FacetValue::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct FacetBucket {
+    pub value: FacetValue,
+    pub count: i32,
+}
+
+// The buckets of each requested facet, keyed by field name.
+#[derive(Debug, Deserialize)]
+pub struct FacetCounts {
+    #[serde(rename = "@search.facets", default)]
+    pub facets: HashMap<String, Vec<FacetBucket>>,
+    #[serde(rename = "@odata.count")]
+    pub count: Option<i32>,
+}
+
+impl FacetCounts {
+    pub fn buckets(&self, field: &str) -> &[FacetBucket] {
+        self.facets
+            .get(field)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SuggestResult {
+    #[serde(rename = "@search.text")]
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestResults {
+    #[serde(rename = "value")]
+    pub suggest_results: Vec<SuggestResult>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AutocompleteResult {
+    pub text: String,
+    #[serde(rename = "queryPlusText")]
+    pub query_plus_text: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AutocompleteResults {
+    #[serde(rename = "value")]
+    pub autocomplete_results: Vec<AutocompleteResult>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct AzureIndexChangedResults {
     pub value: Vec<AzureIndexChangedResult>,
@@ -103,14 +177,26 @@ pub struct AzureIndexChangedResults {
 
 impl AzureIndexChangedResults {
     pub fn new(index_changed_result: AzureIndexChangedResult) -> AzureIndexChangedResults {
+        AzureIndexChangedResults::from_results(vec![index_changed_result])
+    }
+
+    pub fn from_results(value: Vec<AzureIndexChangedResult>) -> AzureIndexChangedResults {
         AzureIndexChangedResults {
             context: "context".to_string(),
-            value: vec![index_changed_result],
+            value,
         }
     }
+
+    pub fn succeeded(&self) -> impl Iterator<Item = &AzureIndexChangedResult> {
+        self.value.iter().filter(|result| result.status)
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &AzureIndexChangedResult> {
+        self.value.iter().filter(|result| !result.status)
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct AzureIndexChangedResult {
     pub key: String,
     pub status: bool,
@@ -146,6 +232,30 @@ pub struct IndexEntry {
     pub facets: Vec<String>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IndexAction {
+    Upload,
+    Merge,
+    MergeOrUpload,
+    Delete,
+}
+
+// An entry in a batch update of the index, see `CreateIndexEntry::create_index_entries`.
+#[derive(Debug, Serialize)]
+pub struct IndexEntryAction {
+    #[serde(rename = "@search.action")]
+    pub action: IndexAction,
+    #[serde(flatten)]
+    pub entry: IndexEntry,
+}
+
+impl IndexEntryAction {
+    pub fn new(action: IndexAction, entry: IndexEntry) -> Self {
+        Self { action, entry }
+    }
+}
+
 // The IndexResult model does not contain all of the information we want in the index,
 // however, the automatic index rebuild will populate the missing information.
 impl From<IndexResult> for IndexEntry {
@@ -234,4 +344,34 @@ mod tests {
         );
         assert_eq!(results.facet_results.facets[2].count, 6);
     }
+
+    #[test]
+    fn facet_counts_deserialize_correctly() {
+        let json = r#"{"@odata.count": 22, "value": [], "@search.facets": {
+            "doc_type": [{"value": "Spc", "count": 12}, {"value": "Pil", "count": 8}],
+            "metadata_storage_size": [{"value": 0, "count": 20}, {"value": 1000, "count": 2}]
+        }}"#;
+
+        let results: FacetCounts = serde_json::from_str(json).unwrap();
+
+        assert_eq!(results.count, Some(22));
+        assert_eq!(
+            results.buckets("doc_type"),
+            &[
+                FacetBucket {
+                    value: FacetValue::String("Spc".to_string()),
+                    count: 12
+                },
+                FacetBucket {
+                    value: FacetValue::String("Pil".to_string()),
+                    count: 8
+                },
+            ]
+        );
+        assert_eq!(
+            results.buckets("metadata_storage_size")[1].value,
+            FacetValue::Int(1000)
+        );
+        assert!(results.buckets("territory").is_empty());
+    }
 }
@@ -0,0 +1,321 @@
+use crate::territory_type::TerritoryType;
+use regex::{Captures, Regex};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+// Longer prefixes come first, as the regex takes the first alternative that matches.
+const PREFIXES: &str = "PLGB|PLNI|PLPI|PL|THRGB|THRNI|THR|NRGB|NRNI|NR";
+const SEPARATOR: &str = r"(?:\s|/|_|-)*";
+
+lazy_static! {
+    static ref RE_PRODUCT_LICENCE: Regex = Regex::new(&format!(
+        r"(?i)(?P<prefix>{prefixes})?{separator}(?P<company>\d{{5}}){separator}(?P<product>\d{{4}})",
+        prefixes = PREFIXES,
+        separator = SEPARATOR
+    ))
+    .unwrap();
+    // Also takes any other letters before a number to be a prefix, so that licences with a prefix we don't
+    // know of yet are kept rather than lost.
+    static ref RE_ANY_PRODUCT_LICENCE: Regex = Regex::new(&format!(
+        r"(?i)(?P<prefix>{prefixes}|[A-Z]+)?{separator}(?P<company>\d{{5}}){separator}(?P<product>\d{{4}})",
+        prefixes = PREFIXES,
+        separator = SEPARATOR
+    ))
+    .unwrap();
+    static ref RE_EXACT_PRODUCT_LICENCE: Regex = Regex::new(&format!(
+        r"(?i)^\s*(?P<prefix>{prefixes}){separator}(?P<company>\d{{5}}){separator}(?P<product>\d{{4}})\s*$",
+        prefixes = PREFIXES,
+        separator = SEPARATOR
+    ))
+    .unwrap();
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LicenceClass {
+    // PL
+    ProductLicence,
+    // PLPI
+    ParallelImport,
+    // THR
+    TraditionalHerbalRegistration,
+    // NR
+    HomeopathicRegistration,
+    // Any other prefix, which is kept as it is
+    Unrecognised,
+}
+
+impl LicenceClass {
+    fn code(self) -> &'static str {
+        match self {
+            LicenceClass::ProductLicence => "PL",
+            LicenceClass::ParallelImport => "PLPI",
+            LicenceClass::TraditionalHerbalRegistration => "THR",
+            LicenceClass::HomeopathicRegistration => "NR",
+            LicenceClass::Unrecognised => "",
+        }
+    }
+}
+
+// A licence number such as `PL 12345/0001`, made up of a prefix for the class of licence (and for licences
+// that only cover Great Britain or Northern Ireland, the territory), a company number and a product number.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProductLicence {
+    class: LicenceClass,
+    territory: Option<TerritoryType>,
+    prefix: String,
+    company_number: String,
+    product_number: String,
+}
+
+impl ProductLicence {
+    pub fn class(&self) -> LicenceClass {
+        self.class
+    }
+
+    // Licences without a GB or NI suffix cover the whole UK. There's no telling for an unrecognised prefix.
+    pub fn territory(&self) -> Option<TerritoryType> {
+        match self.class {
+            LicenceClass::Unrecognised => None,
+            _ => Some(self.territory.unwrap_or(TerritoryType::UK)),
+        }
+    }
+
+    pub fn prefix(&self) -> String {
+        self.prefix.clone()
+    }
+
+    // The form licences are indexed in, e.g. `PL123450001`.
+    pub fn canonical(&self) -> String {
+        format!(
+            "{}{}{}",
+            self.prefix(),
+            self.company_number,
+            self.product_number
+        )
+    }
+
+    // Every licence in the text with a prefix, in the order they appear. A prefix that isn't known is kept as it
+    // is, with the `Unrecognised` class, e.g. `NEW 12345/0001` as `NEW123450001`.
+    pub fn find_all(text: &str) -> Vec<Self> {
+        RE_ANY_PRODUCT_LICENCE
+            .captures_iter(text)
+            .filter_map(|caps| {
+                Self::from_captures(&caps).or_else(|| {
+                    Some(ProductLicence {
+                        class: LicenceClass::Unrecognised,
+                        territory: None,
+                        prefix: caps.name("prefix")?.as_str().to_ascii_uppercase(),
+                        company_number: caps["company"].to_string(),
+                        product_number: caps["product"].to_string(),
+                    })
+                })
+            })
+            .collect()
+    }
+
+    // As `find_all`, but also taking numbers without a prefix to be product licences, e.g. `12345/0001` as `PL 12345/0001`.
+    pub fn find_all_assuming_pl(text: &str) -> Vec<Self> {
+        RE_PRODUCT_LICENCE
+            .captures_iter(text)
+            .filter_map(|caps| {
+                Self::from_captures(&caps).or_else(|| {
+                    Some(ProductLicence {
+                        class: LicenceClass::ProductLicence,
+                        territory: None,
+                        prefix: LicenceClass::ProductLicence.code().to_string(),
+                        company_number: caps["company"].to_string(),
+                        product_number: caps["product"].to_string(),
+                    })
+                })
+            })
+            .collect()
+    }
+
+    // Replaces every licence in the text that has a known prefix with its canonical form.
+    pub fn normalize_all(text: &str) -> String {
+        RE_PRODUCT_LICENCE
+            .replace_all(text, |caps: &Captures| match Self::from_captures(caps) {
+                Some(licence) => licence.canonical(),
+                None => caps[0].to_string(),
+            })
+            .to_string()
+    }
+
+    fn from_captures(caps: &Captures) -> Option<Self> {
+        let prefix = caps.name("prefix")?.as_str().to_ascii_uppercase();
+        let (class, territory) = match prefix.as_str() {
+            "PL" => (LicenceClass::ProductLicence, None),
+            "PLGB" => (LicenceClass::ProductLicence, Some(TerritoryType::GB)),
+            "PLNI" => (LicenceClass::ProductLicence, Some(TerritoryType::NI)),
+            "PLPI" => (LicenceClass::ParallelImport, None),
+            "THR" => (LicenceClass::TraditionalHerbalRegistration, None),
+            "THRGB" => (
+                LicenceClass::TraditionalHerbalRegistration,
+                Some(TerritoryType::GB),
+            ),
+            "THRNI" => (
+                LicenceClass::TraditionalHerbalRegistration,
+                Some(TerritoryType::NI),
+            ),
+            "NR" => (LicenceClass::HomeopathicRegistration, None),
+            "NRGB" => (
+                LicenceClass::HomeopathicRegistration,
+                Some(TerritoryType::GB),
+            ),
+            "NRNI" => (
+                LicenceClass::HomeopathicRegistration,
+                Some(TerritoryType::NI),
+            ),
+            _ => return None,
+        };
+
+        Some(ProductLicence {
+            class,
+            territory,
+            prefix: match territory {
+                Some(territory) => format!("{}{}", class.code(), territory),
+                None => class.code().to_string(),
+            },
+            company_number: caps["company"].to_string(),
+            product_number: caps["product"].to_string(),
+        })
+    }
+}
+
+impl FromStr for ProductLicence {
+    type Err = ProductLicenceParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RE_EXACT_PRODUCT_LICENCE
+            .captures(s)
+            .and_then(|caps| Self::from_captures(&caps))
+            .ok_or_else(|| ProductLicenceParseError {
+                source: s.to_string(),
+            })
+    }
+}
+
+impl Display for ProductLicence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}/{}",
+            self.prefix(),
+            self.company_number,
+            self.product_number
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProductLicenceParseError {
+    source: String,
+}
+
+impl Display for ProductLicenceParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Could not parse ProductLicence from string: {}",
+            self.source
+        )
+    }
+}
+
+impl std::error::Error for ProductLicenceParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    #[test_case("PL 12345/0001", "PL123450001", "PL 12345/0001")]
+    #[test_case("pl12345/0001", "PL123450001", "PL 12345/0001")]
+    #[test_case("PL/12345/0001", "PL123450001", "PL 12345/0001")]
+    #[test_case("PL-12345-0001", "PL123450001", "PL 12345/0001")]
+    #[test_case("PL_12345_0001", "PL123450001", "PL 12345/0001")]
+    #[test_case(" PLGB 12345 0001 ", "PLGB123450001", "PLGB 12345/0001")]
+    #[test_case("thrni 12345/0001", "THRNI123450001", "THRNI 12345/0001")]
+    #[test_case("NR123450001", "NR123450001", "NR 12345/0001")]
+    fn parses_and_formats_licences(input: &str, canonical: &str, display: &str) {
+        let licence: ProductLicence = input.parse().unwrap();
+
+        assert_eq!(licence.canonical(), canonical);
+        assert_eq!(licence.to_string(), display);
+        assert_eq!(
+            licence.to_string().parse::<ProductLicence>().unwrap(),
+            licence
+        );
+    }
+
+    #[test_case("12345/0001"; "without a prefix")]
+    #[test_case("NEW 12345/0001"; "with an unknown prefix")]
+    #[test_case("PL 1234/0001"; "with a short company number")]
+    #[test_case("PL 12345/0001 and more"; "with other text")]
+    fn rejects_anything_else(input: &str) {
+        assert!(input.parse::<ProductLicence>().is_err());
+    }
+
+    #[test_case("PL 12345/0001", LicenceClass::ProductLicence, TerritoryType::UK)]
+    #[test_case("PLGB 12345/0001", LicenceClass::ProductLicence, TerritoryType::GB)]
+    #[test_case("PLNI 12345/0001", LicenceClass::ProductLicence, TerritoryType::NI)]
+    #[test_case("PLPI 12345/0001", LicenceClass::ParallelImport, TerritoryType::UK)]
+    #[test_case(
+        "THR 12345/0001",
+        LicenceClass::TraditionalHerbalRegistration,
+        TerritoryType::UK
+    )]
+    #[test_case(
+        "THRGB 12345/0001",
+        LicenceClass::TraditionalHerbalRegistration,
+        TerritoryType::GB
+    )]
+    #[test_case(
+        "NRNI 12345/0001",
+        LicenceClass::HomeopathicRegistration,
+        TerritoryType::NI
+    )]
+    fn derives_class_and_territory(input: &str, class: LicenceClass, territory: TerritoryType) {
+        let licence: ProductLicence = input.parse().unwrap();
+
+        assert_eq!(licence.class(), class);
+        assert_eq!(licence.territory(), Some(territory));
+    }
+
+    #[test]
+    fn finds_licences_in_text() {
+        let text = "Paracetamol PL 12345/0001, PLGB 12345/0002, NEW 12345/0003, 12345/0004";
+
+        let canonical = |licences: Vec<ProductLicence>| -> Vec<String> {
+            licences.iter().map(ProductLicence::canonical).collect()
+        };
+        assert_eq!(
+            canonical(ProductLicence::find_all(text)),
+            vec!["PL123450001", "PLGB123450002", "NEW123450003"]
+        );
+        assert_eq!(
+            canonical(ProductLicence::find_all_assuming_pl(text)),
+            vec!["PL123450001", "PLGB123450002", "PL123450003", "PL123450004"]
+        );
+    }
+
+    #[test]
+    fn keeps_unrecognised_prefixes() {
+        let licences = ProductLicence::find_all("new-12345-0003");
+
+        assert_eq!(licences.len(), 1);
+        assert_eq!(licences[0].class(), LicenceClass::Unrecognised);
+        assert_eq!(licences[0].territory(), None);
+        assert_eq!(licences[0].canonical(), "NEW123450003");
+        assert_eq!(licences[0].to_string(), "NEW 12345/0003");
+    }
+
+    #[test]
+    fn normalizes_licences_in_text() {
+        assert_eq!(
+            ProductLicence::normalize_all("PRETEXT pl 12345/1234 NEW 12345/1234 12345/1234"),
+            "PRETEXT PL123451234 NEW 12345/1234 12345/1234"
+        );
+    }
+}
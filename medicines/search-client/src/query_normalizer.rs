@@ -1,22 +1,10 @@
+use crate::{models::ProductLicence, synonyms::Synonyms};
 use regex::Captures;
 
 use regex::Regex;
 
 pub fn normalize_product_licences(search_term: &str) -> String {
-    lazy_static! {
-        static ref RE_PRODUCT_LICENCE: Regex = Regex::new(r"(?i)(?P<prefix>PL|PLGB|PLNI|PLPI|THR|THRGB|THRNI|NR|NRGB|NRNI)(\s+|/|_|-)*(?P<fivenumbers>\d{5})(\s+|/|_|-)*(?P<fournumbers>\d{4})").unwrap();
-    }
-
-    RE_PRODUCT_LICENCE
-        .replace_all(&search_term, |caps: &Captures| {
-            format!(
-                "{prefix}{five_numbers}{four_numbers}",
-                prefix = caps[1].to_uppercase(),
-                five_numbers = &caps[3],
-                four_numbers = &caps[5]
-            )
-        })
-        .to_string()
+    ProductLicence::normalize_all(search_term)
 }
 
 pub fn prefer_exact_match_but_support_fuzzy_match(
@@ -54,6 +42,242 @@ pub fn escape_special_words(search_term: &str) -> String {
         .to_string()
 }
 
+// The searchable fields of the indexes in `search/definitions/indexes`, which scope `field:` terms.
+pub const PRODUCTS_SEARCHABLE_FIELDS: &[&str] = &[
+    "content",
+    "product_name",
+    "keywords",
+    "title",
+    "pl_number",
+    "file_name",
+    "metadata_storage_name",
+    "suggestions",
+    "substance_name",
+];
+pub const BMGF_SEARCHABLE_FIELDS: &[&str] = &[
+    "content",
+    "metadata_storage_name",
+    "products",
+    "summary",
+    "pl_numbers",
+    "active_substances",
+    "pbpk_models",
+    "matrices",
+    "pregnancy_trimesters",
+    "file_name",
+    "report_name",
+];
+
+// Friendlier names for `field:` prefixes, with the fields they stand for in either index.
+const FIELD_ALIASES: &[(&str, &[&str])] = &[
+    ("substance", &["substance_name", "active_substances"]),
+    ("product", &["product_name", "products"]),
+    ("pl", &["pl_number", "pl_numbers"]),
+    ("title", &["title", "report_name"]),
+    ("keyword", &["keywords"]),
+    ("file", &["file_name"]),
+];
+
+pub fn default_searchable_fields(search_index: &str) -> Vec<String> {
+    let fields = if search_index.starts_with("bmgf") {
+        BMGF_SEARCHABLE_FIELDS
+    } else {
+        PRODUCTS_SEARCHABLE_FIELDS
+    };
+    fields.iter().map(|field| field.to_string()).collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryTerm {
+    Word(String),
+    Phrase(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryClause {
+    pub fields: Vec<String>,
+    pub term: QueryTerm,
+    pub excluded: bool,
+}
+
+// Splits a search into words and quoted phrases, each optionally prefixed with `-` to exclude it
+// and with `field:` to only search the fields it resolves to. `searchable_fields` limits which fields can be
+// used, and `None` allows any. A prefix that doesn't resolve to a field is searched for as part of the word.
+pub fn parse_query(search_term: &str, searchable_fields: Option<&[String]>) -> Vec<QueryClause> {
+    let chars: Vec<char> = search_term.chars().collect();
+    let mut clauses = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i].is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let excluded = chars[i] == '-';
+        if excluded {
+            i += 1;
+        }
+
+        let mut fields = vec![];
+        if chars.get(i) != Some(&'"') {
+            let end = word_end(&chars, i);
+            if let Some(colon) = chars[i..end].iter().position(|c| *c == ':') {
+                let prefix: String = chars[i..i + colon].iter().collect();
+                fields = resolve_fields(&prefix, searchable_fields);
+                if !fields.is_empty() {
+                    i += colon + 1;
+                }
+            }
+        }
+
+        let term = if chars.get(i) == Some(&'"') {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|c| *c == '"')
+                .map(|offset| i + 1 + offset)
+                .unwrap_or_else(|| chars.len());
+            let phrase: String = chars[i + 1..end].iter().collect();
+            i = end + 1;
+            QueryTerm::Phrase(phrase.trim().to_string())
+        } else {
+            let end = word_end(&chars, i);
+            let word: String = chars[i..end].iter().collect();
+            i = end;
+            QueryTerm::Word(word)
+        };
+
+        match &term {
+            QueryTerm::Word(text) | QueryTerm::Phrase(text) if text.is_empty() => {}
+            _ => clauses.push(QueryClause {
+                fields,
+                term,
+                excluded,
+            }),
+        }
+    }
+
+    clauses
+}
+
+fn word_end(chars: &[char], start: usize) -> usize {
+    chars[start..]
+        .iter()
+        .position(|c| c.is_whitespace())
+        .map(|offset| start + offset)
+        .unwrap_or_else(|| chars.len())
+}
+
+fn resolve_fields(prefix: &str, searchable_fields: Option<&[String]>) -> Vec<String> {
+    let prefix = prefix.to_lowercase();
+    let candidates: Vec<&str> = match FIELD_ALIASES.iter().find(|(alias, _)| *alias == prefix) {
+        Some((_, fields)) => fields.to_vec(),
+        None if !prefix.is_empty() => vec![prefix.as_str()],
+        None => vec![],
+    };
+
+    candidates
+        .into_iter()
+        .filter(|field| match searchable_fields {
+            Some(searchable_fields) => searchable_fields.iter().any(|f| f == field),
+            None => true,
+        })
+        .map(String::from)
+        .collect()
+}
+
+// Renders the clauses as a Lucene query, in which free-text words prefer exact matches but also match fuzzily.
+// Phrases, field-scoped and excluded terms are matched exactly. Each term is OR-ed with its synonyms,
+// which are boosted like exact matches.
+pub fn to_lucene_query(
+    clauses: &[QueryClause],
+    synonyms: &Synonyms,
+    search_fuzziness: &str,
+    search_exactness_boost: &str,
+) -> String {
+    clauses
+        .iter()
+        .map(|clause| {
+            let (text, term) = match &clause.term {
+                QueryTerm::Word(word) => (word, escape_word(word)),
+                QueryTerm::Phrase(phrase) => (phrase, escape_phrase(phrase)),
+            };
+            let synonyms: Vec<String> = synonyms
+                .expand(text)
+                .iter()
+                .map(|synonym| match synonym.contains(char::is_whitespace) {
+                    true => escape_phrase(synonym),
+                    false => escape_word(synonym),
+                })
+                .collect();
+
+            let query = match clause.fields.as_slice() {
+                [] if clause.excluded => any_of(&term, &synonyms),
+                [] => match clause.term {
+                    QueryTerm::Word(_) if synonyms.is_empty() => {
+                        prefer_exact_match_but_support_fuzzy_match(
+                            &term,
+                            search_fuzziness,
+                            search_exactness_boost,
+                        )
+                    }
+                    QueryTerm::Word(_) => format!(
+                        "({}~{} || {})",
+                        term,
+                        search_fuzziness,
+                        std::iter::once(&term)
+                            .chain(&synonyms)
+                            .map(|term| format!("{}^{}", term, search_exactness_boost))
+                            .collect::<Vec<String>>()
+                            .join(" || ")
+                    ),
+                    QueryTerm::Phrase(_) => any_of(&term, &synonyms),
+                },
+                fields => {
+                    let term = any_of(&term, &synonyms);
+                    let scoped: Vec<String> = fields
+                        .iter()
+                        .map(|field| format!("{}:{}", field, term))
+                        .collect();
+                    match scoped.len() {
+                        1 => scoped.join(""),
+                        _ => format!("({})", scoped.join(" || ")),
+                    }
+                }
+            };
+
+            if clause.excluded {
+                format!("-{}", query)
+            } else {
+                query
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn any_of(term: &str, synonyms: &[String]) -> String {
+    if synonyms.is_empty() {
+        return term.to_string();
+    }
+    let terms: Vec<&str> = std::iter::once(term)
+        .chain(synonyms.iter().map(String::as_str))
+        .collect();
+    format!("({})", terms.join(" || "))
+}
+
+fn escape_phrase(phrase: &str) -> String {
+    format!("\"{}\"", escape_special_characters(phrase))
+}
+
+fn escape_word(word: &str) -> String {
+    let word = escape_special_characters(word);
+    match word.as_str() {
+        "AND" | "OR" | "NOT" => word.to_lowercase(),
+        _ => word,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -99,4 +323,129 @@ mod test {
         let result = escape_special_words(&input);
         assert_eq!(result, expected);
     }
+
+    fn given_the_products_index_fields() -> Vec<String> {
+        default_searchable_fields("products-index")
+    }
+
+    fn a_clause(fields: &[&str], term: QueryTerm, excluded: bool) -> QueryClause {
+        QueryClause {
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            term,
+            excluded,
+        }
+    }
+
+    #[test]
+    fn test_parse_query_with_phrases_exclusions_and_fields() {
+        let fields = given_the_products_index_fields();
+        let result = parse_query(
+            r#"ibuprofen "film-coated tablets" -capsule substance:paracetamol pl:"PL123450001""#,
+            Some(&fields),
+        );
+        assert_eq!(
+            result,
+            vec![
+                a_clause(&[], QueryTerm::Word("ibuprofen".to_string()), false),
+                a_clause(
+                    &[],
+                    QueryTerm::Phrase("film-coated tablets".to_string()),
+                    false
+                ),
+                a_clause(&[], QueryTerm::Word("capsule".to_string()), true),
+                a_clause(
+                    &["substance_name"],
+                    QueryTerm::Word("paracetamol".to_string()),
+                    false
+                ),
+                a_clause(
+                    &["pl_number"],
+                    QueryTerm::Phrase("PL123450001".to_string()),
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[test_case("substance:x", &["substance_name"])]
+    #[test_case("SUBSTANCE:x", &["substance_name"])]
+    #[test_case("product_name:x", &["product_name"])]
+    #[test_case("report_name:x", &[]; "field of another index")]
+    #[test_case("ratio:x", &[]; "unknown field")]
+    #[test_case(":x", &[]; "empty field")]
+    fn test_parse_query_resolves_fields_to_the_index(input: &str, expected: &[&str]) {
+        let fields = given_the_products_index_fields();
+        let result = parse_query(input, Some(&fields));
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].fields, expected);
+    }
+
+    #[test]
+    fn test_parse_query_allows_any_field_without_a_list_of_fields() {
+        let result = parse_query("substance:x", None);
+        assert_eq!(
+            result[0].fields,
+            vec!["substance_name", "active_substances"]
+        );
+    }
+
+    #[test_case(r#"a "unterminated phrase"#, 2)]
+    #[test_case(r#""" - -"" a"#, 1)]
+    #[test_case("   ", 0)]
+    fn test_parse_query_skips_empty_terms(input: &str, expected: usize) {
+        assert_eq!(parse_query(input, None).len(), expected);
+    }
+
+    #[test_case("ibuprofen", "(ibuprofen~1 || ibuprofen^4)")]
+    #[test_case(r#""film-coated tablets""#, r#""film\-coated tablets""#)]
+    #[test_case("-capsule", "-capsule")]
+    #[test_case("substance:ibuprofen", "substance_name:ibuprofen")]
+    #[test_case("pl:PL12345/0001", r"pl_number:PL12345\/0001")]
+    #[test_case(r#"-title:"oral suspension""#, r#"-title:"oral suspension""#)]
+    #[test_case("ratio 1:2", r"(ratio~1 || ratio^4) (1\:2~1 || 1\:2^4)")]
+    #[test_case("NOT", "(not~1 || not^4)")]
+    #[test_case(r#""a) OR (b""#, r#""a\) OR \(b""#)]
+    fn test_to_lucene_query(input: &str, expected: &str) {
+        let fields = given_the_products_index_fields();
+        let result = to_lucene_query(
+            &parse_query(input, Some(&fields)),
+            &Synonyms::default(),
+            "1",
+            "4",
+        );
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_to_lucene_query_searches_every_field_of_an_alias() {
+        let result = to_lucene_query(
+            &parse_query("-substance:ibuprofen", None),
+            &Synonyms::default(),
+            "1",
+            "4",
+        );
+        assert_eq!(
+            result,
+            "-(substance_name:ibuprofen || active_substances:ibuprofen)"
+        );
+    }
+
+    #[test_case(
+        "acetaminophen",
+        "(acetaminophen~1 || acetaminophen^4 || paracetamol^4)"
+    )]
+    #[test_case("ascorbic", r#"(ascorbic~1 || ascorbic^4 || "vitamin c"^4)"#)]
+    #[test_case(r#""vitamin c""#, r#"("vitamin c" || ascorbic)"#)]
+    #[test_case("-acetaminophen", "-(acetaminophen || paracetamol)")]
+    #[test_case(
+        "substance:acetaminophen",
+        "substance_name:(acetaminophen || paracetamol)"
+    )]
+    #[test_case("ibuprofen", "(ibuprofen~1 || ibuprofen^4)")]
+    fn test_to_lucene_query_with_synonyms(input: &str, expected: &str) {
+        let fields = given_the_products_index_fields();
+        let synonyms = Synonyms::parse("acetaminophen, paracetamol\nvitamin c, ascorbic");
+        let result = to_lucene_query(&parse_query(input, Some(&fields)), &synonyms, "1", "4");
+        assert_eq!(result, expected);
+    }
 }
@@ -0,0 +1,387 @@
+use crate::SearchError;
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_millis(10_000);
+pub(crate) const DEFAULT_CIRCUIT_BREAKER_THRESHOLD: u32 = 5;
+pub(crate) const DEFAULT_CIRCUIT_BREAKER_OPEN_DURATION: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    static ref CIRCUIT_BREAKERS: Mutex<HashMap<String, Arc<CircuitBreaker>>> =
+        Mutex::new(HashMap::new());
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(0),
+            max_backoff: Duration::from_millis(0),
+        }
+    }
+
+    // Exponential backoff with "equal jitter": half of the delay is fixed and half is random,
+    // so that clients throttled at the same time don't all retry at the same time.
+    // A `Retry-After` from Azure is a lower bound, but if it is longer than we're prepared to wait
+    // there is no point retrying at all.
+    pub(crate) fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        let exponential = self
+            .initial_backoff
+            .checked_mul(2u32.saturating_pow(attempt))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        let jittered = exponential / 2 + exponential.mul_f64(random_fraction() / 2.0);
+
+        match retry_after {
+            Some(retry_after) if retry_after > self.max_backoff => None,
+            Some(retry_after) => Some(retry_after.max(jittered)),
+            None => Some(jittered),
+        }
+    }
+}
+
+// Stops calling Azure Search for a while once it has failed with throttling or unavailability
+// `failure_threshold` times in a row. After `open_duration` a single trial request is let through,
+// which closes the circuit again if it succeeds.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    state: Mutex<CircuitState>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CircuitState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { since: Instant },
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            failure_threshold,
+            open_duration,
+            state: Mutex::new(CircuitState::Closed { failures: 0 }),
+        }
+    }
+
+    // Clients for the same index share a circuit breaker, because they share the service's health.
+    // Clients with different settings get a circuit breaker each, so that none of them loses its settings.
+    pub fn for_index(
+        search_service: &str,
+        search_index: &str,
+        failure_threshold: u32,
+        open_duration: Duration,
+    ) -> Arc<Self> {
+        CIRCUIT_BREAKERS
+            .lock()
+            .expect("Circuit breaker registry lock was poisoned")
+            .entry(format!(
+                "{}/{}?threshold={}&open_ms={}",
+                search_service,
+                search_index,
+                failure_threshold,
+                open_duration.as_millis()
+            ))
+            .or_insert_with(|| Arc::new(CircuitBreaker::new(failure_threshold, open_duration)))
+            .clone()
+    }
+
+    fn try_acquire(&self) -> bool {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Circuit breaker lock was poisoned");
+        let now = Instant::now();
+        match *state {
+            CircuitState::Closed { .. } => true,
+            CircuitState::Open { until } | CircuitState::HalfOpen { since: until }
+                if now >= until =>
+            {
+                // A trial request that never finished (e.g. it was cancelled) must not keep the circuit half open forever,
+                // so a half open circuit lets another trial through after `open_duration`.
+                *state = CircuitState::HalfOpen {
+                    since: now + self.open_duration,
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn record_success(&self) {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Circuit breaker lock was poisoned");
+        *state = CircuitState::Closed { failures: 0 };
+    }
+
+    fn record_failure(&self) {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Circuit breaker lock was poisoned");
+        let open = CircuitState::Open {
+            until: Instant::now() + self.open_duration,
+        };
+        *state = match *state {
+            CircuitState::Closed { failures } if failures + 1 < self.failure_threshold => {
+                CircuitState::Closed {
+                    failures: failures + 1,
+                }
+            }
+            _ => open,
+        };
+    }
+}
+
+pub(crate) async fn with_retries<F, Fut, T>(
+    policy: &RetryPolicy,
+    circuit_breaker: &CircuitBreaker,
+    mut operation: F,
+) -> Result<T, SearchError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, SearchError>>,
+{
+    let mut attempt = 0;
+    loop {
+        if !circuit_breaker.try_acquire() {
+            return Err(SearchError::CircuitOpen);
+        }
+
+        let error = match operation().await {
+            Ok(result) => {
+                circuit_breaker.record_success();
+                return Ok(result);
+            }
+            Err(e) if e.is_retryable() => {
+                circuit_breaker.record_failure();
+                e
+            }
+            // Azure responded, so it's healthy, even if it didn't like the request
+            Err(e) => {
+                circuit_breaker.record_success();
+                return Err(e);
+            }
+        };
+
+        let delay = match policy.delay(attempt, error.retry_after()) {
+            Some(delay) if attempt < policy.max_retries => delay,
+            _ => return Err(error),
+        };
+        tracing::warn!(
+            "Retrying Azure Search request in {:?} (retry {} of {}): {}",
+            delay,
+            attempt + 1,
+            policy.max_retries,
+            error
+        );
+        tokio::time::delay_for(delay).await;
+        attempt += 1;
+    }
+}
+
+// `Retry-After` is either a number of seconds or an HTTP date.
+pub(crate) fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let date = DateTime::parse_from_rfc2822(value).ok()?;
+            (date.with_timezone(&Utc) - Utc::now())
+                .to_std()
+                .ok()
+                .or_else(|| Some(Duration::from_secs(0)))
+        }
+    }
+}
+
+// A random number in [0, 1) without pulling in a random number generator,
+// as every `RandomState` is seeded with different keys.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SearchErrorKind;
+    use reqwest::{header::HeaderValue, StatusCode};
+    use std::cell::Cell;
+    use test_case::test_case;
+
+    fn given_a_policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            initial_backoff: Duration::from_millis(0),
+            max_backoff: Duration::from_millis(10),
+        }
+    }
+
+    fn given_an_error(status: u16, retry_after: Option<Duration>) -> SearchError {
+        SearchError::Http {
+            status: StatusCode::from_u16(status).unwrap(),
+            body: String::default(),
+            retry_after,
+        }
+    }
+
+    fn when_we_call_azure(
+        policy: &RetryPolicy,
+        circuit_breaker: &CircuitBreaker,
+        responses: Vec<Result<&'static str, u16>>,
+    ) -> (Result<&'static str, SearchError>, usize) {
+        let calls = Cell::new(0);
+        let result = tokio_test::block_on(with_retries(policy, circuit_breaker, || {
+            let response = responses[calls.get().min(responses.len() - 1)];
+            calls.set(calls.get() + 1);
+            async move { response.map_err(|status| given_an_error(status, None)) }
+        }));
+        (result, calls.get())
+    }
+
+    #[test]
+    fn retries_throttled_requests_until_they_succeed() {
+        let circuit_breaker = CircuitBreaker::new(10, Duration::from_secs(30));
+        let (result, calls) = when_we_call_azure(
+            &given_a_policy(3),
+            &circuit_breaker,
+            vec![Err(503), Err(429), Ok("results")],
+        );
+
+        assert_eq!(result.unwrap(), "results");
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn gives_up_after_the_maximum_number_of_retries() {
+        let circuit_breaker = CircuitBreaker::new(10, Duration::from_secs(30));
+        let (result, calls) =
+            when_we_call_azure(&given_a_policy(2), &circuit_breaker, vec![Err(503)]);
+
+        assert_eq!(result.unwrap_err().kind(), SearchErrorKind::Throttled);
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn does_not_retry_bad_requests() {
+        let circuit_breaker = CircuitBreaker::new(10, Duration::from_secs(30));
+        let (result, calls) =
+            when_we_call_azure(&given_a_policy(3), &circuit_breaker, vec![Err(400)]);
+
+        assert_eq!(result.unwrap_err().kind(), SearchErrorKind::BadRequest);
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn opens_the_circuit_after_consecutive_failures() {
+        let circuit_breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+        let (result, calls) =
+            when_we_call_azure(&given_a_policy(5), &circuit_breaker, vec![Err(503)]);
+
+        assert!(matches!(result, Err(SearchError::CircuitOpen)));
+        assert_eq!(calls, 2);
+
+        let (result, calls) =
+            when_we_call_azure(&given_a_policy(5), &circuit_breaker, vec![Ok("results")]);
+        assert!(matches!(result, Err(SearchError::CircuitOpen)));
+        assert_eq!(calls, 0);
+    }
+
+    #[test]
+    fn closes_the_circuit_when_a_trial_request_succeeds() {
+        let circuit_breaker = CircuitBreaker::new(1, Duration::from_millis(0));
+        let (result, _) = when_we_call_azure(&given_a_policy(0), &circuit_breaker, vec![Err(503)]);
+        assert!(result.is_err());
+
+        let (result, calls) =
+            when_we_call_azure(&given_a_policy(0), &circuit_breaker, vec![Ok("results")]);
+        assert_eq!(result.unwrap(), "results");
+        assert_eq!(calls, 1);
+        assert_eq!(
+            *circuit_breaker.state.lock().unwrap(),
+            CircuitState::Closed { failures: 0 }
+        );
+    }
+
+    #[test]
+    fn successful_requests_reset_the_failure_count() {
+        let circuit_breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+        for _ in 0..3 {
+            let (result, _) = when_we_call_azure(
+                &given_a_policy(1),
+                &circuit_breaker,
+                vec![Err(503), Ok("results")],
+            );
+            assert_eq!(result.unwrap(), "results");
+        }
+    }
+
+    #[test_case(0, 100, 200)]
+    #[test_case(1, 200, 400)]
+    #[test_case(2, 400, 800)]
+    #[test_case(10, 500, 1000)]
+    fn backs_off_exponentially_with_jitter(attempt: u32, min_millis: u64, max_millis: u64) {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_millis(1000),
+        };
+
+        let delay = policy.delay(attempt, None).unwrap();
+
+        assert!(delay >= Duration::from_millis(min_millis), "{:?}", delay);
+        assert!(delay <= Duration::from_millis(max_millis), "{:?}", delay);
+    }
+
+    #[test]
+    fn waits_at_least_as_long_as_retry_after() {
+        let policy = given_a_policy(3);
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_millis(8))),
+            Some(Duration::from_millis(8))
+        );
+        assert_eq!(policy.delay(0, Some(Duration::from_secs(60))), None);
+    }
+
+    #[test_case("120", Some(Duration::from_secs(120)))]
+    #[test_case("Wed, 21 Oct 2015 07:28:00 GMT", Some(Duration::from_secs(0)))]
+    #[test_case("soon", None)]
+    fn parses_retry_after(value: &str, expected: Option<Duration>) {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+
+        assert_eq!(parse_retry_after(&headers), expected);
+    }
+}
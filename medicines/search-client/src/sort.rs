@@ -0,0 +1,86 @@
+use std::fmt::{Display, Formatter};
+
+// Azure Search sorts by this pseudo-field to rank by relevance, e.g. to break ties between other keys.
+pub const RELEVANCE: &str = "search.score()";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+impl Display for SortDirection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortDirection::Asc => write!(f, "asc"),
+            SortDirection::Desc => write!(f, "desc"),
+        }
+    }
+}
+
+// A key of an OData `$orderby` expression, e.g. `created desc`.
+// The field has to be `sortable` in the index definition.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderBy {
+    pub field: String,
+    pub direction: SortDirection,
+}
+
+impl OrderBy {
+    pub fn asc(field: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            direction: SortDirection::Asc,
+        }
+    }
+
+    pub fn desc(field: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            direction: SortDirection::Desc,
+        }
+    }
+
+    pub fn relevance() -> Self {
+        Self::desc(RELEVANCE)
+    }
+}
+
+impl Display for OrderBy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.field, self.direction)
+    }
+}
+
+// Renders the keys as the value of `$orderby`, or `None` to rank by relevance alone.
+pub fn to_order_by_expression(order_by: &[OrderBy]) -> Option<String> {
+    if order_by.is_empty() {
+        return None;
+    }
+    Some(
+        order_by
+            .iter()
+            .map(OrderBy::to_string)
+            .collect::<Vec<String>>()
+            .join(","),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(vec![], None)]
+    #[test_case(vec![OrderBy::desc("created")], Some("created desc"))]
+    #[test_case(
+        vec![OrderBy::asc("product_name"), OrderBy::desc("created"), OrderBy::relevance()],
+        Some("product_name asc,created desc,search.score() desc")
+    )]
+    fn renders_order_by_expressions(order_by: Vec<OrderBy>, expected: Option<&str>) {
+        assert_eq!(
+            to_order_by_expression(&order_by),
+            expected.map(String::from)
+        );
+    }
+}
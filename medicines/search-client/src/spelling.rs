@@ -0,0 +1,360 @@
+use crate::models::Facet;
+use serde_derive::Deserialize;
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+// The facetable fields the vocabulary is built from: `facets` holds the substance names
+// (and product names) and `product_name` holds the product names.
+pub(crate) const VOCABULARY_FACETS: &[&str] = &["facets", "product_name"];
+
+// Shorter words aren't checked, as too many words are within an edit or two of them.
+const MIN_WORD_LENGTH: usize = 4;
+
+// How long a vocabulary is kept before it's fetched again.
+const VOCABULARY_TTL: Duration = Duration::from_secs(60 * 60);
+
+lazy_static! {
+    static ref VOCABULARY_CACHES: Mutex<HashMap<String, Arc<VocabularyCache>>> =
+        Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct VocabularyFacetResults {
+    #[serde(rename = "@search.facets")]
+    pub facet_results: HashMap<String, Vec<Facet>>,
+}
+
+// The words in an index, with the number of documents each appears in,
+// which spelling suggestions for a search are chosen from.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Vocabulary {
+    words: HashMap<String, i32>,
+}
+
+impl Vocabulary {
+    pub fn from_facets<'a>(facets: impl IntoIterator<Item = &'a Facet>) -> Self {
+        let mut vocabulary = Vocabulary::default();
+        for facet in facets {
+            for word in words(&facet.value) {
+                *vocabulary.words.entry(word.to_lowercase()).or_default() += facet.count;
+            }
+        }
+        vocabulary
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains_key(&word.to_lowercase())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    // Up to `top` alternatives to the search, each replacing the words which aren't in the
+    // vocabulary with close matches. The first uses the closest match for every word,
+    // and the rest each swap one word for its next closest match.
+    pub fn did_you_mean(&self, search_term: &str, top: usize) -> Vec<String> {
+        let corrections: Vec<(&str, Vec<Candidate>)> = words(search_term)
+            .filter(|word| is_checked(search_term, word) && !self.contains(word))
+            .map(|word| (word, self.candidates(word, top)))
+            .filter(|(_, candidates)| !candidates.is_empty())
+            .collect();
+        if corrections.is_empty() {
+            return vec![];
+        }
+
+        let best: Vec<(&str, &str)> = corrections
+            .iter()
+            .map(|(word, candidates)| (*word, candidates[0].word))
+            .collect();
+
+        let mut alternatives: Vec<(usize, &Candidate)> = corrections
+            .iter()
+            .enumerate()
+            .flat_map(|(i, (_, candidates))| candidates.iter().skip(1).map(move |c| (i, c)))
+            .collect();
+        alternatives.sort_by_key(|&(_, candidate)| candidate);
+
+        let mut suggestions = vec![replace_words(search_term, &best)];
+        for (i, candidate) in alternatives {
+            let mut replacements = best.clone();
+            replacements[i].1 = candidate.word;
+            let suggestion = replace_words(search_term, &replacements);
+            if !suggestions.contains(&suggestion) {
+                suggestions.push(suggestion);
+            }
+        }
+        suggestions.truncate(top);
+        suggestions
+    }
+
+    fn candidates(&self, word: &str, top: usize) -> Vec<Candidate<'_>> {
+        let word = word.to_lowercase();
+        let key = phonetic_key(&word);
+        let max_distance = if word.chars().count() <= 5 { 1 } else { 2 };
+
+        let mut candidates: Vec<Candidate> = self
+            .words
+            .iter()
+            .filter_map(|(candidate, count)| {
+                let sounds_alike = phonetic_key(candidate) == key;
+                // Words which sound alike are allowed one more edit
+                let allowed = max_distance + sounds_alike as usize;
+                let distance = edit_distance(&word, candidate, allowed)?;
+                Some(Candidate {
+                    word: candidate,
+                    distance,
+                    sounds_alike,
+                    count: *count,
+                })
+            })
+            .collect();
+        candidates.sort();
+        candidates.truncate(top);
+        candidates
+    }
+}
+
+// The vocabulary is built from every facet value in the index, so it's kept for a while
+// rather than fetched for each search.
+#[derive(Default)]
+pub(crate) struct VocabularyCache {
+    cached: Mutex<Option<(Instant, Arc<Vocabulary>)>>,
+}
+
+impl VocabularyCache {
+    // Clients for the same index share a vocabulary, because they search the same documents.
+    pub fn for_index(search_service: &str, search_index: &str) -> Arc<Self> {
+        VOCABULARY_CACHES
+            .lock()
+            .expect("Vocabulary cache registry lock was poisoned")
+            .entry(format!("{}/{}", search_service, search_index))
+            .or_default()
+            .clone()
+    }
+
+    pub fn get(&self) -> Option<Arc<Vocabulary>> {
+        match &*self.lock() {
+            Some((fetched, vocabulary)) if fetched.elapsed() < VOCABULARY_TTL => {
+                Some(vocabulary.clone())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn set(&self, vocabulary: Vocabulary) -> Arc<Vocabulary> {
+        let vocabulary = Arc::new(vocabulary);
+        *self.lock() = Some((Instant::now(), vocabulary.clone()));
+        vocabulary
+    }
+
+    // The vocabulary is only ever replaced whole, so it's still usable if another thread panicked.
+    fn lock(&self) -> MutexGuard<'_, Option<(Instant, Arc<Vocabulary>)>> {
+        self.cached
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct Candidate<'a> {
+    word: &'a str,
+    distance: usize,
+    sounds_alike: bool,
+    count: i32,
+}
+
+impl Candidate<'_> {
+    // Sounding alike is worth half an edit, and more common words win ties.
+    fn rank(&self) -> (usize, Reverse<i32>, &str) {
+        (
+            self.distance * 2 + !self.sounds_alike as usize,
+            Reverse(self.count),
+            self.word,
+        )
+    }
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= MIN_WORD_LENGTH)
+        .filter(|word| word.chars().all(char::is_alphabetic))
+}
+
+// Field names, as in `substance:ibuprofen`, aren't part of what's searched for.
+fn is_checked(search_term: &str, word: &str) -> bool {
+    let offset = word.as_ptr() as usize - search_term.as_ptr() as usize;
+    !search_term[offset + word.len()..].starts_with(':')
+}
+
+fn replace_words(search_term: &str, replacements: &[(&str, &str)]) -> String {
+    let mut result = String::with_capacity(search_term.len());
+    let mut rest = 0;
+    for (word, replacement) in replacements {
+        let offset = word.as_ptr() as usize - search_term.as_ptr() as usize;
+        result.push_str(&search_term[rest..offset]);
+        result.push_str(replacement);
+        rest = offset + word.len();
+    }
+    result.push_str(&search_term[rest..]);
+    result
+}
+
+// The optimal string alignment distance between the words, counting a transposition
+// of neighbouring letters as one edit, or None if it's more than `max`.
+fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().max(b.len()) - a.len().min(b.len()) > max {
+        return None;
+    }
+
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>(); a.len() + 1];
+    for i in 1..=a.len() {
+        rows[i][0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            rows[i][j] = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                rows[i][j] = rows[i][j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        if rows[i].iter().all(|&distance| distance > max) {
+            return None;
+        }
+    }
+
+    Some(rows[a.len()][b.len()]).filter(|&distance| distance <= max)
+}
+
+// A Soundex style key, with `ph` read as `f` and the first letter coded like the rest,
+// so that e.g. `phenoxymethylpenicillin` and `fenoxymethylpenicillin` sound alike.
+fn phonetic_key(word: &str) -> String {
+    let mut key = String::new();
+    let mut previous = None;
+    for c in word.to_lowercase().replace("ph", "f").chars() {
+        let code = match c {
+            'b' | 'f' | 'p' | 'v' => Some('1'),
+            'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+            'd' | 't' => Some('3'),
+            'l' => Some('4'),
+            'm' | 'n' => Some('5'),
+            'r' => Some('6'),
+            // Like in Soundex, `h` and `w` don't separate letters with the same code but vowels do
+            'h' | 'w' => continue,
+            _ => None,
+        };
+        if let Some(code) = code {
+            if previous != Some(code) {
+                key.push(code);
+            }
+        }
+        previous = code;
+    }
+    key
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    fn given_a_vocabulary() -> Vocabulary {
+        let facet = |count: i32, value: &str| Facet {
+            count,
+            value: value.to_string(),
+        };
+        Vocabulary::from_facets(&[
+            facet(40, "I"),
+            facet(30, "I, IBUPROFEN"),
+            facet(10, "I, IBUPROFEN, NUROFEN 200MG TABLETS"),
+            facet(20, "P, PARACETAMOL"),
+            facet(5, "P, PHENOXYMETHYLPENICILLIN"),
+            facet(8, "P, PARACETAMOL, PANADOL 500MG TABLETS"),
+            facet(3, "P, PARACETAMOL, PANADOL EXTRA TABLETS"),
+            facet(2, "PANADEINE TABLETS"),
+            facet(4, "NUROFEN 200MG CAPSULES"),
+            facet(6, "PANADOL 500MG CAPLETS"),
+        ])
+    }
+
+    #[test_case("ibuprofin", &["ibuprofen"])]
+    #[test_case("IBUPROFIN", &["ibuprofen"]; "ignoring case")]
+    #[test_case("ibuprofen", &[]; "when spelt correctly")]
+    #[test_case("fenoxymethylpenicilin", &["phenoxymethylpenicillin"]; "sounding alike")]
+    #[test_case("paracetmol tablest", &["paracetamol tablets"]; "in every word")]
+    #[test_case("panadoll", &["panadol"])]
+    #[test_case("capsles", &["capsules", "caplets"]; "with alternatives")]
+    #[test_case("zzzzzzzz", &[]; "with nothing close")]
+    #[test_case("product:panadoll 200mg", &["product:panadol 200mg"]; "keeping field names and numbers")]
+    #[test_case("\"paracetmol tablets\" -ibuprofin", &["\"paracetamol tablets\" -ibuprofen"]; "keeping query syntax")]
+    fn suggests_corrections(search_term: &str, expected: &[&str]) {
+        assert_eq!(given_a_vocabulary().did_you_mean(search_term, 5), expected);
+    }
+
+    #[test]
+    fn limits_suggestions() {
+        assert_eq!(
+            given_a_vocabulary().did_you_mean("capsles", 1),
+            vec!["capsules"]
+        );
+    }
+
+    #[test]
+    fn caches_vocabularies_per_index() {
+        let cache = VocabularyCache::for_index("https://service", "cached-index");
+        assert_eq!(cache.get(), None);
+
+        cache.set(given_a_vocabulary());
+        let cached = VocabularyCache::for_index("https://service", "cached-index").get();
+        assert_eq!(cached.as_deref(), Some(&given_a_vocabulary()));
+        assert_eq!(
+            VocabularyCache::for_index("https://service", "other-index").get(),
+            None
+        );
+    }
+
+    #[test]
+    fn counts_documents_for_each_word() {
+        let vocabulary = given_a_vocabulary();
+        assert_eq!(vocabulary.words.get("paracetamol"), Some(&31));
+        assert_eq!(vocabulary.words.get("tablets"), Some(&23));
+        assert!(!vocabulary.contains("200mg"));
+        assert!(!vocabulary.contains("i"));
+    }
+
+    #[test_case("ibuprofin", "ibuprofen", 2, Some(1))]
+    #[test_case("tablest", "tablets", 2, Some(1); "transposed")]
+    #[test_case("paracetmol", "paracetamol", 2, Some(1))]
+    #[test_case("panadol", "panadeine", 2, None)]
+    #[test_case("panadol", "panadeine", 4, Some(4))]
+    #[test_case("nurofen", "ibuprofen", 2, None)]
+    fn measures_edit_distance(a: &str, b: &str, max: usize, expected: Option<usize>) {
+        assert_eq!(edit_distance(a, b, max), expected);
+    }
+
+    #[test_case("ibuprofin", "ibuprofen")]
+    #[test_case("fenoxymethylpenicillin", "phenoxymethylpenicillin")]
+    #[test_case("ashcroft", "ascroft")]
+    fn sounds_alike(a: &str, b: &str) {
+        assert_eq!(phonetic_key(a), phonetic_key(b));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{filter::Filter, models::IndexResult, SearchError};
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+
+// Documents are walked in order of the key of the products index, which has to be filterable and sortable.
+pub(crate) const KEY_FIELD: &str = "metadata_storage_name";
+
+// Only one page of documents is held at a time. Azure Search returns at most 1000 results per request.
+pub const PAGE_SIZE: usize = 1000;
+
+#[async_trait]
+pub(crate) trait KeysetPages {
+    // The first `top` documents matching the filter with a key after `after`, in order of their keys.
+    async fn page_after(
+        &self,
+        after: Option<&str>,
+        filter: Option<&Filter>,
+        top: usize,
+    ) -> Result<Vec<IndexResult>, SearchError>;
+}
+
+// Every document matching the filter, fetched a page at a time by asking for the documents after the
+// last key of the previous page. Unlike `$skip`, this isn't capped and doesn't skip or repeat documents
+// when the index changes in the meantime, although documents added behind the last key are missed.
+pub(crate) fn stream_documents<C>(
+    client: &C,
+    filter: Option<Filter>,
+    page_size: usize,
+) -> BoxStream<'_, Result<IndexResult, SearchError>>
+where
+    C: KeysetPages + Sync,
+{
+    stream::try_unfold(Some(None), move |after: Option<Option<String>>| {
+        let filter = filter.clone();
+        async move {
+            let after = match after {
+                Some(after) => after,
+                None => return Ok(None),
+            };
+            let page = client
+                .page_after(after.as_deref(), filter.as_ref(), page_size)
+                .await?;
+            let next = if page.len() < page_size {
+                None
+            } else {
+                page.last()
+                    .map(|document| Some(document.metadata_storage_name.clone()))
+            };
+
+            Ok::<_, SearchError>(Some((page, next)))
+        }
+    })
+    .map_ok(|page| stream::iter(page.into_iter().map(Ok)))
+    .try_flatten()
+    .boxed()
+}
+
+// Restricts the filter to the documents after the given key.
+pub(crate) fn keyset_filter(after: Option<&str>, filter: Option<&Filter>) -> Option<Filter> {
+    match (after, filter) {
+        (Some(after), Some(filter)) => Some(Filter::and(vec![
+            filter.clone(),
+            Filter::gt(KEY_FIELD, after),
+        ])),
+        (Some(after), None) => Some(Filter::gt(KEY_FIELD, after)),
+        (None, filter) => filter.cloned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    struct TestPages {
+        keys: Vec<&'static str>,
+        requests: Mutex<Vec<Option<String>>>,
+    }
+
+    #[async_trait]
+    impl KeysetPages for TestPages {
+        async fn page_after(
+            &self,
+            after: Option<&str>,
+            filter: Option<&Filter>,
+            top: usize,
+        ) -> Result<Vec<IndexResult>, SearchError> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push(keyset_filter(after, filter).map(|filter| filter.to_string()));
+
+            Ok(self
+                .keys
+                .iter()
+                .filter(|&&key| match after {
+                    Some(after) => key > after,
+                    None => true,
+                })
+                .take(top)
+                .map(|key| given_a_document(key))
+                .collect())
+        }
+    }
+
+    fn given_a_document(key: &str) -> IndexResult {
+        serde_json::from_value(json!({
+            "doc_type": "Spc",
+            "territory": null,
+            "file_name": key,
+            "metadata_storage_name": key,
+            "metadata_storage_path": "",
+            "product_name": null,
+            "substance_name": [],
+            "title": "",
+            "created": null,
+            "facets": [],
+            "keywords": null,
+            "metadata_storage_size": 0,
+            "release_state": null,
+            "rev_label": null,
+            "suggestions": [],
+            "@search.highlights": null
+        }))
+        .unwrap()
+    }
+
+    fn given_pages(keys: Vec<&'static str>) -> TestPages {
+        TestPages {
+            keys,
+            requests: Mutex::new(vec![]),
+        }
+    }
+
+    fn when_we_stream_documents(pages: &TestPages, filter: Option<Filter>) -> Vec<String> {
+        let documents: Vec<IndexResult> =
+            tokio_test::block_on(stream_documents(pages, filter, 2).try_collect()).unwrap();
+        documents
+            .into_iter()
+            .map(|document| document.metadata_storage_name)
+            .collect()
+    }
+
+    #[test]
+    fn streams_every_document_a_page_at_a_time() {
+        let pages = given_pages(vec!["a", "b", "c", "d", "e"]);
+
+        let keys = when_we_stream_documents(&pages, Some(Filter::eq("doc_type", "Spc")));
+
+        assert_eq!(keys, vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(
+            *pages.requests.lock().unwrap(),
+            vec![
+                Some("doc_type eq 'Spc'".to_string()),
+                Some("(doc_type eq 'Spc' and metadata_storage_name gt 'b')".to_string()),
+                Some("(doc_type eq 'Spc' and metadata_storage_name gt 'd')".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn asks_for_another_page_after_a_full_one() {
+        let pages = given_pages(vec!["a", "b", "c", "d"]);
+
+        let keys = when_we_stream_documents(&pages, None);
+
+        assert_eq!(keys, vec!["a", "b", "c", "d"]);
+        assert_eq!(
+            *pages.requests.lock().unwrap(),
+            vec![
+                None,
+                Some("metadata_storage_name gt 'b'".to_string()),
+                Some("metadata_storage_name gt 'd'".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use std::{collections::HashMap, path::Path};
+
+// A synonym dictionary in the Solr format that Azure Search synonym maps use, with one rule per line:
+// `adrenaline, epinephrine` makes the terms equivalent, and `nurofen => nurofen, ibuprofen` expands
+// the terms on the left to those on the right. Terms are matched case-insensitively.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Synonyms {
+    expansions: HashMap<String, Vec<String>>,
+}
+
+impl Synonyms {
+    pub fn parse(rules: &str) -> Self {
+        let mut synonyms = Synonyms::default();
+        for rule in rules.lines().map(str::trim) {
+            if rule.is_empty() || rule.starts_with('#') {
+                continue;
+            }
+            match rule.find("=>") {
+                Some(arrow) => {
+                    let targets = terms(&rule[arrow + 2..]);
+                    for term in terms(&rule[..arrow]) {
+                        synonyms.add(&term, &targets);
+                    }
+                }
+                None => {
+                    let terms = terms(rule);
+                    for term in &terms {
+                        synonyms.add(term, &terms);
+                    }
+                }
+            }
+        }
+        synonyms
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        std::fs::read_to_string(path).map(|rules| Synonyms::parse(&rules))
+    }
+
+    // The other terms to also search for, which don't include the term itself.
+    pub fn expand(&self, term: &str) -> &[String] {
+        self.expansions
+            .get(&term.to_lowercase())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expansions.is_empty()
+    }
+
+    fn add(&mut self, term: &str, targets: &[String]) {
+        let expansions = self.expansions.entry(term.to_string()).or_default();
+        for target in targets {
+            if target != term && !expansions.contains(target) {
+                expansions.push(target.clone());
+            }
+        }
+    }
+}
+
+fn terms(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|term| term.trim().to_lowercase())
+        .filter(|term| !term.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_case::test_case;
+
+    fn given_some_synonyms() -> Synonyms {
+        Synonyms::parse(
+            "
+            # Comments and blank lines are ignored
+            paracetamol, Acetaminophen
+
+            nurofen, brufen => nurofen, brufen, ibuprofen
+            ascorbic acid, vitamin c
+            ",
+        )
+    }
+
+    #[test_case("paracetamol", &["acetaminophen"])]
+    #[test_case("ACETAMINOPHEN", &["paracetamol"])]
+    #[test_case("nurofen", &["brufen", "ibuprofen"])]
+    #[test_case("ibuprofen", &[]; "mappings only apply one way")]
+    #[test_case("vitamin c", &["ascorbic acid"])]
+    #[test_case("aspirin", &[])]
+    fn expands_terms(term: &str, expected: &[&str]) {
+        assert_eq!(given_some_synonyms().expand(term), expected);
+    }
+
+    #[test]
+    fn merges_rules_for_the_same_term() {
+        let synonyms =
+            Synonyms::parse("adrenaline, epinephrine\nadrenaline, adrenalin, epinephrine");
+        assert_eq!(synonyms.expand("adrenaline"), &["epinephrine", "adrenalin"]);
+    }
+}
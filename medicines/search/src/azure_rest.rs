@@ -37,6 +37,18 @@ pub async fn make_put_request_with_body(
     Ok(())
 }
 
+pub async fn make_get_request(url: &str, api_key: &str) -> Result<String, reqwest::Error> {
+    let client = reqwest::Client::new();
+    client
+        .get(url)
+        .header("api-key", api_key)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await
+}
+
 pub async fn make_post_request(url: &str, api_key: &str) -> Result<(), reqwest::Error> {
     let client = reqwest::Client::new();
     let _response = client
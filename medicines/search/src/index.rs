@@ -8,12 +8,8 @@ pub async fn create_or_update_index(index_definition: &str) -> Result<(), reqwes
     let search_service = get_from_env(SEARCH_SERVICE);
     let index_name = get_from_env(INDEX_NAME);
     let api_key = get_from_env(SEARCH_API_ADMIN_KEY);
-    let raw_index_definition;
-    match index_definition {
-        "bmgf" => raw_index_definition = get_bmgf_raw_index_definition(),
-        _ => raw_index_definition = get_default_raw_index_definition(),
-    }
-    let index_definition = get_index_definition(raw_index_definition, &index_name);
+    let index_definition =
+        get_index_definition(get_raw_index_definition(index_definition), &index_name);
     let mut url = Url::parse(&get_base_url(&search_service)).unwrap();
     url.set_path(&format!("{}/{}", url.path(), index_name));
 
@@ -40,6 +36,13 @@ fn get_resource_url(search_service: &str, index_name: &str) -> String {
         .replace("INDEX_NAME_PLACEHOLDER", index_name)
 }
 
+pub(crate) fn get_raw_index_definition(index_definition: &str) -> String {
+    match index_definition {
+        "bmgf" => get_bmgf_raw_index_definition(),
+        _ => get_default_raw_index_definition(),
+    }
+}
+
 fn get_default_raw_index_definition() -> String {
     include_str!("../definitions/indexes/default.json").to_string()
 }
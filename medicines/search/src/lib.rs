@@ -3,3 +3,4 @@ pub mod datasource;
 mod env;
 pub mod index;
 pub mod indexer;
+pub mod synonym_map;
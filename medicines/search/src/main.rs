@@ -2,7 +2,7 @@
 extern crate clap;
 
 use clap::App;
-use search::{datasource, index, indexer};
+use search::{datasource, index, indexer, synonym_map};
 
 #[tokio::main]
 async fn main() {
@@ -25,6 +25,12 @@ async fn main() {
         ("delete_index", Some(_m)) => index::delete_index()
             .await
             .expect("Failed to delete index."),
+        ("create_or_update_synonym_map", Some(m)) => {
+            let index_definition = m.value_of("index").unwrap_or("default");
+            synonym_map::create_or_update_synonym_map(m.value_of("file"), index_definition)
+                .await
+                .expect("Failed to create synonym map")
+        }
         ("create_indexer", Some(m)) => {
             let indexer_definition = m.value_of("indexer").unwrap_or("default");
             indexer::create_indexer(indexer_definition)
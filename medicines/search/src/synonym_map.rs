@@ -0,0 +1,180 @@
+use crate::{
+    azure_rest,
+    env::{get_from_env, INDEX_NAME, SEARCH_API_ADMIN_KEY, SEARCH_SERVICE},
+    index::get_raw_index_definition,
+};
+use reqwest::{StatusCode, Url};
+use serde_json::{json, Value};
+
+// The index definitions attach this synonym map to the fields that should be searched with synonyms.
+pub const SYNONYM_MAP_NAME: &str = "medicines-synonyms";
+
+pub async fn create_or_update_synonym_map(
+    synonyms_file: Option<&str>,
+    index_definition: &str,
+) -> Result<(), anyhow::Error> {
+    let search_service = get_from_env(SEARCH_SERVICE);
+    let api_key = get_from_env(SEARCH_API_ADMIN_KEY);
+    let index_name = get_from_env(INDEX_NAME);
+
+    let raw_synonyms = match synonyms_file {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => get_default_raw_synonyms(),
+    };
+    let synonym_map_definition = get_synonym_map_definition(&raw_synonyms);
+    let url = Url::parse(&get_resource_url(&search_service, SYNONYM_MAP_NAME))?;
+    azure_rest::make_put_request_with_body(synonym_map_definition, url, &api_key).await?;
+
+    // Attach the map to the existing index too, as changing a field's synonym maps doesn't need a rebuild.
+    // When the index is being rebuilt it doesn't exist yet, and will be created with the map attached.
+    let fields = get_synonym_fields(&get_raw_index_definition(index_definition))?;
+    let index_url = get_index_url(&search_service, &index_name);
+    let index = match azure_rest::make_get_request(&index_url, &api_key).await {
+        Ok(index) => index,
+        Err(e) if e.status() == Some(StatusCode::NOT_FOUND) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let index = attach_synonym_map(serde_json::from_str(&index)?, &fields);
+    azure_rest::make_put_request_with_body(index.to_string(), Url::parse(&index_url)?, &api_key)
+        .await?;
+
+    Ok(())
+}
+
+fn get_resource_url(search_service: &str, synonym_map_name: &str) -> String {
+    "https://SEARCH_SERVICE_PLACEHOLDER.search.windows.net/synonymmaps/SYNONYM_MAP_NAME_PLACEHOLDER?api-version=2019-05-06"
+        .replace("SEARCH_SERVICE_PLACEHOLDER", search_service)
+        .replace("SYNONYM_MAP_NAME_PLACEHOLDER", synonym_map_name)
+}
+
+fn get_index_url(search_service: &str, index_name: &str) -> String {
+    "https://SEARCH_SERVICE_PLACEHOLDER.search.windows.net/indexes/INDEX_NAME_PLACEHOLDER?api-version=2019-05-06"
+        .replace("SEARCH_SERVICE_PLACEHOLDER", search_service)
+        .replace("INDEX_NAME_PLACEHOLDER", index_name)
+}
+
+fn get_default_raw_synonyms() -> String {
+    include_str!("../definitions/synonyms/default.txt").to_string()
+}
+
+// The synonyms file has the Solr format Azure expects, plus blank lines and `#` comments which Azure doesn't accept.
+fn get_synonym_map_definition(raw_synonyms: &str) -> String {
+    let rules: Vec<&str> = raw_synonyms
+        .lines()
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty() && !rule.starts_with('#'))
+        .collect();
+
+    json!({
+        "name": SYNONYM_MAP_NAME,
+        "format": "solr",
+        "synonyms": rules.join("\n"),
+    })
+    .to_string()
+}
+
+fn get_synonym_fields(raw_index_definition: &str) -> Result<Vec<String>, serde_json::Error> {
+    let index_definition: Value = serde_json::from_str(raw_index_definition)?;
+    let fields = index_definition["fields"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter(|field| {
+            field["synonymMaps"]
+                .as_array()
+                .map(|maps| maps.iter().any(|map| map == SYNONYM_MAP_NAME))
+                .unwrap_or(false)
+        })
+        .filter_map(|field| field["name"].as_str().map(String::from))
+        .collect();
+    Ok(fields)
+}
+
+fn attach_synonym_map(mut index: Value, fields: &[String]) -> Value {
+    if let Value::Object(index) = &mut index {
+        index.retain(|key, _| !key.starts_with("@odata"));
+    }
+    if let Some(index_fields) = index["fields"].as_array_mut() {
+        for field in index_fields {
+            let is_synonym_field = field["name"]
+                .as_str()
+                .map(|name| fields.iter().any(|f| f == name))
+                .unwrap_or(false);
+            if is_synonym_field {
+                field["synonymMaps"] = json!([SYNONYM_MAP_NAME]);
+            }
+        }
+    }
+    index
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_resource_url() {
+        assert_eq!(
+            get_resource_url("service_name", "synonym_map_name"),
+            "https://service_name.search.windows.net/synonymmaps/synonym_map_name?api-version=2019-05-06"
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_get_synonym_map_definition() {
+        let raw_synonyms =
+            "# Substances\nadrenaline, epinephrine\n\n  nurofen => nurofen, ibuprofen  \n";
+
+        let definition: Value =
+            serde_json::from_str(&get_synonym_map_definition(raw_synonyms)).unwrap();
+
+        assert_eq!(
+            definition,
+            json!({
+                "name": "medicines-synonyms",
+                "format": "solr",
+                "synonyms": "adrenaline, epinephrine\nnurofen => nurofen, ibuprofen",
+            })
+        );
+    }
+
+    #[test]
+    fn test_get_synonym_fields() {
+        assert_eq!(
+            get_synonym_fields(&get_raw_index_definition("default")).unwrap(),
+            vec!["product_name", "substance_name"]
+        );
+        assert_eq!(
+            get_synonym_fields(&get_raw_index_definition("bmgf")).unwrap(),
+            vec!["products", "active_substances"]
+        );
+    }
+
+    #[test]
+    fn test_attach_synonym_map() {
+        let index = json!({
+            "@odata.context": "https://service_name.search.windows.net/$metadata#indexes/$entity",
+            "@odata.etag": "\"0x8D77267697666D4\"",
+            "name": "index_name",
+            "fields": [
+                { "name": "content", "synonymMaps": [] },
+                { "name": "substance_name", "synonymMaps": [] }
+            ]
+        });
+
+        let attached = attach_synonym_map(index, &["substance_name".to_string()]);
+
+        assert_eq!(
+            attached,
+            json!({
+                "name": "index_name",
+                "fields": [
+                    { "name": "content", "synonymMaps": [] },
+                    { "name": "substance_name", "synonymMaps": ["medicines-synonyms"] }
+                ]
+            })
+        );
+    }
+}
@@ -64,9 +64,9 @@ export interface ISearchInfo {
 }
 
 const query = `
-query($searchTerm: String, $first: Int, $after: String) {
+query($searchTerm: String, $first: Int, $skip: Int) {
   medicineLevelsInPregnancy {
-    reports(search: $searchTerm, first: $first, after: $after) {
+    reports(search: $searchTerm, first: $first, skip: $skip) {
       count: totalCount
       edges {
         node {
@@ -100,7 +100,7 @@ const getReportsForSearch = async ({
   const variables = {
     searchTerm,
     first: pageSize,
-    after: makeCursor(page, pageSize),
+    skip: calculatePageStartRecord(page, pageSize),
   };
   const { data } = await graphqlRequest<ISearchResponse, typeof variables>({
     query,
@@ -143,12 +143,6 @@ export const convertReportResponseToReport = ({
   };
 };
 
-export const makeCursor = (page: number, pageSize: number): string => {
-  const skip = calculatePageStartRecord(page, pageSize);
-
-  return Buffer.from((skip - 1).toString()).toString('base64');
-};
-
 export const calculatePageStartRecord = (
   page: number,
   pageSize: number,
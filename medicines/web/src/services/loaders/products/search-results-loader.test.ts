@@ -1,23 +1,23 @@
-import { makeCursor } from './search-results-loader';
+import { calculatePageStartRecord } from './search-results-loader';
 
-describe(makeCursor, () => {
-  it.each([2, 4, 5, 10, 15, 20])('offsets first result by -1', (pageSize) => {
-    const cursor = makeCursor(1, pageSize);
-    expect(cursor).toBe('LTE=');
+describe(calculatePageStartRecord, () => {
+  it.each([2, 4, 5, 10, 15, 20])('starts the first page at 0', (pageSize) => {
+    const skip = calculatePageStartRecord(1, pageSize);
+    expect(skip).toBe(0);
   });
 
   it.each([
-    [2, 10, 'OQ=='],
-    [3, 5, 'OQ=='],
-    [2, 2, 'MQ=='],
-    [3, 1, 'MQ=='],
-    [2, 3, 'Mg=='],
-    [4, 1, 'Mg=='],
+    [2, 10, 10],
+    [3, 5, 10],
+    [2, 2, 2],
+    [3, 1, 2],
+    [2, 3, 3],
+    [4, 1, 3],
   ])(
-    'page %i for pageSize %i results starts after %s',
-    (page, pageSize, expectedCursor) => {
-      const cursor = makeCursor(page, pageSize);
-      expect(cursor).toBe(expectedCursor);
+    'page %i for pageSize %i results starts at %i',
+    (page, pageSize, expectedSkip) => {
+      const skip = calculatePageStartRecord(page, pageSize);
+      expect(skip).toBe(expectedSkip);
     },
   );
 });
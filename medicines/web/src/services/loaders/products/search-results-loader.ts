@@ -64,9 +64,9 @@ interface IDocumentResponse {
 }
 
 const query = `
-query($searchTerm: String, $first: Int, $after: String, $documentTypes: [DocumentType!], $territoryTypes: [TerritoryType!]) {
+query($searchTerm: String, $first: Int, $skip: Int, $documentTypes: [DocumentType!], $territoryTypes: [TerritoryType!]) {
   products {
-    documents(search: $searchTerm, first: $first, after: $after, documentTypes: $documentTypes, territoryTypes: $territoryTypes) {
+    documents(search: $searchTerm, first: $first, skip: $skip, documentTypes: $documentTypes, territoryTypes: $territoryTypes) {
       count: totalCount
       edges {
         cursor
@@ -123,7 +123,7 @@ const getDocumentsForSearch = async ({
   const variables = {
     searchTerm,
     first: pageSize,
-    after: makeCursor(page, pageSize),
+    skip: calculatePageStartRecord(page, pageSize),
     documentTypes: docTypes.map((s) => s.toUpperCase()),
     territoryTypes: territoryTypes.map((t) => t.toUpperCase()),
   };
@@ -135,14 +135,10 @@ const getDocumentsForSearch = async ({
   return convertResponseToSearchPage(data);
 };
 
-export const makeCursor = (page: number, pageSize: number): string => {
-  const skip = calculatePageStartRecord(page, pageSize);
-
-  return Buffer.from((skip - 1).toString()).toString('base64');
-};
-
-const calculatePageStartRecord = (page: number, pageSize: number): number =>
-  pageSize * (page - 1);
+export const calculatePageStartRecord = (
+  page: number,
+  pageSize: number,
+): number => pageSize * (page - 1);
 
 export const graphqlSearchLoader = new DataLoader<ISearchInfo, IDocuments>(
   async (searchTerms) => {